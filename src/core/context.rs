@@ -1,7 +1,8 @@
 use super::*;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::sync::RwLock;
+use std::sync::{Arc, Weak};
 
 #[doc(hidden)]
 pub use crate::context::HasContext;
@@ -17,6 +18,9 @@ pub struct Context {
     pub(super) vao: crate::context::VertexArray,
     /// A cache of programs to avoid recompiling a [Program] every frame.
     pub programs: Arc<RwLock<HashMap<Vec<u8>, Program>>>,
+    /// A cache of resources, for example lookup tables, which are shared by everything using this context.
+    /// Only weak references are kept, since a resource can contain a context itself, for example in a texture.
+    resources: Arc<RwLock<HashMap<TypeId, Weak<dyn Any + Send + Sync>>>>,
 }
 
 impl Context {
@@ -44,11 +48,32 @@ impl Context {
                 context,
                 vao,
                 programs: Arc::new(RwLock::new(HashMap::new())),
+                resources: Arc::new(RwLock::new(HashMap::new())),
             }
         };
         Ok(c)
     }
 
+    ///
+    /// Returns the resource of the given type which is shared by everything using this context.
+    /// The resource is created using the given callback if it is not currently in use,
+    /// ie. the returned resource must be kept by the user of the resource and is dropped when no longer used.
+    ///
+    pub(crate) fn shared_resource<T: Any + Send + Sync>(
+        &self,
+        create: impl FnOnce(&Context) -> T,
+    ) -> Arc<T> {
+        let mut resources = self.resources.write().unwrap();
+        let id = TypeId::of::<T>();
+        if let Some(resource) = resources.get(&id).and_then(Weak::upgrade) {
+            return resource.downcast::<T>().unwrap();
+        }
+        let resource = Arc::new(create(self));
+        let weak = Arc::downgrade(&resource);
+        resources.insert(id, weak);
+        resource
+    }
+
     ///
     /// Set the scissor test for this context (see [ScissorBox]).
     ///
@@ -369,74 +369,70 @@ impl TextureCubeMap {
         front_data: &[T],
         back_data: &[T],
     ) {
-        check_data_length::<T>(
-            self.width,
-            self.height,
-            1,
-            self.data_byte_size,
-            right_data.len(),
-        );
-        check_data_length::<T>(
-            self.width,
-            self.height,
-            1,
-            self.data_byte_size,
-            left_data.len(),
-        );
-        check_data_length::<T>(
-            self.width,
-            self.height,
-            1,
-            self.data_byte_size,
-            top_data.len(),
-        );
-        check_data_length::<T>(
-            self.width,
-            self.height,
-            1,
-            self.data_byte_size,
-            bottom_data.len(),
-        );
-        check_data_length::<T>(
-            self.width,
-            self.height,
-            1,
-            self.data_byte_size,
-            front_data.len(),
-        );
-        check_data_length::<T>(
-            self.width,
-            self.height,
-            1,
-            self.data_byte_size,
-            back_data.len(),
+        self.fill_mip_level(
+            0,
+            right_data,
+            left_data,
+            top_data,
+            bottom_data,
+            front_data,
+            back_data,
         );
+        self.generate_mip_maps();
+    }
+
+    ///
+    /// Fills the given mip level of the cube map texture with the given pixel data for the 6 images.
+    /// In contrast to [TextureCubeMap::fill], mip maps are not generated, so this can be used to fill each mip level with precomputed data.
+    ///
+    /// # Panic
+    /// Will panic if the mip level does not exist or if the length of the data for all 6 images does not correspond to the size of the mip level and the format specified at construction.
+    ///
+    pub fn fill_mip_level<T: TextureDataType>(
+        &mut self,
+        mip_level: u32,
+        right_data: &[T],
+        left_data: &[T],
+        top_data: &[T],
+        bottom_data: &[T],
+        front_data: &[T],
+        back_data: &[T],
+    ) {
+        if mip_level >= self.number_of_mip_maps {
+            panic!(
+                "mip level {} does not exist, the texture only has {} mip levels",
+                mip_level, self.number_of_mip_maps
+            );
+        }
+        let width = (self.width >> mip_level).max(1);
+        let height = (self.height >> mip_level).max(1);
+        let data = [
+            right_data,
+            left_data,
+            top_data,
+            bottom_data,
+            front_data,
+            back_data,
+        ];
+        for side_data in data {
+            check_data_length::<T>(width, height, 1, self.data_byte_size, side_data.len());
+        }
         self.bind();
-        for i in 0..6 {
-            let data = match i {
-                0 => right_data,
-                1 => left_data,
-                2 => top_data,
-                3 => bottom_data,
-                4 => front_data,
-                5 => back_data,
-                _ => unreachable!(),
-            };
+        for (i, side_data) in data.into_iter().enumerate() {
             unsafe {
                 self.context.tex_sub_image_2d(
                     crate::context::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+                    mip_level as i32,
                     0,
                     0,
-                    0,
-                    self.width as i32,
-                    self.height as i32,
+                    width as i32,
+                    height as i32,
                     format_from_data_type::<T>(),
                     T::data_type(),
-                    crate::context::PixelUnpackData::Slice(to_byte_slice(data)),
+                    crate::context::PixelUnpackData::Slice(to_byte_slice(side_data)),
                 );
             }
         }
-        self.generate_mip_maps();
     }
 
     ///
@@ -29,6 +29,12 @@ pub enum RendererError {
     InvalidBufferLength(String, usize, usize),
     #[error("the material {0} is required by the geometry {1} but could not be found")]
     MissingMaterial(String, String),
+    #[error("invalid IES profile: {0}")]
+    InvalidIesProfile(String),
+    #[error("invalid environment: {0}")]
+    InvalidEnvironment(String),
+    #[error("failed to read or write file: {0}")]
+    Io(#[from] std::io::Error),
     #[cfg(feature = "text")]
     #[error("Failed to find font with index {0} in the given font collection")]
     MissingFont(u32),
@@ -69,6 +75,9 @@ macro_rules! impl_render_target_extensions_body {
         /// Render the objects using the given camera and lights into this render target.
         /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
         /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+        /// Finally, each object is only rendered with the lights that affects it, see [Light::affects].
+        /// If any of the objects are transmissive (see [MaterialType::Transmissive]), the opaque objects are first rendered into a [TransmissionBackground]
+        /// which is given to the transmissive objects as an extra light.
         ///
         pub fn render(
             &self,
@@ -83,6 +92,9 @@ macro_rules! impl_render_target_extensions_body {
         /// Render the objects using the given camera and lights into the part of this render target defined by the scissor box.
         /// Use an empty array for the `lights` argument, if the objects does not require lights to be rendered.
         /// Also, objects outside the camera frustum are not rendered and the objects are rendered in the order given by [cmp_render_order].
+        /// Finally, each object is only rendered with the lights that affects it, see [Light::affects].
+        /// If any of the objects are transmissive (see [MaterialType::Transmissive]), the opaque objects inside the scissor box are first rendered into a new [TransmissionBackground]
+        /// which is given to the transmissive objects as an extra light.
         ///
         pub fn render_partially(
             &self,
@@ -96,6 +108,28 @@ macro_rules! impl_render_target_extensions_body {
                 .filter(|o| camera.in_frustum(&o.aabb()))
                 .partition(|o| o.material_type() == MaterialType::Deferred);
 
+            // Transmission background, which is the opaque objects seen through the transmissive objects
+            let transmission_background = if forward_objects
+                .iter()
+                .any(|o| o.material_type() == MaterialType::Transmissive)
+            {
+                let opaque_objects = deferred_objects
+                    .iter()
+                    .chain(
+                        forward_objects
+                            .iter()
+                            .filter(|o| o.material_type() == MaterialType::Opaque),
+                    )
+                    .map(|o| o as &dyn Object)
+                    .collect::<Vec<_>>();
+                let mut background =
+                    TransmissionBackground::new_empty(&self.context, camera.viewport());
+                background.update_partially(scissor_box, camera, &opaque_objects, lights);
+                Some(background)
+            } else {
+                None
+            };
+
             // Deferred
             if deferred_objects.len() > 0 {
                 // Geometry pass
@@ -104,11 +138,20 @@ macro_rules! impl_render_target_extensions_body {
                     Viewport::new_at_origo(camera.viewport().width, camera.viewport().height);
                 geometry_pass_camera.set_viewport(viewport);
                 deferred_objects.sort_by(|a, b| cmp_render_order(&geometry_pass_camera, a, b));
+                // The fourth layer is only needed if a material uses the material extensions
+                let gbuffer_layers: &[u32] = if deferred_objects
+                    .iter()
+                    .any(|o| o.uses_material_extensions())
+                {
+                    &[0, 1, 2, 3]
+                } else {
+                    &[0, 1, 2]
+                };
                 let mut geometry_pass_texture = Texture2DArray::new_empty::<[u8; 4]>(
                     &self.context,
                     viewport.width,
                     viewport.height,
-                    3,
+                    gbuffer_layers.len() as u32,
                     Interpolation::Nearest,
                     Interpolation::Nearest,
                     None,
@@ -122,29 +165,38 @@ macro_rules! impl_render_target_extensions_body {
                     Wrapping::ClampToEdge,
                     Wrapping::ClampToEdge,
                 );
-                let gbuffer_layers = [0, 1, 2];
                 RenderTarget::new(
-                    geometry_pass_texture.as_color_target(&gbuffer_layers, None),
+                    geometry_pass_texture.as_color_target(gbuffer_layers, None),
                     geometry_pass_depth_texture.as_depth_target(),
                 )
                 .clear(ClearState::default())
                 .write::<RendererError>(|| {
-                    for object in deferred_objects {
-                        object.render(&geometry_pass_camera, lights);
+                    for object in deferred_objects.iter() {
+                        object.render(
+                            &geometry_pass_camera,
+                            &affecting_lights(lights, &object.aabb()),
+                        );
                     }
                     Ok(())
                 })
                 .unwrap();
 
-                // Lighting pass
+                // Lighting pass, which shades all the deferred objects at once and therefore uses the lights affecting any of them
+                let mut aabb = AxisAlignedBoundingBox::EMPTY;
+                for object in deferred_objects.iter() {
+                    aabb.expand_with_aabb(&object.aabb());
+                }
+                let lights = affecting_lights(lights, &aabb);
                 self.apply_screen_effect_partially(
                     scissor_box,
-                    &lighting_pass::LightingPassEffect {},
+                    &lighting_pass::LightingPassEffect {
+                        contact_shadows: None,
+                    },
                     camera,
-                    lights,
+                    &lights,
                     Some(ColorTexture::Array {
                         texture: &geometry_pass_texture,
-                        layers: &gbuffer_layers,
+                        layers: gbuffer_layers,
                     }),
                     Some(DepthTexture::Single(&geometry_pass_depth_texture)),
                 );
@@ -154,7 +206,13 @@ macro_rules! impl_render_target_extensions_body {
             forward_objects.sort_by(|a, b| cmp_render_order(camera, a, b));
             self.write_partially::<RendererError>(scissor_box, || {
                 for object in forward_objects {
-                    object.render(camera, lights);
+                    let mut lights = affecting_lights(lights, &object.aabb());
+                    if object.material_type() == MaterialType::Transmissive {
+                        if let Some(background) = &transmission_background {
+                            lights.push(background);
+                        }
+                    }
+                    object.render(camera, &lights);
                 }
                 Ok(())
             })
@@ -165,6 +223,7 @@ macro_rules! impl_render_target_extensions_body {
         ///
         /// Render the geometries with the given [Material] using the given camera and lights into this render target.
         /// Use an empty array for the `lights` argument, if the material does not require lights to be rendered.
+        /// Each geometry is only rendered with the lights that affects it, see [Light::affects].
         ///
         pub fn render_with_material(
             &self,
@@ -185,6 +244,7 @@ macro_rules! impl_render_target_extensions_body {
         ///
         /// Render the geometries with the given [Material] using the given camera and lights into the part of this render target defined by the scissor box.
         /// Use an empty array for the `lights` argument, if the material does not require lights to be rendered.
+        /// Each geometry is only rendered with the lights that affects it, see [Light::affects].
         ///
         pub fn render_partially_with_material(
             &self,
@@ -199,7 +259,8 @@ macro_rules! impl_render_target_extensions_body {
                     .into_iter()
                     .filter(|o| camera.in_frustum(&o.aabb()))
                 {
-                    render_with_material(&self.context, camera, geometry, material, lights);
+                    let lights = affecting_lights(lights, &geometry.aabb());
+                    render_with_material(&self.context, camera, geometry, material, &lights);
                 }
                 Ok(())
             })
@@ -210,6 +271,7 @@ macro_rules! impl_render_target_extensions_body {
         ///
         /// Render the geometries with the given [Effect] using the given camera and lights into this render target.
         /// Use an empty array for the `lights` argument, if the effect does not require lights to be rendered.
+        /// Each geometry is only rendered with the lights that affects it, see [Light::affects].
         ///
         pub fn render_with_effect(
             &self,
@@ -234,6 +296,7 @@ macro_rules! impl_render_target_extensions_body {
         ///
         /// Render the geometries with the given [Effect] using the given camera and lights into the part of this render target defined by the scissor box.
         /// Use an empty array for the `lights` argument, if the effect does not require lights to be rendered.
+        /// Each geometry is only rendered with the lights that affects it, see [Light::affects].
         ///
         pub fn render_partially_with_effect(
             &self,
@@ -250,12 +313,13 @@ macro_rules! impl_render_target_extensions_body {
                     .into_iter()
                     .filter(|o| camera.in_frustum(&o.aabb()))
                 {
+                    let lights = affecting_lights(lights, &geometry.aabb());
                     render_with_effect(
                         &self.context,
                         camera,
                         geometry,
                         effect,
-                        lights,
+                        &lights,
                         color_texture,
                         depth_texture,
                     );
@@ -386,6 +450,16 @@ impl_render_target_extensions!(
 impl_render_target_extensions!(ColorTargetMultisample<C: TextureDataType>);
 impl_render_target_extensions!(DepthTargetMultisample<D: DepthTextureDataType>);
 
+///
+/// Returns the lights that affects the given axis aligned bounding box, see [Light::affects].
+///
+fn affecting_lights<'a>(
+    lights: &[&'a dyn Light],
+    aabb: &AxisAlignedBoundingBox,
+) -> Vec<&'a dyn Light> {
+    lights.iter().copied().filter(|l| l.affects(aabb)).collect()
+}
+
 ///
 /// Combines shader ID components together into a single ID vector, to be used as a key in shader caching.
 ///
@@ -400,11 +474,31 @@ fn combine_ids(
     return id;
 }
 
+///
+/// Combines the shader ID components of a [Material] rendered on a geometry with the given id into a single ID vector, see [combine_ids].
+///
+fn combine_material_ids(
+    geometry: GeometryId,
+    material: &impl Material,
+    lights: &[&dyn Light],
+) -> Vec<u8> {
+    let mut id = combine_ids(geometry, material.id(), lights.iter().map(|l| l.id()));
+    if let Some(source_id) = material.source_id() {
+        // The marker separates the source id from the light ids, since 0xFE is not a valid light id
+        id.push(0xFE);
+        id.extend(source_id.to_le_bytes());
+    }
+    id
+}
+
 ///
 /// Render the given [Geometry] with the given [Material].
 /// Must be called in the callback given as input to a [RenderTarget], [ColorTarget] or [DepthTarget] write method.
 /// Use an empty array for the `lights` argument, if the material does not require lights to be rendered.
 ///
+/// # Panic
+/// Will panic if the material has a [VertexDeformation] and the geometry does not support vertex deformation, see [VertexDeformation].
+///
 pub fn render_with_material(
     context: &Context,
     camera: &Camera,
@@ -412,23 +506,45 @@ pub fn render_with_material(
     material: impl Material,
     lights: &[&dyn Light],
 ) {
-    let fragment_attributes = material.fragment_attributes();
-    let id = combine_ids(
-        geometry.id(fragment_attributes),
-        material.id(),
-        lights.iter().map(|l| l.id()),
-    );
+    let vertex_deformation = material.vertex_deformation();
+    // The attributes used by the vertex deformation are also required in the vertex shader
+    let deformation_attributes =
+        VertexDeformation::fragment_attributes(vertex_deformation.as_ref());
+    let mut fragment_attributes = material.fragment_attributes();
+    fragment_attributes.normal |= deformation_attributes.normal;
+    fragment_attributes.uv |= deformation_attributes.uv;
+    let mut id = combine_material_ids(geometry.id(fragment_attributes), &material, lights);
+    if let Some(ref vertex_deformation) = vertex_deformation {
+        // The marker separates the deformation from the light ids, since 0xFF is not a valid light id
+        id.push(0xFF);
+        id.extend(vertex_deformation.id().to_le_bytes());
+    }
 
     let mut programs = context.programs.write().unwrap();
     let program = programs.entry(id).or_insert_with(|| {
+        let vertex_shader_source = geometry.vertex_shader_source(fragment_attributes);
         Program::from_source(
             context,
-            &geometry.vertex_shader_source(fragment_attributes),
+            &if let Some(ref vertex_deformation) = vertex_deformation {
+                if !vertex_shader_source.contains("USE_VERTEX_DEFORMATION") {
+                    panic!("The geometry does not support vertex deformation, see VertexDeformation for the geometries which do");
+                }
+                format!(
+                    "#define USE_VERTEX_DEFORMATION\n{}\n{}",
+                    vertex_shader_source,
+                    vertex_deformation.vertex_shader_source()
+                )
+            } else {
+                vertex_shader_source
+            },
             &material.fragment_shader_source(lights),
         )
         .expect("Failed compiling shader")
     });
     material.use_uniforms(program, camera, lights);
+    if let Some(ref vertex_deformation) = vertex_deformation {
+        vertex_deformation.use_uniforms(program);
+    }
     geometry.draw(
         camera,
         program,
@@ -486,11 +602,7 @@ pub fn apply_screen_material(
     if fragment_attributes.normal || fragment_attributes.position || fragment_attributes.tangents {
         panic!("Not possible to use the given material to render full screen, the full screen geometry only provides uv coordinates and color");
     }
-    let id = combine_ids(
-        GeometryId::Screen,
-        material.id(),
-        lights.iter().map(|l| l.id()),
-    );
+    let id = combine_material_ids(GeometryId::Screen, &material, lights);
 
     let mut programs = context.programs.write().unwrap();
     let program = programs.entry(id).or_insert_with(|| {
@@ -549,27 +661,28 @@ pub fn apply_screen_effect(
 ///
 /// Compare function for sorting objects based on distance from the camera.
 /// The order is opaque objects from nearest to farthest away from the camera,
-/// then transparent objects from farthest away to closest to the camera.
+/// then transmissive objects and then transparent objects, both from farthest away to closest to the camera.
 ///
 pub fn cmp_render_order(
     camera: &Camera,
     obj0: impl Object,
     obj1: impl Object,
 ) -> std::cmp::Ordering {
-    if obj0.material_type() == MaterialType::Transparent
-        && obj1.material_type() != MaterialType::Transparent
-    {
-        std::cmp::Ordering::Greater
-    } else if obj0.material_type() != MaterialType::Transparent
-        && obj1.material_type() == MaterialType::Transparent
-    {
-        std::cmp::Ordering::Less
+    // The rank of each material type in the render order
+    let rank = |material_type: MaterialType| match material_type {
+        MaterialType::Transmissive => 1,
+        MaterialType::Transparent => 2,
+        _ => 0,
+    };
+    let (rank0, rank1) = (rank(obj0.material_type()), rank(obj1.material_type()));
+    if rank0 != rank1 {
+        rank0.cmp(&rank1)
     } else {
         let distance_a = camera.position().distance2(obj0.aabb().center());
         let distance_b = camera.position().distance2(obj1.aabb().center());
         if distance_a.is_nan() || distance_b.is_nan() {
             distance_a.is_nan().cmp(&distance_b.is_nan()) // whatever - just save us from panicing on unwrap below
-        } else if obj0.material_type() == MaterialType::Transparent {
+        } else if rank0 > 0 {
             distance_b.partial_cmp(&distance_a).unwrap()
         } else {
             distance_a.partial_cmp(&distance_b).unwrap()
@@ -669,6 +782,8 @@ pub fn ray_intersect(
     .write::<RendererError>(|| {
         for (id, geometry) in geometries.into_iter().enumerate() {
             material.geometry_id = id as u32;
+            material.alpha_mask = geometry.alpha_mask();
+            material.vertex_deformation = geometry.vertex_deformation();
             render_with_material(context, &camera, &geometry, &material, &[]);
         }
         Ok(())
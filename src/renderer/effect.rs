@@ -48,6 +48,18 @@ mod fog;
 #[doc(inline)]
 pub use fog::*;
 
+mod contact_shadow;
+#[doc(inline)]
+pub use contact_shadow::*;
+
+mod outline;
+#[doc(inline)]
+pub use outline::*;
+
+mod volumetric_light;
+#[doc(inline)]
+pub use volumetric_light::*;
+
 mod copy;
 #[doc(inline)]
 pub use copy::*;
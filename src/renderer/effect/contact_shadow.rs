@@ -0,0 +1,153 @@
+use crate::renderer::*;
+
+///
+/// An effect that adds the small shadows where objects are in contact with each other, for example where a small object touches the ground, which are often missing from shadow maps due to the limited resolution.
+/// The shadows are found by marching a short distance from each pixel towards the lights in the depth texture.
+/// Only the lights which support it casts contact shadows, see [Light::incoming_light_shader_source].
+///
+/// When applied as an effect after forward rendering, the color in each pixel is darkened by the fraction of the light from these lights which is blocked,
+/// so since the effect does not know how much of the color is from other sources, for example an [AmbientLight], the shadows might be too dark.
+/// Use the [ContactShadowEffect::intensity] to adjust for that.
+/// When using deferred rendering, the contact shadows are instead applied to each light in the lighting pass, see [DeferredPhysicalMaterial::lighting_pass_with_contact_shadows].
+///
+#[derive(Clone, Debug)]
+pub struct ContactShadowEffect {
+    /// The distance towards the lights in which objects cast contact shadows.
+    pub distance: f32,
+    /// The assumed thickness of the objects in the depth texture, ie. a point is in shadow if it is behind an object but less than this distance behind.
+    pub thickness: f32,
+    /// The fraction of the light which is blocked in a contact shadow.
+    pub intensity: f32,
+    /// The number of steps towards each light. More steps gives more accurate shadows but is more expensive.
+    pub step_count: u32,
+}
+
+impl Default for ContactShadowEffect {
+    fn default() -> Self {
+        Self {
+            distance: 0.2,
+            thickness: 0.1,
+            intensity: 1.0,
+            step_count: 16,
+        }
+    }
+}
+
+impl ContactShadowEffect {
+    ///
+    /// Sends the uniform data needed for the `contact_shadow` function in `shaders/contact_shadow.frag`.
+    ///
+    pub(super) fn use_contact_shadow_uniforms(&self, program: &Program, camera: &Camera) {
+        program.use_uniform_if_required("viewProjection", camera.projection() * camera.view());
+        program.use_uniform_if_required("contactShadowDistance", self.distance);
+        program.use_uniform_if_required("contactShadowThickness", self.thickness);
+        program.use_uniform_if_required("contactShadowIntensity", self.intensity);
+        program.use_uniform_if_required("contactShadowStepCount", self.step_count.max(1) as i32);
+    }
+}
+
+impl Effect for ContactShadowEffect {
+    fn fragment_shader_source(
+        &self,
+        lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) -> String {
+        let (mut source, incoming_lights) = incoming_lights_shader_source(lights);
+        source.push_str(&format!(
+            "{}\n{}\n{}\n{}\n",
+            color_texture
+                .expect("Must supply a color texture to apply a contact shadow effect")
+                .fragment_shader_source(),
+            depth_texture
+                .expect("Must supply a depth texture to apply a contact shadow effect")
+                .fragment_shader_source(),
+            ToneMapping::fragment_shader_source(),
+            ColorMapping::fragment_shader_source(),
+        ));
+        source.push_str("uniform mat4 viewProjectionInverse;\nuniform vec3 eyePosition;\n");
+        source.push_str(include_str!("shaders/contact_shadow.frag"));
+        let light_sum = (0..incoming_lights.len())
+            .map(|i| format!(
+                "
+                    irradiance = dot(calculate_incoming_light{i}(position, normal, light_direction, visibility), vec3(0.2126, 0.7152, 0.0722))
+                        * max(dot(normal, light_direction), 0.0);
+                    total += irradiance;
+                    if (irradiance * visibility > 0.0) {{
+                        blocked += irradiance * visibility * (1.0 - contact_shadow(eyePosition, position, light_direction));
+                    }}
+                "
+            ))
+            .collect::<String>();
+        source.push_str(&format!(
+            "
+                float calculate_contact_shadow(vec3 position, vec3 normal)
+                {{
+                    float total = 0.0;
+                    float blocked = 0.0;
+                    float irradiance;
+                    vec3 light_direction;
+                    float visibility;
+                    {light_sum}
+                    return total > 0.0 ? blocked / total : 0.0;
+                }}
+            "
+        ));
+        source.push_str(include_str!("shaders/contact_shadow_effect.frag"));
+        source
+    }
+
+    fn id(
+        &self,
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) -> EffectMaterialId {
+        EffectMaterialId::ContactShadowEffect(
+            color_texture.expect("Must supply a color texture to apply a contact shadow effect"),
+            depth_texture.expect("Must supply a depth texture to apply a contact shadow effect"),
+        )
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            uv: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(
+        &self,
+        program: &Program,
+        camera: &Camera,
+        lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) {
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        color_texture
+            .expect("Must supply a color texture to apply a contact shadow effect")
+            .use_uniforms(program);
+        depth_texture
+            .expect("Must supply a depth texture to apply a contact shadow effect")
+            .use_uniforms(program);
+        program.use_uniform(
+            "viewProjectionInverse",
+            (camera.projection() * camera.view()).invert().unwrap(),
+        );
+        program.use_uniform_if_required("eyePosition", camera.position());
+        self.use_contact_shadow_uniforms(program, camera);
+        let (_, incoming_lights) = incoming_lights_shader_source(lights);
+        for (i, light) in incoming_lights.iter().enumerate() {
+            light.use_uniforms(program, i as u32);
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        RenderStates {
+            depth_test: DepthTest::Always,
+            cull: Cull::Back,
+            ..Default::default()
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::renderer::*;
 
-pub struct LightingPassEffect {}
+pub struct LightingPassEffect {
+    /// Contact shadows applied to each light which supports it, see [ContactShadowEffect].
+    pub contact_shadows: Option<ContactShadowEffect>,
+}
 
 impl Effect for LightingPassEffect {
     fn fragment_shader_source(
@@ -9,17 +12,61 @@ impl Effect for LightingPassEffect {
         color_texture: Option<ColorTexture>,
         depth_texture: Option<DepthTexture>,
     ) -> String {
-        let mut fragment_shader = lights_shader_source(
-            lights,
-            LightingModel::Cook(
-                NormalDistributionFunction::TrowbridgeReitzGGX,
-                GeometryFunction::SmithSchlickGGX,
-            ),
+        let lighting_model = LightingModel::Cook(
+            NormalDistributionFunction::TrowbridgeReitzGGX,
+            GeometryFunction::SmithSchlickGGX,
         );
+        // The material extensions must be defined before the lights since they change the lighting calculations
+        let mut fragment_shader = if has_material_extensions(color_texture.as_ref().unwrap()) {
+            "#define USE_CLEARCOAT\n#define USE_SHEEN\n#define USE_SPECULAR\n".to_string()
+        } else {
+            String::new()
+        };
+        if self.contact_shadows.is_some() {
+            // The lighting calculations needs the depth texture for the contact shadows, so the lights are added after the textures
+            fragment_shader.push_str(lighting_model_shader(lighting_model));
+            fragment_shader.push_str(include_str!("../../core/shared.frag"));
+            fragment_shader.push_str(include_str!("../light/shaders/light_shared.frag"));
+        } else {
+            fragment_shader.push_str(&lights_shader_source(lights, lighting_model));
+        }
         fragment_shader.push_str(&color_texture.unwrap().fragment_shader_source());
         fragment_shader.push_str(&depth_texture.unwrap().fragment_shader_source());
         fragment_shader.push_str(ToneMapping::fragment_shader_source());
         fragment_shader.push_str(ColorMapping::fragment_shader_source());
+        fragment_shader.push_str("uniform mat4 viewProjectionInverse;\n");
+        if self.contact_shadows.is_some() {
+            fragment_shader.push_str(include_str!("shaders/contact_shadow.frag"));
+            let mut dir_fun = String::new();
+            for (i, light) in lights.iter().enumerate() {
+                fragment_shader.push_str(&light.shader_source(i as u32));
+                let lighting = format!("calculate_lighting{i}(surface_color, position, normal, view_direction, metallic, roughness, occlusion)");
+                if let Some(light_source) = light.incoming_light_shader_source(i as u32) {
+                    fragment_shader.push_str(&light_source);
+                    dir_fun.push_str(&format!(
+                        "
+                            calculate_incoming_light{i}(position, normal, light_direction, visibility);
+                            color += {lighting} * contact_shadow(camera_position, position, light_direction);
+                        "
+                    ));
+                } else {
+                    dir_fun.push_str(&format!("color += {lighting};\n"));
+                }
+            }
+            fragment_shader.push_str(&format!(
+                "
+                    vec3 calculate_lighting(vec3 camera_position, vec3 surface_color, vec3 position, vec3 normal, float metallic, float roughness, float occlusion)
+                    {{
+                        vec3 color = vec3(0.0, 0.0, 0.0);
+                        vec3 view_direction = normalize(camera_position - position);
+                        vec3 light_direction;
+                        float visibility;
+                        {dir_fun}
+                        return color;
+                    }}
+                "
+            ));
+        }
         fragment_shader.push_str(include_str!("shaders/deferred_lighting.frag"));
         fragment_shader
     }
@@ -29,7 +76,13 @@ impl Effect for LightingPassEffect {
         color_texture: Option<ColorTexture>,
         depth_texture: Option<DepthTexture>,
     ) -> EffectMaterialId {
-        EffectMaterialId::LightingPassEffect(color_texture.unwrap(), depth_texture.unwrap())
+        let color_texture = color_texture.unwrap();
+        EffectMaterialId::LightingPassEffect(
+            color_texture,
+            depth_texture.unwrap(),
+            self.contact_shadows.is_some(),
+            has_material_extensions(&color_texture),
+        )
     }
 
     fn fragment_attributes(&self) -> FragmentAttributes {
@@ -60,6 +113,9 @@ impl Effect for LightingPassEffect {
             (camera.projection() * camera.view()).invert().unwrap(),
         );
         program.use_uniform("debug_type", DebugType::None as i32);
+        if let Some(contact_shadows) = &self.contact_shadows {
+            contact_shadows.use_contact_shadow_uniforms(program, camera);
+        }
     }
 
     fn render_states(&self) -> RenderStates {
@@ -71,6 +127,13 @@ impl Effect for LightingPassEffect {
     }
 }
 
+///
+/// Whether the geometry pass contains the fourth layer with the material extensions, see [DeferredPhysicalMaterial].
+///
+fn has_material_extensions(color_texture: &ColorTexture) -> bool {
+    matches!(color_texture, ColorTexture::Array { layers, .. } if layers.len() > 3)
+}
+
 ///
 /// Used for debug purposes - only internal.
 ///
@@ -0,0 +1,109 @@
+use crate::renderer::*;
+
+///
+/// An effect that draws outlines around objects, for example to achieve a cartoon style together with a [ToonMaterial].
+/// The outlines are found in screen space from the depth texture, so an outline is drawn where the distance to the camera changes abruptly, ie. along the silhouettes of the objects,
+/// and where the surface normal changes more than the [OutlineEffect::crease_angle], for example along the sharp edges of a cube.
+///
+#[derive(Clone, Debug)]
+pub struct OutlineEffect {
+    /// The color of the outlines.
+    pub color: Srgba,
+    /// The thickness of the outlines in pixels.
+    pub thickness: f32,
+    /// The change in distance to the camera, relative to the distance, above which an outline is drawn.
+    pub depth_threshold: f32,
+    /// The angle between surface normals above which an outline is drawn.
+    pub crease_angle: Radians,
+}
+
+impl Default for OutlineEffect {
+    fn default() -> Self {
+        Self {
+            color: Srgba::BLACK,
+            thickness: 1.0,
+            depth_threshold: 0.05,
+            crease_angle: degrees(60.0).into(),
+        }
+    }
+}
+
+impl Effect for OutlineEffect {
+    fn fragment_shader_source(
+        &self,
+        _lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) -> String {
+        format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            include_str!("../../core/shared.frag"),
+            color_texture
+                .expect("Must supply a color texture to apply an outline effect")
+                .fragment_shader_source(),
+            depth_texture
+                .expect("Must supply a depth texture to apply an outline effect")
+                .fragment_shader_source(),
+            ToneMapping::fragment_shader_source(),
+            ColorMapping::fragment_shader_source(),
+            include_str!("shaders/outline_effect.frag")
+        )
+    }
+
+    fn id(
+        &self,
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) -> EffectMaterialId {
+        EffectMaterialId::OutlineEffect(
+            color_texture.expect("Must supply a color texture to apply an outline effect"),
+            depth_texture.expect("Must supply a depth texture to apply an outline effect"),
+        )
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            uv: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(
+        &self,
+        program: &Program,
+        camera: &Camera,
+        _lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) {
+        let color_texture =
+            color_texture.expect("Must supply a color texture to apply an outline effect");
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        color_texture.use_uniforms(program);
+        depth_texture
+            .expect("Must supply a depth texture to apply an outline effect")
+            .use_uniforms(program);
+        program.use_uniform(
+            "resolution",
+            vec2(color_texture.width() as f32, color_texture.height() as f32),
+        );
+        program.use_uniform(
+            "viewProjectionInverse",
+            (camera.projection() * camera.view()).invert().unwrap(),
+        );
+        program.use_uniform("eyePosition", camera.position());
+        program.use_uniform("outlineColor", self.color.to_linear_srgb());
+        program.use_uniform("thickness", self.thickness);
+        program.use_uniform("depthThreshold", self.depth_threshold);
+        program.use_uniform("creaseCosine", self.crease_angle.cos());
+    }
+
+    fn render_states(&self) -> RenderStates {
+        RenderStates {
+            depth_test: DepthTest::Always,
+            cull: Cull::Back,
+            ..Default::default()
+        }
+    }
+}
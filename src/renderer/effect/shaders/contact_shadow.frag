@@ -0,0 +1,30 @@
+
+uniform mat4 viewProjection;
+uniform float contactShadowDistance;
+uniform float contactShadowThickness;
+uniform float contactShadowIntensity;
+uniform int contactShadowStepCount;
+
+// Returns the fraction of the light from the given direction which is not blocked by the geometry in the depth texture within a short distance from the given position.
+// Requires that the depth texture and the viewProjectionInverse uniform are defined.
+float contact_shadow(vec3 camera_position, vec3 position, vec3 light_direction)
+{
+    float step_length = contactShadowDistance / float(contactShadowStepCount);
+    // A per pixel offset to turn banding into noise
+    float offset = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    float bias = 0.005 * distance(camera_position, position);
+    for (int i = 0; i < contactShadowStepCount; i++) {
+        vec3 p = position + light_direction * (float(i) + offset) * step_length;
+        vec4 clip_position = viewProjection * vec4(p, 1.0);
+        vec2 uv = 0.5 * clip_position.xy / clip_position.w + 0.5;
+        if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+            break;
+        }
+        vec3 surface_position = world_pos_from_depth(viewProjectionInverse, sample_depth(uv), uv);
+        float difference = distance(camera_position, p) - distance(camera_position, surface_position);
+        if (difference > bias && difference < contactShadowThickness) {
+            return 1.0 - contactShadowIntensity;
+        }
+    }
+    return 1.0;
+}
@@ -0,0 +1,23 @@
+
+in vec2 uvs;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+    vec4 color = sample_color(uvs);
+    float depth = sample_depth(uvs);
+    if (depth < 1.0) {
+        vec3 position = world_pos_from_depth(viewProjectionInverse, depth, uvs);
+        // Reconstruct the normal from the depth texture and make it face the camera
+        vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+        normal = dot(normal, eyePosition - position) < 0.0 ? -normal : normal;
+        color.rgb *= 1.0 - calculate_contact_shadow(position, normal);
+    }
+
+    // Output
+    outColor = color;
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    gl_FragDepth = depth;
+}
@@ -1,5 +1,4 @@
 
-uniform mat4 viewProjectionInverse;
 uniform float zNear;
 uniform float zFar;
 uniform vec3 cameraPosition;
@@ -33,7 +32,28 @@ void main()
     vec3 normal = normalize(vec3(n2.x, n2.y, (int(floor(n.z * 255.0)) & 128) == 128 ? z: -z));
     float roughness_factor = n.w;
     float occlusion = float(int(floor(n.z * 255.0)) & 127) / 127.0;
-    vec3 total_emissive = sample_layer(uvs, 2).rgb;
+    vec4 e = sample_layer(uvs, 2);
+    vec3 total_emissive = e.rgb;
+
+#if defined(USE_SPECULAR) || defined(USE_SHEEN) || defined(USE_CLEARCOAT)
+    // The material extensions are stored in the alpha channel of the emissive layer and in the fourth layer
+    int packed_specular_sheen = int(round(e.a * 255.0));
+    vec4 extensions = sample_layer(uvs, 3);
+#endif
+#ifdef USE_SPECULAR
+    float specular_code = float(packed_specular_sheen >> 4) / 15.0;
+    dielectric_specular = vec3(0.36 * specular_code * specular_code);
+#endif
+#ifdef USE_SHEEN
+    sheen_color = extensions.rgb;
+    sheen_roughness = float(packed_specular_sheen & 15) / 15.0;
+#endif
+#ifdef USE_CLEARCOAT
+    int packed_clearcoat = int(round(extensions.a * 255.0));
+    clearcoat_factor = float(packed_clearcoat >> 4) / 15.0;
+    clearcoat_roughness = float(packed_clearcoat & 15) / 15.0;
+    clearcoat_normal = normal;
+#endif
 
     if(debug_type == 0) // Position
     {
@@ -0,0 +1,55 @@
+
+uniform vec2 resolution;
+uniform mat4 viewProjectionInverse;
+uniform vec3 eyePosition;
+uniform vec4 outlineColor;
+uniform float thickness;
+uniform float depthThreshold;
+uniform float creaseCosine;
+
+in vec2 uvs;
+
+layout (location = 0) out vec4 outColor;
+
+vec3 position_at(vec2 uv)
+{
+    return world_pos_from_depth(viewProjectionInverse, sample_depth(uv), uv);
+}
+
+// Reconstructs the surface normal from the positions of the neighbouring pixels
+vec3 normal_at(vec2 uv)
+{
+    vec2 pixel = 1.0 / resolution;
+    vec3 position = position_at(uv);
+    return normalize(cross(position_at(uv + vec2(pixel.x, 0.0)) - position, position_at(uv + vec2(0.0, pixel.y)) - position));
+}
+
+void main()
+{
+    vec4 color = sample_color(uvs);
+    float depth = sample_depth(uvs);
+    float dist = distance(position_at(uvs), eyePosition);
+    vec3 normal = normal_at(uvs);
+
+    vec2 offset = thickness / resolution;
+    vec2 offsets[4] = vec2[](vec2(offset.x, 0.0), vec2(-offset.x, 0.0), vec2(0.0, offset.y), vec2(0.0, -offset.y));
+    float edge = 0.0;
+    float edge_depth = depth;
+    for (int i = 0; i < 4; i++) {
+        vec2 uv = uvs + offsets[i];
+        float neighbour_depth = sample_depth(uv);
+        float neighbour_dist = distance(position_at(uv), eyePosition);
+        // The silhouette is drawn outside the closest object
+        if (dist - neighbour_dist > depthThreshold * neighbour_dist) {
+            edge = 1.0;
+            edge_depth = min(edge_depth, neighbour_depth);
+        } else if (depth < 1.0 && abs(dist - neighbour_dist) < depthThreshold * dist && dot(normal, normal_at(uv)) < creaseCosine) {
+            edge = 1.0;
+        }
+    }
+
+    outColor = mix(color, outlineColor, edge * outlineColor.a);
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    gl_FragDepth = edge_depth;
+}
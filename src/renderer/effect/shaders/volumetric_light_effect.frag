@@ -0,0 +1,40 @@
+
+uniform mat4 viewProjectionInverse;
+uniform vec3 eyePosition;
+uniform float density;
+uniform int stepCount;
+uniform float maxDistance;
+
+in vec2 uvs;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+    vec4 color = sample_color(uvs);
+    float depth = sample_depth(uvs);
+    vec3 pos = world_pos_from_depth(viewProjectionInverse, depth, uvs);
+
+    vec3 ray_direction = pos - eyePosition;
+    float dist = length(ray_direction);
+    ray_direction /= dist;
+    dist = depth < 1.0 ? min(dist, maxDistance) : maxDistance;
+
+    // Ray march from the eye towards the surface with a per pixel offset to turn banding into noise
+    float step_length = dist / float(stepCount);
+    float step_transmittance = exp(-density * step_length);
+    float offset = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    float transmittance = 1.0;
+    vec3 scattered_light = vec3(0.0);
+    for (int i = 0; i < stepCount; i++) {
+        vec3 position = eyePosition + ray_direction * (float(i) + offset) * step_length;
+        scattered_light += transmittance * (1.0 - step_transmittance) * calculate_scattered_light(position, ray_direction);
+        transmittance *= step_transmittance;
+    }
+
+    // Output
+    outColor = vec4(color.rgb * transmittance + scattered_light, color.a);
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    gl_FragDepth = depth;
+}
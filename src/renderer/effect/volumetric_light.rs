@@ -0,0 +1,132 @@
+use crate::renderer::*;
+
+///
+/// An effect that simulates light scattered by particles in the air, ie. visible light shafts (god rays) where the light is not blocked by objects.
+/// The light is calculated by ray marching from the camera to the surface seen in each pixel, evaluating the light arriving at each step including shadows.
+/// Only lights which support scattering are used, see [Light::incoming_light_shader_source].
+/// Shadows are only visible in the air if the light has a shadow map, see for example [DirectionalLight::generate_shadow_map].
+///
+#[derive(Clone, Debug)]
+pub struct VolumetricLightEffect {
+    /// The density of the air, ie. the fraction of the light which is scattered per unit of distance.
+    pub density: f32,
+    /// The anisotropy of the scattering, which is the `g` parameter of the Henyey-Greenstein phase function and should be in the range `]-1, 1[`.
+    /// Zero scatters the light equally in all directions, positive values scatters the light forward, ie. the light shafts are brighter when looking towards the light,
+    /// and negative values scatters the light backwards.
+    pub anisotropy: f32,
+    /// The number of steps along each view ray. More steps gives more accurate light shafts but is more expensive.
+    pub step_count: u32,
+    /// The maximum distance from the camera where the light is scattered, which is also the distance used for pixels where nothing is rendered.
+    pub max_distance: f32,
+}
+
+impl Default for VolumetricLightEffect {
+    fn default() -> Self {
+        Self {
+            density: 0.05,
+            anisotropy: 0.5,
+            step_count: 64,
+            max_distance: 100.0,
+        }
+    }
+}
+
+impl Effect for VolumetricLightEffect {
+    fn fragment_shader_source(
+        &self,
+        lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) -> String {
+        let (mut source, incoming_lights) = incoming_lights_shader_source(lights);
+        source.push_str(&format!(
+            "{}\n{}\n{}\n{}\n",
+            color_texture
+                .expect("Must supply a color texture to apply a volumetric light effect")
+                .fragment_shader_source(),
+            depth_texture
+                .expect("Must supply a depth texture to apply a volumetric light effect")
+                .fragment_shader_source(),
+            ToneMapping::fragment_shader_source(),
+            ColorMapping::fragment_shader_source(),
+        ));
+        let light_sum = (0..incoming_lights.len())
+            .map(|i| format!(
+                "light += calculate_incoming_light{i}(position, vec3(0.0), light_direction, visibility) * visibility * henyey_greenstein(dot(ray_direction, light_direction), anisotropy);\n"
+            ))
+            .collect::<String>();
+        source.push_str(&format!(
+            "
+                uniform float anisotropy;
+
+                vec3 calculate_scattered_light(vec3 position, vec3 ray_direction)
+                {{
+                    vec3 light = vec3(0.0);
+                    vec3 light_direction;
+                    float visibility;
+                    {light_sum}
+                    return light;
+                }}
+            "
+        ));
+        source.push_str(include_str!("shaders/volumetric_light_effect.frag"));
+        source
+    }
+
+    fn id(
+        &self,
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) -> EffectMaterialId {
+        EffectMaterialId::VolumetricLightEffect(
+            color_texture.expect("Must supply a color texture to apply a volumetric light effect"),
+            depth_texture.expect("Must supply a depth texture to apply a volumetric light effect"),
+        )
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            uv: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(
+        &self,
+        program: &Program,
+        camera: &Camera,
+        lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) {
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        color_texture
+            .expect("Must supply a color texture to apply a volumetric light effect")
+            .use_uniforms(program);
+        depth_texture
+            .expect("Must supply a depth texture to apply a volumetric light effect")
+            .use_uniforms(program);
+        program.use_uniform(
+            "viewProjectionInverse",
+            (camera.projection() * camera.view()).invert().unwrap(),
+        );
+        program.use_uniform("eyePosition", camera.position());
+        program.use_uniform("density", self.density);
+        program.use_uniform_if_required("anisotropy", self.anisotropy);
+        program.use_uniform("stepCount", self.step_count.max(1) as i32);
+        program.use_uniform("maxDistance", self.max_distance);
+        let (_, incoming_lights) = incoming_lights_shader_source(lights);
+        for (i, light) in incoming_lights.iter().enumerate() {
+            light.use_uniforms(program, i as u32);
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        RenderStates {
+            depth_test: DepthTest::Always,
+            cull: Cull::Back,
+            ..Default::default()
+        }
+    }
+}
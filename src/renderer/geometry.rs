@@ -50,6 +50,14 @@ macro_rules! impl_geometry_body {
         fn aabb(&self) -> AxisAlignedBoundingBox {
             self.$inner().aabb()
         }
+
+        fn alpha_mask(&self) -> Option<AlphaMask> {
+            self.$inner().alpha_mask()
+        }
+
+        fn vertex_deformation(&self) -> Option<VertexDeformation> {
+            self.$inner().vertex_deformation()
+        }
     };
 }
 
@@ -160,6 +168,24 @@ pub trait Geometry {
     ///
     fn aabb(&self) -> AxisAlignedBoundingBox;
 
+    ///
+    /// Returns the [AlphaMask] of the material this geometry is rendered with, if the geometry is combined with a material (for example in a [Gm]) and that material discards fragments.
+    /// This is used to discard the same fragments when generating shadow maps and when picking.
+    /// The default implementation returns `None`.
+    ///
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        None
+    }
+
+    ///
+    /// Returns the [VertexDeformation] of the material this geometry is rendered with, if the geometry is combined with a material (for example in a [Gm]) and that material deforms the vertices.
+    /// This is used to apply the same deformation when generating shadow maps and when picking.
+    /// The default implementation returns `None`.
+    ///
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        None
+    }
+
     ///
     /// For updating the animation of this geometry if it is animated, if not, this method does nothing.
     /// The time parameter should be some continious time, for example the time since start.
@@ -255,6 +281,14 @@ impl<T: Geometry> Geometry for std::sync::RwLock<T> {
         self.read().unwrap().aabb()
     }
 
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        self.read().unwrap().alpha_mask()
+    }
+
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        self.read().unwrap().vertex_deformation()
+    }
+
     fn animate(&mut self, time: f32) {
         self.write().unwrap().animate(time)
     }
@@ -357,7 +391,9 @@ impl BaseMesh {
     fn use_attributes(&self, program: &Program, attributes: FragmentAttributes) {
         program.use_vertex_attribute("position", &self.positions);
 
-        if attributes.normal {
+        // The attributes can be unused even though the material requires them, since the compiler removes the inputs which do not contribute to the output.
+        // For example, the normal and tangent are unused if the object is only affected by an ambient light, and the uv coordinates if they are only used to sample a normal texture.
+        if attributes.normal && program.requires_attribute("normal") {
             program.use_vertex_attribute(
                 "normal",
                 self.normals.as_ref().unwrap_or_else(|| {
@@ -368,7 +404,7 @@ impl BaseMesh {
             );
         }
 
-        if attributes.tangents {
+        if attributes.tangents && program.requires_attribute("tangent") {
             program.use_vertex_attribute(
                 "tangent",
                 self.tangents.as_ref().unwrap_or_else(|| {
@@ -379,7 +415,7 @@ impl BaseMesh {
             );
         }
 
-        if attributes.uv {
+        if attributes.uv && program.requires_attribute("uv_coordinates") {
             program.use_vertex_attribute(
                 "uv_coordinates",
                 self.uvs.as_ref().unwrap_or_else(|| {
@@ -390,7 +426,7 @@ impl BaseMesh {
             );
         }
 
-        if attributes.color {
+        if attributes.color && program.requires_attribute("color") {
             if let Some(colors) = &self.colors {
                 program.use_vertex_attribute("color", colors);
             }
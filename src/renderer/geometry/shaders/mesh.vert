@@ -55,6 +55,10 @@ in vec4 instance_color;
 out vec4 col;
 flat out int instance_id;
 
+#ifdef USE_VERTEX_DEFORMATION
+void deform_vertex(vec3 local_position, vec2 uv, inout vec3 world_position, inout vec3 world_normal);
+#endif
+
 void main()
 {
     // *** POSITION ***
@@ -77,9 +81,6 @@ void main()
 #ifdef USE_INSTANCE_TRANSLATIONS 
     worldPosition.xyz += instance_translation;
 #endif
-    gl_Position = viewProjection * worldPosition;
-
-    pos = worldPosition.xyz;
 
     // *** NORMAL ***
 #ifdef USE_NORMALS 
@@ -110,6 +111,30 @@ void main()
 #endif
 #endif
 
+    // *** DEFORMATION ***
+#ifdef USE_VERTEX_DEFORMATION
+#ifdef USE_NORMALS
+    vec3 world_normal = nor;
+#else
+    vec3 world_normal = vec3(0.0);
+#endif
+#ifdef USE_UVS
+    vec2 deformation_uv = uvs;
+#else
+    vec2 deformation_uv = vec2(0.0);
+#endif
+    deform_vertex(position, deformation_uv, worldPosition.xyz, world_normal);
+#ifdef USE_NORMALS
+    nor = normalize(world_normal);
+#ifdef USE_TANGENTS
+    tang = normalize(tang - dot(tang, nor) * nor);
+    bitang = normalize(cross(nor, tang) * tangent.w);
+#endif
+#endif
+#endif
+    gl_Position = viewProjection * worldPosition;
+    pos = worldPosition.xyz;
+
     // *** COLOR ***
     col = vec4(1.0);
 #ifdef USE_VERTEX_COLORS 
@@ -13,6 +13,10 @@ out vec4 col;
 out vec3 pos;
 flat out int instance_id;
 
+#ifdef USE_VERTEX_DEFORMATION
+void deform_vertex(vec3 local_position, vec2 uv, inout vec3 world_position, inout vec3 world_normal);
+#endif
+
 void main()
 {
     uvs = uv_coordinate;
@@ -37,7 +41,12 @@ void main()
                 z, 0.0,
                 center.x, center.y, center.z, 1.0);
     vec4 world_pos = instanced_transform * transformation * vec4(position, 1.);
-    pos = world_pos.xyz / world_pos.w;
+    world_pos /= world_pos.w;
+#ifdef USE_VERTEX_DEFORMATION
+    vec3 world_normal = z;
+    deform_vertex(position, uvs, world_pos.xyz, world_normal);
+#endif
+    pos = world_pos.xyz;
     gl_Position = viewProjection * world_pos;
     instance_id = gl_InstanceID;
 }
@@ -12,6 +12,12 @@ macro_rules! impl_light_body {
         fn use_uniforms(&self, program: &Program, i: u32) {
             self.$inner().use_uniforms(program, i)
         }
+        fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+            self.$inner().affects(aabb)
+        }
+        fn incoming_light_shader_source(&self, i: u32) -> Option<String> {
+            self.$inner().incoming_light_shader_source(i)
+        }
         fn id(&self) -> LightId {
             self.$inner().id()
         }
@@ -28,10 +34,37 @@ mod spot_light;
 #[doc(inline)]
 pub use spot_light::*;
 
+mod ies;
+#[doc(inline)]
+pub use ies::*;
+
 mod point_light;
 #[doc(inline)]
 pub use point_light::*;
 
+mod rect_area_light;
+#[doc(inline)]
+pub use rect_area_light::*;
+
+mod disk_area_light;
+#[doc(inline)]
+pub use disk_area_light::*;
+
+mod clustered_lights;
+#[doc(inline)]
+pub use clustered_lights::*;
+
+mod dynamic_lights;
+#[doc(inline)]
+pub use dynamic_lights::*;
+
+mod ltc;
+use ltc::*;
+
+mod shadow;
+#[doc(inline)]
+pub use shadow::*;
+
 mod ambient_light;
 #[doc(inline)]
 pub use ambient_light::*;
@@ -40,6 +73,26 @@ mod environment;
 #[doc(inline)]
 pub use environment::*;
 
+mod cpu_environment;
+#[doc(inline)]
+pub use cpu_environment::*;
+
+mod spherical_harmonics;
+#[doc(inline)]
+pub use spherical_harmonics::*;
+
+mod light_probe_grid;
+#[doc(inline)]
+pub use light_probe_grid::*;
+
+mod reflection_probe;
+#[doc(inline)]
+pub use reflection_probe::*;
+
+mod transmission_background;
+#[doc(inline)]
+pub use transmission_background::*;
+
 use crate::core::*;
 use crate::renderer::camera::*;
 use crate::renderer::LightId;
@@ -58,6 +111,28 @@ pub struct Attenuation {
     pub quadratic: f32,
 }
 
+impl Attenuation {
+    ///
+    /// Returns the distance from a light with this attenuation and the given intensity where the contribution of the light drops below the given threshold,
+    /// ie. the radius of the volume influenced by the light.
+    /// Returns infinity if the contribution never drops below the threshold, which is the case if both the linear and quadratic attenuation are zero.
+    ///
+    pub fn influence_radius(&self, intensity: f32, threshold: f32) -> f32 {
+        // Solve constant + linear * distance + quadratic * distance * distance = intensity / threshold
+        let c = self.constant - intensity / threshold;
+        if c >= 0.0 {
+            0.0
+        } else if self.quadratic > 0.0 {
+            (-self.linear + (self.linear * self.linear - 4.0 * self.quadratic * c).sqrt())
+                / (2.0 * self.quadratic)
+        } else if self.linear > 0.0 {
+            -c / self.linear
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
 impl Default for Attenuation {
     fn default() -> Self {
         Self {
@@ -80,6 +155,38 @@ pub trait Light {
     /// Should bind the uniforms that is needed for calculating this lights contribution to the color in [Light::shader_source].
     fn use_uniforms(&self, program: &Program, i: u32);
 
+    ///
+    /// Returns whether or not this light can contribute to the color of a surface inside the given axis aligned bounding box.
+    /// This is used to avoid evaluating lights for objects that are outside the volume influenced by the light.
+    /// The default implementation returns true, ie. the light affects everything.
+    ///
+    fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        let _ = aabb;
+        true
+    }
+
+    ///
+    /// The fragment shader source for calculating the light arriving at a point, which is used when the light is not reflected by a surface in the usual way,
+    /// for example light scattered by the air (see [VolumetricLightEffect](crate::renderer::effect::VolumetricLightEffect)), the shadows received by a [ShadowCatcherMaterial](crate::renderer::material::ShadowCatcherMaterial)
+    /// or screen-space contact shadows (see [ContactShadowEffect](crate::renderer::effect::ContactShadowEffect)).
+    /// It should contain a function with this signature
+    /// `vec3 calculate_incoming_light{}(vec3 position, vec3 normal, out vec3 light_direction, out float visibility)`
+    /// Where `{}` is replaced with the number i given as input.
+    /// This function should return the light arriving at the given position without shadows, set `light_direction` to the direction from the position towards the light
+    /// and set `visibility` to the fraction of the light which is not blocked by shadow casting objects.
+    /// The normal is only used to offset the shadow lookup and is zero for positions which are not on a surface.
+    ///
+    /// The source is always added directly after the source returned by [Light::shader_source] with the same number i, so it can use the uniforms and functions declared there,
+    /// and the uniforms are bound using [Light::use_uniforms] as usual.
+    /// However, the `calculate_lighting{}` function is not necessarily called, so this function should use all of the uniforms bound in [Light::use_uniforms].
+    /// The default implementation returns `None`, ie. the light is not used in these cases.
+    /// Of the lights in this crate, [DirectionalLight], [SpotLight] and [PointLight] support this.
+    ///
+    fn incoming_light_shader_source(&self, i: u32) -> Option<String> {
+        let _ = i;
+        None
+    }
+
     ///
     /// Returns a unique ID for each variation of the shader source returned from `Light::shader_source`.
     ///
@@ -120,6 +227,12 @@ impl<T: Light> Light for std::sync::Arc<std::sync::RwLock<T>> {
     fn use_uniforms(&self, program: &Program, i: u32) {
         self.read().unwrap().use_uniforms(program, i)
     }
+    fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        self.read().unwrap().affects(aabb)
+    }
+    fn incoming_light_shader_source(&self, i: u32) -> Option<String> {
+        self.read().unwrap().incoming_light_shader_source(i)
+    }
     fn id(&self) -> LightId {
         self.read().unwrap().id()
     }
@@ -158,6 +271,41 @@ pub fn lights_shader_source(lights: &[&dyn Light], lighting_model: LightingModel
     shader_source
 }
 
+///
+/// Returns the shader source needed to call `calculate_incoming_light{i}` (see [Light::incoming_light_shader_source]) for each of the given lights which supports it,
+/// together with those lights, where the index of a light in the returned list is the number i used in the shader source and the number to use in [Light::use_uniforms].
+/// The source includes the shared shader source and a lighting model, which is not used but is needed to compile the shader source of the lights.
+///
+pub(crate) fn incoming_lights_shader_source<'a>(
+    lights: &[&'a dyn Light],
+) -> (String, Vec<&'a dyn Light>) {
+    let mut source = lighting_model_shader(LightingModel::Blinn).to_string();
+    source.push('\n');
+    source.push_str(include_str!("../core/shared.frag"));
+    source.push_str(include_str!("light/shaders/light_shared.frag"));
+    let mut incoming_lights = Vec::new();
+    for light in lights {
+        let i = incoming_lights.len() as u32;
+        if let Some(incoming_light_source) = light.incoming_light_shader_source(i) {
+            source.push_str(&light.shader_source(i));
+            source.push_str(&incoming_light_source);
+            incoming_lights.push(*light);
+        }
+    }
+    (source, incoming_lights)
+}
+
+/// The contribution below which a light is considered to not affect a surface.
+const LIGHT_INFLUENCE_THRESHOLD: f32 = 0.01;
+
+///
+/// Returns the largest component of the given linear color multiplied by the given intensity.
+///
+fn max_intensity(color: Srgba, intensity: f32) -> f32 {
+    let color = color.to_linear_srgb();
+    color.x.max(color.y).max(color.z) * intensity
+}
+
 fn shadow_matrix(camera: &Camera) -> Mat4 {
     let bias_matrix = crate::Mat4::new(
         0.5, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.5, 0.5, 0.5, 1.0,
@@ -185,3 +333,56 @@ pub(crate) fn lighting_model_shader(lighting_model: LightingModel) -> &'static s
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contribution(attenuation: &Attenuation, intensity: f32, distance: f32) -> f32 {
+        intensity
+            / (attenuation.constant
+                + attenuation.linear * distance
+                + attenuation.quadratic * distance * distance)
+    }
+
+    #[test]
+    fn influence_radius_with_quadratic_attenuation() {
+        let attenuation = Attenuation {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        let radius = attenuation.influence_radius(2.0, 0.01);
+        assert!((contribution(&attenuation, 2.0, radius) - 0.01).abs() < 1.0e-5);
+        assert!(contribution(&attenuation, 2.0, 0.9 * radius) > 0.01);
+    }
+
+    #[test]
+    fn influence_radius_with_linear_attenuation() {
+        let attenuation = Attenuation {
+            constant: 1.0,
+            linear: 1.0,
+            quadratic: 0.0,
+        };
+        assert_eq!(attenuation.influence_radius(1.0, 0.01), 99.0);
+    }
+
+    #[test]
+    fn influence_radius_without_attenuation() {
+        assert_eq!(
+            Attenuation::default().influence_radius(1.0, 0.01),
+            f32::INFINITY
+        );
+    }
+
+    #[test]
+    fn influence_radius_below_threshold() {
+        let attenuation = Attenuation {
+            constant: 10.0,
+            linear: 1.0,
+            quadratic: 1.0,
+        };
+        assert_eq!(attenuation.influence_radius(1.0, 0.1), 0.0);
+        assert_eq!(attenuation.influence_radius(0.5, 0.1), 0.0);
+    }
+}
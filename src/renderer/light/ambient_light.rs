@@ -1,6 +1,9 @@
 use crate::core::*;
 use crate::renderer::*;
 
+/// The maximum number of [AmbientLight::reflection_probes] used when rendering, since each probe requires two texture units.
+pub const MAX_REFLECTION_PROBES: usize = 4;
+
 ///
 /// A light which shines on all surfaces.
 /// Can be uniform (a light that shines equally on any surface) or calculated from an environment map using the [Environment] struct.
@@ -12,6 +15,9 @@ pub struct AmbientLight {
     pub color: Srgba,
     /// The light shining from the environment. This is calculated based on an environment map.
     pub environment: Option<Environment>,
+    /// Local reflection probes which are used instead of the environment for surfaces inside the bounding box of a probe, for example inside a room.
+    /// Overlapping probes are blended and at most [MAX_REFLECTION_PROBES] probes are used.
+    pub reflection_probes: Vec<ReflectionProbe>,
 }
 
 impl AmbientLight {
@@ -21,6 +27,7 @@ impl AmbientLight {
             intensity,
             color,
             environment: None,
+            reflection_probes: Vec::new(),
         }
     }
 
@@ -35,21 +42,90 @@ impl AmbientLight {
             intensity,
             color,
             environment: Some(Environment::new(context, environment_map)),
+            reflection_probes: Vec::new(),
         }
     }
+
+    fn used_reflection_probes(&self) -> &[ReflectionProbe] {
+        &self.reflection_probes[..self.reflection_probes.len().min(MAX_REFLECTION_PROBES)]
+    }
 }
 
 impl Light for AmbientLight {
     fn shader_source(&self, i: u32) -> String {
-        if self.environment.is_some() {
-            format!(
-            "
+        let reflection_probes = self.used_reflection_probes();
+        if self.environment.is_none() && reflection_probes.is_empty() {
+            return format!(
+                "
+                    uniform vec3 ambientColor;
+                    vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                    {{
+                        return occlusion * ambientColor * diffuse_reflectance(surface_color, metallic);
+                    }}
+                
+                ", i);
+        }
+
+        let mut uniforms = String::new();
+        let mut probes = String::new();
+        for j in 0..reflection_probes.len() {
+            uniforms.push_str(&format!(
+                "
+                uniform samplerCube probeIrradianceMap{i}_{j};
+                uniform samplerCube probePrefilterMap{i}_{j};
+                uniform vec3 probeMin{i}_{j};
+                uniform vec3 probeMax{i}_{j};
+                uniform vec3 probePosition{i}_{j};
+                uniform float probeBlendDistance{i}_{j};
+                "
+            ));
+            probes.push_str(&format!(
+                "
+                    weight = reflection_probe_weight(position, probeMin{i}_{j}, probeMax{i}_{j}, probeBlendDistance{i}_{j});
+                    if (weight > 0.0) {{
+                        vec3 direction = box_projected_direction(position, R, probeMin{i}_{j}, probeMax{i}_{j}, probePosition{i}_{j});
+                        irradiance += weight * texture(probeIrradianceMap{i}_{j}, N).rgb;
+                        prefilteredColor += weight * textureLod(probePrefilterMap{i}_{j}, direction, roughness * MAX_REFLECTION_LOD).rgb;
+                        #ifdef USE_CLEARCOAT
+                        direction = box_projected_direction(position, clearcoat_R, probeMin{i}_{j}, probeMax{i}_{j}, probePosition{i}_{j});
+                        clearcoatColor += weight * textureLod(probePrefilterMap{i}_{j}, direction, clearcoat_roughness * MAX_REFLECTION_LOD).rgb;
+                        #endif
+                        total_weight += weight;
+                    }}
+                "
+            ));
+        }
+        let fallback = if self.environment.is_some() {
+            uniforms.push_str(
+                "
                 uniform samplerCube irradianceMap;
                 uniform samplerCube prefilterMap;
+                ",
+            );
+            "
+                    irradiance += (1.0 - total_weight) * texture(irradianceMap, N).rgb;
+                    prefilteredColor += (1.0 - total_weight) * textureLod(prefilterMap, R, roughness * MAX_REFLECTION_LOD).rgb;
+                    #ifdef USE_CLEARCOAT
+                    clearcoatColor += (1.0 - total_weight) * textureLod(prefilterMap, clearcoat_R, clearcoat_roughness * MAX_REFLECTION_LOD).rgb;
+                    #endif
+                    float uniform_weight = 0.0;
+                    vec3 uniform_color = vec3(0.0);
+            "
+        } else {
+            // Surfaces outside the reflection probes are lit by a uniform ambient light
+            "
+                    float uniform_weight = 1.0 - total_weight;
+                    vec3 uniform_color = uniform_weight * diffuse_reflectance(surface_color, metallic);
+            "
+        };
+
+        format!(
+            "
+                {uniforms}
                 uniform sampler2D brdfLUT;
                 uniform vec3 ambientColor;
     
-                vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
                 {{
                     vec3 N = normal;
                     vec3 V = view_direction;
@@ -57,42 +133,82 @@ impl Light for AmbientLight {
                     float NdV = max(0.001, dot(N, V));
                     
                     // calculate reflectance at normal incidence; if dia-electric (like plastic) use F0 
-                    // of 0.04 and if it's a metal, use the albedo color as F0 (metallic workflow)    
-                    vec3 F0 = mix(vec3(0.04), surface_color, metallic);
+                    // of 0.04 (unless specified by the material) and if it's a metal, use the albedo color as F0 (metallic workflow)    
+                    vec3 F0 = specular_reflectance(surface_color, metallic);
                     vec3 specular_fresnel = fresnel_schlick_roughness(F0, NdV, roughness);
                     vec3 diffuse_fresnel = 1.0 - specular_fresnel;
 
+                    // Blend the light from the reflection probes containing the position and the environment
+                    const float MAX_REFLECTION_LOD = 4.0;
+                    vec3 irradiance = vec3(0.0);
+                    vec3 prefilteredColor = vec3(0.0);
+                    float total_weight = 0.0;
+                    float weight;
+                    #ifdef USE_CLEARCOAT
+                    vec3 clearcoat_R = reflect(-V, clearcoat_normal);
+                    vec3 clearcoatColor = vec3(0.0);
+                    #endif
+                    {probes}
+                    if (total_weight > 1.0) {{
+                        irradiance /= total_weight;
+                        prefilteredColor /= total_weight;
+                        #ifdef USE_CLEARCOAT
+                        clearcoatColor /= total_weight;
+                        #endif
+                        total_weight = 1.0;
+                    }}
+                    {fallback}
+
                     // Diffuse
-                    vec3 irradiance = texture(irradianceMap, N).rgb;
-                    vec3 diffuse = diffuse_fresnel * mix(surface_color, vec3(0.0), metallic) * irradiance;
+                    vec3 diffuse = diffuse_fresnel * diffuse_reflectance(surface_color, metallic) * irradiance;
                     
-                    // sample both the pre-filter map and the BRDF lut and combine them together as per the Split-Sum approximation to get the IBL specular part.
-                    const float MAX_REFLECTION_LOD = 4.0;
-                    vec3 prefilteredColor = textureLod(prefilterMap, R,  roughness * MAX_REFLECTION_LOD).rgb;    
+                    // sample the BRDF lut and combine it with the pre-filtered color as per the Split-Sum approximation to get the IBL specular part.
                     vec2 brdf  = texture(brdfLUT, vec2(NdV, roughness)).rg;
                     vec3 specular = prefilteredColor * (specular_fresnel * brdf.x + brdf.y);
+                    vec3 color = diffuse + specular + uniform_color;
+
+                    #ifdef USE_SHEEN
+                    // The sheen layer reflects light mostly at grazing angles, which is approximated by a fit of the albedo of the Charlie distribution
+                    float sheen_albedo = mix(0.05, 0.5, pow(1.0 - NdV, 3.0));
+                    color = color * (1.0 - 0.157 * max(sheen_color.r, max(sheen_color.g, sheen_color.b))) + sheen_albedo * sheen_color * (irradiance + uniform_weight);
+                    #endif
+
+                    #ifdef USE_CLEARCOAT
+                    float clearcoat_fresnel = clearcoat_factor * fresnel_schlick_roughness(vec3(0.04), max(0.001, dot(clearcoat_normal, V)), clearcoat_roughness).x;
+                    color = color * (1.0 - clearcoat_fresnel) + clearcoat_fresnel * (clearcoatColor + uniform_weight);
+                    #endif
     
-                    return (diffuse + specular) * occlusion * ambientColor;
+                    return color * occlusion * ambientColor;
                 }}
             
-            ", i)
-        } else {
-            format!(
-                "
-                    uniform vec3 ambientColor;
-                    vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
-                    {{
-                        return occlusion * ambientColor * mix(surface_color, vec3(0.0), metallic);
-                    }}
-                
-                ", i)
-        }
+            "
+        )
     }
-    fn use_uniforms(&self, program: &Program, _i: u32) {
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        let reflection_probes = self.used_reflection_probes();
+        for (j, probe) in reflection_probes.iter().enumerate() {
+            program.use_texture_cube(
+                &format!("probeIrradianceMap{}_{}", i, j),
+                &probe.environment.irradiance_map,
+            );
+            program.use_texture_cube(
+                &format!("probePrefilterMap{}_{}", i, j),
+                &probe.environment.prefilter_map,
+            );
+            program.use_uniform(&format!("probeMin{}_{}", i, j), probe.aabb.min());
+            program.use_uniform(&format!("probeMax{}_{}", i, j), probe.aabb.max());
+            program.use_uniform(&format!("probePosition{}_{}", i, j), probe.position);
+            program.use_uniform(
+                &format!("probeBlendDistance{}_{}", i, j),
+                probe.blend_distance,
+            );
+        }
         if let Some(ref environment) = self.environment {
             program.use_texture_cube("irradianceMap", &environment.irradiance_map);
             program.use_texture_cube("prefilterMap", &environment.prefilter_map);
             program.use_texture("brdfLUT", &environment.brdf_map);
+        } else if let Some(probe) = reflection_probes.first() {
+            program.use_texture("brdfLUT", &probe.environment.brdf_map);
         }
         program.use_uniform(
             "ambientColor",
@@ -101,7 +217,10 @@ impl Light for AmbientLight {
     }
 
     fn id(&self) -> LightId {
-        LightId::AmbientLight(self.environment.is_some())
+        LightId::AmbientLight(
+            self.environment.is_some(),
+            self.used_reflection_probes().len() as u8,
+        )
     }
 }
 
@@ -111,6 +230,7 @@ impl Default for AmbientLight {
             color: Srgba::WHITE,
             intensity: 1.0,
             environment: None,
+            reflection_probes: Vec::new(),
         }
     }
 }
@@ -0,0 +1,482 @@
+use crate::core::*;
+use crate::renderer::light::*;
+use crate::renderer::*;
+
+/// The number of clusters along the horizontal axis of the screen.
+const CLUSTER_COUNT_X: usize = 16;
+/// The number of clusters along the vertical axis of the screen.
+const CLUSTER_COUNT_Y: usize = 9;
+/// The number of clusters along the view direction, the clusters are distributed exponentially between the near and far plane.
+const CLUSTER_COUNT_Z: usize = 24;
+/// The width of the texture containing the light indices.
+const INDEX_TEXTURE_WIDTH: usize = 1024;
+
+///
+/// A set of point and spot lights which is evaluated using clustered forward lighting.
+/// The view frustum is divided into clusters and each cluster contains a list of the lights that affects it,
+/// so each fragment only evaluates the lights in its cluster.
+/// The light parameters are stored in textures, so the shader does not depend on the number of lights
+/// and adding, removing or changing lights does not require a new shader to be compiled.
+/// This makes it possible to render hundreds of dynamic lights.
+///
+/// The lights in the set are specified using [ClusteredLights::update] which also assigns the lights to the clusters of the given camera,
+/// so it should be called each frame where the camera or the lights have changed.
+/// The clustered lights can be used in the same way as any other light, for example with [PhysicalMaterial] and [DeferredPhysicalMaterial].
+///
+/// **Note:** The lights in the set do not cast shadows.
+///
+pub struct ClusteredLights {
+    context: Context,
+    light_data: Texture2D,
+    cluster_data: Texture2D,
+    light_indices: Texture2D,
+    view_projection: Mat4,
+    view_position: Vec3,
+    view_direction: Vec3,
+    z_near: f32,
+    z_far: f32,
+}
+
+impl ClusteredLights {
+    ///
+    /// Constructs a new set of clustered lights containing the given lights assigned to the clusters of the given camera.
+    ///
+    pub fn new<'a>(
+        context: &Context,
+        camera: &Camera,
+        point_lights: impl IntoIterator<Item = &'a PointLight>,
+        spot_lights: impl IntoIterator<Item = &'a SpotLight>,
+    ) -> Self {
+        let mut clustered_lights = Self {
+            context: context.clone(),
+            light_data: data_texture::<[f32; 4]>(context, 4, 1),
+            cluster_data: data_texture::<[f32; 2]>(
+                context,
+                CLUSTER_COUNT_X * CLUSTER_COUNT_Y,
+                CLUSTER_COUNT_Z,
+            ),
+            light_indices: data_texture::<f32>(context, INDEX_TEXTURE_WIDTH, 1),
+            view_projection: Mat4::identity(),
+            view_position: Vec3::zero(),
+            view_direction: vec3(0.0, 0.0, -1.0),
+            z_near: 0.0,
+            z_far: 0.0,
+        };
+        clustered_lights.update(camera, point_lights, spot_lights);
+        clustered_lights
+    }
+
+    ///
+    /// Updates the set of lights to the given lights and assigns them to the clusters of the given camera.
+    /// Should be called whenever the camera or the lights have changed.
+    /// The textures containing the lights and clusters are reused and only replaced by larger textures when the data no longer fits.
+    ///
+    pub fn update<'a>(
+        &mut self,
+        camera: &Camera,
+        point_lights: impl IntoIterator<Item = &'a PointLight>,
+        spot_lights: impl IntoIterator<Item = &'a SpotLight>,
+    ) {
+        let mut light_data = Vec::new();
+        let mut bounding_spheres = Vec::new();
+        for light in point_lights {
+            let color = light.color.to_linear_srgb().truncate() * light.intensity;
+            light_data.extend_from_slice(&[
+                [light.position.x, light.position.y, light.position.z, 0.0],
+                [color.x, color.y, color.z, 0.0],
+                [
+                    light.attenuation.constant,
+                    light.attenuation.linear,
+                    light.attenuation.quadratic,
+                    0.0,
+                ],
+                [0.0; 4],
+            ]);
+            bounding_spheres.push((light.position, light.influence_radius()));
+        }
+        for light in spot_lights {
+            let color = light.color.to_linear_srgb().truncate() * light.intensity;
+            let direction = light.direction.normalize();
+            light_data.extend_from_slice(&[
+                [light.position.x, light.position.y, light.position.z, 1.0],
+                [color.x, color.y, color.z, 0.0],
+                [
+                    light.attenuation.constant,
+                    light.attenuation.linear,
+                    light.attenuation.quadratic,
+                    light.cutoff.0,
+                ],
+                [direction.x, direction.y, direction.z, 0.0],
+            ]);
+            bounding_spheres.push((light.position, light.influence_radius()));
+        }
+
+        let z_near = camera.z_near().max(0.001);
+        let z_far = camera.z_far().max(z_near + 0.001);
+        let (cluster_data, light_indices) =
+            assign_clusters(camera, z_near, z_far, &bounding_spheres);
+
+        fill_data_texture(&self.context, &mut self.light_data, &light_data);
+        fill_data_texture(&self.context, &mut self.cluster_data, &cluster_data);
+        fill_data_texture(&self.context, &mut self.light_indices, &light_indices);
+        self.view_projection = camera.projection() * camera.view();
+        self.view_position = *camera.position();
+        self.view_direction = camera.view_direction();
+        self.z_near = z_near;
+        self.z_far = z_far;
+    }
+}
+
+impl Light for ClusteredLights {
+    fn shader_source(&self, i: u32) -> String {
+        format!(
+            "
+                uniform sampler2D clusterLightData{i};
+                uniform sampler2D clusterData{i};
+                uniform sampler2D clusterLightIndices{i};
+                uniform mat4 clusterViewProjection{i};
+                uniform vec3 clusterViewPosition{i};
+                uniform vec3 clusterViewDirection{i};
+                uniform vec2 clusterNearFar{i};
+
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    // Find the cluster containing the position
+                    vec4 clip_position = clusterViewProjection{i} * vec4(position, 1.0);
+                    vec2 screen_position = 0.5 * clip_position.xy / clip_position.w + 0.5;
+                    float depth = dot(position - clusterViewPosition{i}, clusterViewDirection{i});
+                    float depth_slice = log(max(depth, clusterNearFar{i}.x) / clusterNearFar{i}.x) / log(clusterNearFar{i}.y / clusterNearFar{i}.x);
+                    int x = clamp(int(screen_position.x * {count_x}.0), 0, {count_x} - 1);
+                    int y = clamp(int(screen_position.y * {count_y}.0), 0, {count_y} - 1);
+                    int z = clamp(int(depth_slice * {count_z}.0), 0, {count_z} - 1);
+                    vec2 cluster = texelFetch(clusterData{i}, ivec2(x + {count_x} * y, z), 0).xy;
+
+                    // Evaluate the lights in the cluster
+                    vec3 color = vec3(0.0);
+                    int offset = int(cluster.x);
+                    int count = int(cluster.y);
+                    for (int j = offset; j < offset + count; j++)
+                    {{
+                        int light = int(texelFetch(clusterLightIndices{i}, ivec2(j % {index_width}, j / {index_width}), 0).x);
+                        vec4 position_and_type = texelFetch(clusterLightData{i}, ivec2(0, light), 0);
+                        vec3 light_color = texelFetch(clusterLightData{i}, ivec2(1, light), 0).rgb;
+                        vec4 attenuation_and_cutoff = texelFetch(clusterLightData{i}, ivec2(2, light), 0);
+
+                        vec3 light_direction = position_and_type.xyz - position;
+                        float distance = length(light_direction);
+                        light_direction = light_direction / distance;
+                        light_color = attenuate(light_color, attenuation_and_cutoff.xyz, distance);
+
+                        if (position_and_type.w > 0.5) {{
+                            // Spot light
+                            vec3 direction = texelFetch(clusterLightData{i}, ivec2(3, light), 0).xyz;
+                            float angle = acos(dot(-light_direction, direction));
+                            float cutoff = attenuation_and_cutoff.w;
+                            if (angle >= cutoff) {{
+                                continue;
+                            }}
+                            light_color *= 1.0 - smoothstep(0.75 * cutoff, cutoff, angle);
+                        }}
+                        color += calculate_light(light_color, light_direction, surface_color, view_direction, normal, metallic, roughness);
+                    }}
+                    return color;
+                }}
+
+            ",
+            count_x = CLUSTER_COUNT_X,
+            count_y = CLUSTER_COUNT_Y,
+            count_z = CLUSTER_COUNT_Z,
+            index_width = INDEX_TEXTURE_WIDTH,
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        program.use_texture(&format!("clusterLightData{}", i), &self.light_data);
+        program.use_texture(&format!("clusterData{}", i), &self.cluster_data);
+        program.use_texture(&format!("clusterLightIndices{}", i), &self.light_indices);
+        program.use_uniform(&format!("clusterViewProjection{}", i), self.view_projection);
+        program.use_uniform(&format!("clusterViewPosition{}", i), self.view_position);
+        program.use_uniform(&format!("clusterViewDirection{}", i), self.view_direction);
+        program.use_uniform(
+            &format!("clusterNearFar{}", i),
+            vec2(self.z_near, self.z_far),
+        );
+    }
+
+    fn id(&self) -> LightId {
+        LightId::ClusteredLights
+    }
+}
+
+type ClusterIndex = (usize, usize, usize);
+
+///
+/// Assigns the lights with the given bounding spheres to the clusters of the given camera.
+/// Returns the offset into the light indices and the number of lights for each cluster together with the light indices.
+///
+fn assign_clusters(
+    camera: &Camera,
+    z_near: f32,
+    z_far: f32,
+    bounding_spheres: &[(Vec3, f32)],
+) -> (Vec<[f32; 2]>, Vec<f32>) {
+    let mut clusters = vec![Vec::new(); CLUSTER_COUNT_X * CLUSTER_COUNT_Y * CLUSTER_COUNT_Z];
+    for (index, (position, radius)) in bounding_spheres.iter().enumerate() {
+        if let Some((min, max)) = cluster_bounds(camera, z_near, z_far, *position, *radius) {
+            for z in min.2..=max.2 {
+                for y in min.1..=max.1 {
+                    for x in min.0..=max.0 {
+                        clusters[x + CLUSTER_COUNT_X * (y + CLUSTER_COUNT_Y * z)]
+                            .push(index as f32);
+                    }
+                }
+            }
+        }
+    }
+    let mut cluster_data = Vec::with_capacity(clusters.len());
+    let mut light_indices = Vec::new();
+    for cluster in clusters {
+        cluster_data.push([light_indices.len() as f32, cluster.len() as f32]);
+        light_indices.extend(cluster);
+    }
+    (cluster_data, light_indices)
+}
+
+///
+/// Returns the minimum and maximum cluster indices of the clusters that intersects the given sphere
+/// or `None` if the sphere is outside the view frustum.
+///
+fn cluster_bounds(
+    camera: &Camera,
+    z_near: f32,
+    z_far: f32,
+    position: Vec3,
+    radius: f32,
+) -> Option<(ClusterIndex, ClusterIndex)> {
+    if radius <= 0.0 {
+        return None;
+    }
+    let center = (camera.view() * position.extend(1.0)).truncate();
+    let depth = -center.z;
+    let depth_min = (depth - radius).max(z_near);
+    let depth_max = (depth + radius).min(z_far);
+    if depth_min > depth_max {
+        return None;
+    }
+    let slice = |depth: f32| {
+        let s = (depth / z_near).ln() / (z_far / z_near).ln();
+        ((s * CLUSTER_COUNT_Z as f32) as usize).min(CLUSTER_COUNT_Z - 1)
+    };
+
+    let mut min = vec2(0.0f32, 0.0);
+    let mut max = vec2(1.0f32, 1.0);
+    if radius.is_finite() && depth - radius > z_near {
+        // Project the corners of the axis aligned bounding box of the sphere in view space
+        min = vec2(f32::MAX, f32::MAX);
+        max = vec2(f32::MIN, f32::MIN);
+        for x in [center.x - radius, center.x + radius] {
+            for y in [center.y - radius, center.y + radius] {
+                for d in [depth_min, depth_max] {
+                    let p = camera.projection() * vec4(x, y, -d, 1.0);
+                    let p = 0.5 * p.truncate().truncate() / p.w + vec2(0.5, 0.5);
+                    min = vec2(min.x.min(p.x), min.y.min(p.y));
+                    max = vec2(max.x.max(p.x), max.y.max(p.y));
+                }
+            }
+        }
+        if max.x < 0.0 || max.y < 0.0 || min.x > 1.0 || min.y > 1.0 {
+            return None;
+        }
+    }
+    let tile = |v: f32, count: usize| ((v.clamp(0.0, 1.0) * count as f32) as usize).min(count - 1);
+    Some((
+        (
+            tile(min.x, CLUSTER_COUNT_X),
+            tile(min.y, CLUSTER_COUNT_Y),
+            slice(depth_min),
+        ),
+        (
+            tile(max.x, CLUSTER_COUNT_X),
+            tile(max.y, CLUSTER_COUNT_Y),
+            slice(depth_max),
+        ),
+    ))
+}
+
+///
+/// Returns a new texture with the given size for storing data which can be looked up using `texelFetch`, see [fill_data_texture].
+///
+fn data_texture<T: TextureDataType>(context: &Context, width: usize, height: usize) -> Texture2D {
+    Texture2D::new_empty::<T>(
+        context,
+        width as u32,
+        height as u32,
+        Interpolation::Nearest,
+        Interpolation::Nearest,
+        None,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    )
+}
+
+///
+/// Fills the given data texture with the given data, row by row, where the remaining texels are set to the default value.
+/// The existing texture is reused if it is large enough, otherwise it is replaced by a texture with the same width and enough rows.
+///
+fn fill_data_texture<T: TextureDataType + Default + Copy>(
+    context: &Context,
+    texture: &mut Texture2D,
+    data: &[T],
+) {
+    let width = texture.width() as usize;
+    let height = data.len().div_ceil(width);
+    if height > texture.height() as usize {
+        *texture = data_texture::<T>(context, width, height);
+    }
+    texture.fill(&texture_data(data, width, texture.height() as usize));
+}
+
+///
+/// Returns the given data laid out row by row in a texture with the given width and height, where the remaining texels are set to the default value.
+///
+fn texture_data<T: Default + Copy>(data: &[T], width: usize, height: usize) -> Vec<T> {
+    let mut rows = data.chunks(width).collect::<Vec<_>>();
+    // The rows are reversed since the rows are flipped when filling the texture
+    rows.reverse();
+    let mut texture_data = vec![T::default(); width * (height - rows.len())];
+    for (i, row) in rows.into_iter().enumerate() {
+        texture_data.extend_from_slice(row);
+        if i == 0 {
+            texture_data.resize(texture_data.len() + width - row.len(), T::default());
+        }
+    }
+    texture_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new_perspective(
+            Viewport::new_at_origo(160, 90),
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+            degrees(60.0),
+            0.1,
+            100.0,
+        )
+    }
+
+    fn lights_in_cluster(
+        cluster_data: &[[f32; 2]],
+        light_indices: &[f32],
+        (x, y, z): ClusterIndex,
+    ) -> Vec<usize> {
+        let [offset, count] = cluster_data[x + CLUSTER_COUNT_X * (y + CLUSTER_COUNT_Y * z)];
+        light_indices[offset as usize..(offset + count) as usize]
+            .iter()
+            .map(|index| *index as usize)
+            .collect()
+    }
+
+    fn depth_slice(depth: f32) -> usize {
+        ((depth / 0.1).ln() / (100.0f32 / 0.1).ln() * CLUSTER_COUNT_Z as f32) as usize
+    }
+
+    #[test]
+    fn assign_lights_to_clusters() {
+        let camera = camera();
+        let (cluster_data, light_indices) = assign_clusters(
+            &camera,
+            0.1,
+            100.0,
+            &[
+                (vec3(-5.0, 0.0, -10.0), 1.0),
+                (vec3(5.0, 0.0, -10.0), 1.0),
+                (vec3(0.0, 0.0, -10.0), 2.0),
+            ],
+        );
+        assert_eq!(
+            cluster_data.len(),
+            CLUSTER_COUNT_X * CLUSTER_COUNT_Y * CLUSTER_COUNT_Z
+        );
+        let z = depth_slice(10.0);
+        assert_eq!(
+            lights_in_cluster(&cluster_data, &light_indices, (4, 4, z)),
+            vec![0]
+        );
+        assert_eq!(
+            lights_in_cluster(&cluster_data, &light_indices, (11, 4, z)),
+            vec![1]
+        );
+        assert_eq!(
+            lights_in_cluster(&cluster_data, &light_indices, (8, 4, z)),
+            vec![2]
+        );
+        assert!(
+            lights_in_cluster(&cluster_data, &light_indices, (8, 4, depth_slice(1.0))).is_empty()
+        );
+        assert!(
+            lights_in_cluster(&cluster_data, &light_indices, (8, 4, depth_slice(50.0))).is_empty()
+        );
+        assert!(lights_in_cluster(&cluster_data, &light_indices, (0, 0, z)).is_empty());
+    }
+
+    #[test]
+    fn cluster_offsets_are_consecutive() {
+        let (cluster_data, light_indices) = assign_clusters(
+            &camera(),
+            0.1,
+            100.0,
+            &[(vec3(-2.0, 1.0, -5.0), 3.0), (vec3(1.0, 0.0, -20.0), 10.0)],
+        );
+        let mut offset = 0.0;
+        for [cluster_offset, count] in cluster_data {
+            assert_eq!(cluster_offset, offset);
+            offset += count;
+        }
+        assert_eq!(offset as usize, light_indices.len());
+    }
+
+    #[test]
+    fn lights_outside_the_frustum_are_not_assigned() {
+        let (_, light_indices) = assign_clusters(
+            &camera(),
+            0.1,
+            100.0,
+            &[
+                (vec3(0.0, 0.0, 10.0), 1.0),
+                (vec3(100.0, 0.0, -10.0), 1.0),
+                (vec3(0.0, 0.0, -200.0), 1.0),
+                (vec3(0.0, 0.0, -10.0), 0.0),
+            ],
+        );
+        assert!(light_indices.is_empty());
+    }
+
+    #[test]
+    fn lights_without_attenuation_are_assigned_to_all_clusters() {
+        let (cluster_data, light_indices) = assign_clusters(
+            &camera(),
+            0.1,
+            100.0,
+            &[(vec3(0.0, 0.0, 0.0), f32::INFINITY)],
+        );
+        assert!(cluster_data.iter().all(|[_, count]| *count == 1.0));
+        assert_eq!(light_indices.len(), cluster_data.len());
+    }
+
+    #[test]
+    fn texture_data_layout() {
+        let data = [1, 2, 3, 4, 5];
+        let layout = texture_data(&data, 2, 4);
+        assert_eq!(layout, vec![0, 0, 5, 0, 3, 4, 1, 2]);
+        // The element at index j is found at texel (j % width, j / width) of the flipped texture
+        for (j, value) in data.iter().enumerate() {
+            assert_eq!(layout[(4 - 1 - j / 2) * 2 + j % 2], *value);
+        }
+        assert_eq!(texture_data(&[1, 2, 3, 4], 2, 2), vec![3, 4, 1, 2]);
+    }
+}
@@ -0,0 +1,344 @@
+use super::environment::PREFILTER_MIP_LEVELS;
+use crate::core::*;
+use crate::renderer::*;
+
+/// The first bytes of a serialized [CpuEnvironment].
+const MAGIC: &[u8; 8] = b"3DENV001";
+
+///
+/// The precomputed maps of an [Environment] stored on the CPU, see [Environment::to_cpu_environment] and [Environment::from_cpu_environment].
+/// This can be saved to and loaded from a file to avoid computing the maps every time an application starts.
+///
+/// The sides of the cube maps are ordered right, left, top, bottom, front and back like in [TextureCubeMap::new].
+///
+/// # Format
+/// [CpuEnvironment::serialize] produces the following binary format where all numbers are little endian:
+/// - The 8 bytes `3DENV001`.
+/// - The width and height of each side of the irradiance map as an unsigned 32-bit integer.
+/// - The width and height of each side of the largest mip level of the prefilter map as an unsigned 32-bit integer.
+/// - The number of mip levels of the prefilter map as an unsigned 32-bit integer (currently always 5).
+/// - The width of the BRDF lookup table as an unsigned 32-bit integer.
+/// - The height of the BRDF lookup table as an unsigned 32-bit integer.
+/// - The six sides of the irradiance map, where each side contains the texels row by row, each texel as four (RGBA) 16-bit floats.
+/// - The six sides of each mip level of the prefilter map, starting with the largest mip level and stored like the irradiance map.
+///   The width and height of a mip level is half of the size of the previous mip level, rounded down.
+/// - The BRDF lookup table, containing the texels row by row, each texel as two (RG) 32-bit floats.
+///
+pub struct CpuEnvironment {
+    /// The six sides of the irradiance map.
+    pub irradiance_map: Vec<CpuTexture>,
+    /// The six sides of each mip level of the prefilter map, starting with the largest mip level.
+    pub prefilter_map: Vec<Vec<CpuTexture>>,
+    /// The BRDF lookup table.
+    pub brdf_map: CpuTexture,
+}
+
+impl CpuEnvironment {
+    ///
+    /// Serializes the maps into the format described in [CpuEnvironment].
+    /// Returns an error if the maps are not valid, for example if the sides of a cube map have different sizes.
+    ///
+    pub fn serialize(&self) -> Result<Vec<u8>, RendererError> {
+        self.validate()?;
+        let mut bytes = MAGIC.to_vec();
+        for value in [
+            self.irradiance_map.first().map(|t| t.width).unwrap_or(0),
+            self.prefilter_map
+                .first()
+                .and_then(|sides| sides.first())
+                .map(|t| t.width)
+                .unwrap_or(0),
+            self.prefilter_map.len() as u32,
+            self.brdf_map.width,
+            self.brdf_map.height,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for side in self
+            .irradiance_map
+            .iter()
+            .chain(self.prefilter_map.iter().flatten())
+        {
+            for value in rgba_data(side).into_iter().flatten() {
+                bytes.extend_from_slice(&f16::from_f32(value).to_le_bytes());
+            }
+        }
+        for texel in rgba_data(&self.brdf_map) {
+            bytes.extend_from_slice(&texel[0].to_le_bytes());
+            bytes.extend_from_slice(&texel[1].to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    ///
+    /// Deserializes maps in the format described in [CpuEnvironment].
+    ///
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, RendererError> {
+        let invalid = |message: &str| RendererError::InvalidEnvironment(message.to_string());
+        if !bytes.starts_with(MAGIC) {
+            return Err(invalid("missing header"));
+        }
+        let mut remaining = &bytes[MAGIC.len()..];
+        let mut take = |count: usize| {
+            if remaining.len() < count {
+                return Err(invalid("unexpected end of data"));
+            }
+            let (taken, rest) = remaining.split_at(count);
+            remaining = rest;
+            Ok(taken)
+        };
+        let mut header = [0u32; 5];
+        for value in header.iter_mut() {
+            *value = u32::from_le_bytes(take(4)?.try_into().unwrap());
+        }
+        let [irradiance_size, prefilter_size, prefilter_mip_levels, brdf_width, brdf_height] =
+            header;
+        if prefilter_mip_levels != PREFILTER_MIP_LEVELS {
+            return Err(invalid("unsupported number of prefilter mip levels"));
+        }
+        // The sizes are checked to avoid overflow when the header is corrupt
+        let byte_count = |width: u32, height: u32, texel_size: usize| {
+            (width as usize)
+                .checked_mul(height as usize)
+                .and_then(|count| count.checked_mul(texel_size))
+                .ok_or_else(|| invalid("the size of a map is too large"))
+        };
+
+        let mut cube_map_sides = |size: u32| {
+            (0..6)
+                .map(|_| {
+                    let data = take(byte_count(size, size, 8)?)?
+                        .chunks_exact(8)
+                        .map(|texel| {
+                            [0, 2, 4, 6].map(|i| f16::from_le_bytes([texel[i], texel[i + 1]]))
+                        })
+                        .collect();
+                    Ok(cube_map_side(TextureData::RgbaF16(data), size))
+                })
+                .collect::<Result<Vec<_>, RendererError>>()
+        };
+        let irradiance_map = cube_map_sides(irradiance_size)?;
+        let prefilter_map = (0..prefilter_mip_levels)
+            .map(|mip| cube_map_sides((prefilter_size >> mip).max(1)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let brdf_data = take(byte_count(brdf_width, brdf_height, 8)?)?
+            .chunks_exact(8)
+            .map(|texel| [0, 4].map(|i| f32::from_le_bytes(texel[i..i + 4].try_into().unwrap())))
+            .collect();
+        if !remaining.is_empty() {
+            return Err(invalid("unexpected data after the BRDF lookup table"));
+        }
+        let cpu_environment = Self {
+            irradiance_map,
+            prefilter_map,
+            brdf_map: CpuTexture {
+                data: TextureData::RgF32(brdf_data),
+                width: brdf_width,
+                height: brdf_height,
+                mip_map_filter: None,
+                wrap_s: Wrapping::ClampToEdge,
+                wrap_t: Wrapping::ClampToEdge,
+                ..Default::default()
+            },
+        };
+        cpu_environment.validate()?;
+        Ok(cpu_environment)
+    }
+
+    ///
+    /// Saves the maps to a file at the given path in the format described in [CpuEnvironment].
+    ///
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<(), RendererError> {
+        std::fs::write(path, self.serialize()?)?;
+        Ok(())
+    }
+
+    ///
+    /// Loads the maps from a file at the given path in the format described in [CpuEnvironment].
+    ///
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, RendererError> {
+        Self::deserialize(&std::fs::read(path)?)
+    }
+
+    pub(super) fn validate(&self) -> Result<(), RendererError> {
+        let invalid = |message: &str| Err(RendererError::InvalidEnvironment(message.to_string()));
+        let is_valid_cube_map = |sides: &[CpuTexture], size: u32| {
+            size > 0
+                && sides.len() == 6
+                && sides
+                    .iter()
+                    .all(|side| side.width == size && side.height == size && has_valid_data(side))
+        };
+        if self.irradiance_map.is_empty()
+            || !is_valid_cube_map(&self.irradiance_map, self.irradiance_map[0].width)
+        {
+            return invalid("the irradiance map must have six square sides of the same size");
+        }
+        if self.prefilter_map.len() != PREFILTER_MIP_LEVELS as usize
+            || self.prefilter_map[0].is_empty()
+        {
+            return invalid("the prefilter map has the wrong number of mip levels");
+        }
+        let prefilter_size = self.prefilter_map[0][0].width;
+        if prefilter_size < 1 << (PREFILTER_MIP_LEVELS - 1)
+            || self
+                .prefilter_map
+                .iter()
+                .enumerate()
+                .any(|(mip, sides)| !is_valid_cube_map(sides, prefilter_size >> mip))
+        {
+            return invalid("the mip levels of the prefilter map must have six square sides which are half the size of the previous mip level");
+        }
+        if self.brdf_map.width == 0 || self.brdf_map.height == 0 {
+            return invalid("the BRDF lookup table is empty");
+        }
+        if !has_valid_data(&self.brdf_map) {
+            return invalid("the BRDF lookup table does not contain width times height texels");
+        }
+        Ok(())
+    }
+}
+
+///
+/// Returns whether or not the number of texels in the data of the given texture is equal to the width times the height of the texture.
+///
+fn has_valid_data(texture: &CpuTexture) -> bool {
+    let texel_count = match &texture.data {
+        TextureData::RU8(data) => data.len(),
+        TextureData::RgU8(data) => data.len(),
+        TextureData::RgbU8(data) => data.len(),
+        TextureData::RgbaU8(data) => data.len(),
+        TextureData::RF16(data) => data.len(),
+        TextureData::RgF16(data) => data.len(),
+        TextureData::RgbF16(data) => data.len(),
+        TextureData::RgbaF16(data) => data.len(),
+        TextureData::RF32(data) => data.len(),
+        TextureData::RgF32(data) => data.len(),
+        TextureData::RgbF32(data) => data.len(),
+        TextureData::RgbaF32(data) => data.len(),
+    };
+    texel_count as u64 == texture.width as u64 * texture.height as u64
+}
+
+fn cube_map_side(data: TextureData, size: u32) -> CpuTexture {
+    CpuTexture {
+        data,
+        width: size,
+        height: size,
+        wrap_s: Wrapping::ClampToEdge,
+        wrap_t: Wrapping::ClampToEdge,
+        ..Default::default()
+    }
+}
+
+///
+/// Returns the texels of the given texture as RGBA, where texels with fewer channels are expanded and 8-bit values are converted to the range `[0, 1]`.
+///
+pub(super) fn rgba_data(texture: &CpuTexture) -> Vec<[f32; 4]> {
+    let u8_to_f32 = |v: u8| v as f32 / 255.0;
+    match &texture.data {
+        TextureData::RU8(data) => data
+            .iter()
+            .map(|&r| [u8_to_f32(r), 0.0, 0.0, 1.0])
+            .collect(),
+        TextureData::RgU8(data) => data
+            .iter()
+            .map(|&[r, g]| [u8_to_f32(r), u8_to_f32(g), 0.0, 1.0])
+            .collect(),
+        TextureData::RgbU8(data) => data
+            .iter()
+            .map(|&[r, g, b]| [u8_to_f32(r), u8_to_f32(g), u8_to_f32(b), 1.0])
+            .collect(),
+        TextureData::RgbaU8(data) => data.iter().map(|c| c.map(u8_to_f32)).collect(),
+        TextureData::RF16(data) => data.iter().map(|&r| [r.to_f32(), 0.0, 0.0, 1.0]).collect(),
+        TextureData::RgF16(data) => data
+            .iter()
+            .map(|&[r, g]| [r.to_f32(), g.to_f32(), 0.0, 1.0])
+            .collect(),
+        TextureData::RgbF16(data) => data
+            .iter()
+            .map(|&[r, g, b]| [r.to_f32(), g.to_f32(), b.to_f32(), 1.0])
+            .collect(),
+        TextureData::RgbaF16(data) => data.iter().map(|c| c.map(f16::to_f32)).collect(),
+        TextureData::RF32(data) => data.iter().map(|&r| [r, 0.0, 0.0, 1.0]).collect(),
+        TextureData::RgF32(data) => data.iter().map(|&[r, g]| [r, g, 0.0, 1.0]).collect(),
+        TextureData::RgbF32(data) => data.iter().map(|&[r, g, b]| [r, g, b, 1.0]).collect(),
+        TextureData::RgbaF32(data) => data.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube_map(size: u32, value: f32) -> Vec<CpuTexture> {
+        (0..6)
+            .map(|side| {
+                let texel = [value, side as f32, 0.5, 1.0].map(f16::from_f32);
+                cube_map_side(
+                    TextureData::RgbaF16(vec![texel; (size * size) as usize]),
+                    size,
+                )
+            })
+            .collect()
+    }
+
+    fn cpu_environment() -> CpuEnvironment {
+        CpuEnvironment {
+            irradiance_map: cube_map(2, 0.25),
+            prefilter_map: (0..PREFILTER_MIP_LEVELS)
+                .map(|mip| cube_map(16 >> mip, mip as f32))
+                .collect(),
+            brdf_map: CpuTexture {
+                data: TextureData::RgF32((0..12).map(|i| [i as f32, 0.5]).collect()),
+                width: 4,
+                height: 3,
+                mip_map_filter: None,
+                wrap_s: Wrapping::ClampToEdge,
+                wrap_t: Wrapping::ClampToEdge,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn serialize_deserialize() {
+        let environment = cpu_environment();
+        let bytes = environment.serialize().unwrap();
+        let deserialized = CpuEnvironment::deserialize(&bytes).unwrap();
+        assert_eq!(deserialized.irradiance_map, environment.irradiance_map);
+        assert_eq!(deserialized.prefilter_map, environment.prefilter_map);
+        assert_eq!(deserialized.brdf_map, environment.brdf_map);
+    }
+
+    #[test]
+    fn deserialize_invalid() {
+        let bytes = cpu_environment().serialize().unwrap();
+        assert!(CpuEnvironment::deserialize(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(CpuEnvironment::deserialize(&trailing).is_err());
+
+        let mut huge = bytes.clone();
+        huge[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CpuEnvironment::deserialize(&huge).is_err());
+
+        let mut empty = bytes;
+        empty[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(CpuEnvironment::deserialize(&empty).is_err());
+    }
+
+    #[test]
+    fn validate_data_length() {
+        let mut environment = cpu_environment();
+        environment.brdf_map.width = 5;
+        assert!(environment.serialize().is_err());
+
+        let mut environment = cpu_environment();
+        environment.irradiance_map[3].data = TextureData::RgbaF16(Vec::new());
+        assert!(environment.validate().is_err());
+    }
+}
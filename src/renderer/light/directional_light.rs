@@ -2,20 +2,81 @@ use crate::core::*;
 use crate::renderer::light::*;
 use crate::renderer::*;
 
+/// The maximum number of cascades supported by [DirectionalLight::generate_cascaded_shadow_map].
+pub const MAX_SHADOW_CASCADES: u32 = 4;
+
+///
+/// Defines how the view frustum of a camera is split into cascades when generating a [cascaded shadow map](DirectionalLight::generate_cascaded_shadow_map).
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CascadeSplitScheme {
+    /// The cascades are evenly distributed between the near and far plane.
+    Uniform,
+    /// The cascades are distributed logarithmically between the near and far plane, which gives more resolution close to the camera.
+    Logarithmic,
+    /// A blend between the [CascadeSplitScheme::Uniform] and [CascadeSplitScheme::Logarithmic] split schemes,
+    /// where a lambda value of 0 gives uniform splits and a value of 1 gives logarithmic splits.
+    Practical(f32),
+}
+
+impl CascadeSplitScheme {
+    ///
+    /// Returns the distance from the camera to the far end of each of the cascades when splitting the range between `z_near` and `z_far`.
+    ///
+    pub fn split_distances(&self, z_near: f32, z_far: f32, cascade_count: u32) -> Vec<f32> {
+        let z_near = z_near.max(0.001);
+        (1..=cascade_count)
+            .map(|i| {
+                let f = i as f32 / cascade_count as f32;
+                let uniform = z_near + (z_far - z_near) * f;
+                let logarithmic = z_near * (z_far / z_near).powf(f);
+                match self {
+                    Self::Uniform => uniform,
+                    Self::Logarithmic => logarithmic,
+                    Self::Practical(lambda) => {
+                        let lambda = lambda.clamp(0.0, 1.0);
+                        lambda * logarithmic + (1.0 - lambda) * uniform
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for CascadeSplitScheme {
+    fn default() -> Self {
+        Self::Practical(0.5)
+    }
+}
+
+struct CascadedShadowMap {
+    texture: DepthTexture2DArray,
+    matrices: Vec<Mat4>,
+    split_distances: Vec<f32>,
+    bias_scales: Vec<f32>,
+    view_position: Vec3,
+    view_direction: Vec3,
+}
+
 ///
 /// A light which shines in the given direction.
-/// The light will cast shadows if you [generate a shadow map](DirectionalLight::generate_shadow_map).
+/// The light will cast shadows if you [generate a shadow map](DirectionalLight::generate_shadow_map)
+/// or [generate a cascaded shadow map](DirectionalLight::generate_cascaded_shadow_map).
 ///
 pub struct DirectionalLight {
     context: Context,
     shadow_texture: Option<DepthTexture2D>,
     shadow_matrix: Mat4,
+    cascaded_shadow_map: Option<CascadedShadowMap>,
+    static_shadow_map: Option<StaticShadowMap>,
     /// The intensity of the light. This allows for higher intensity than 1 which can be used to simulate high intensity light sources like the sun.
     pub intensity: f32,
     /// The base color of the light.
     pub color: Srgba,
     /// The direction the light shines.
     pub direction: Vec3,
+    /// The [ShadowSettings] used when the light casts shadows.
+    pub shadow_settings: ShadowSettings,
 }
 
 impl DirectionalLight {
@@ -30,9 +91,12 @@ impl DirectionalLight {
             context: context.clone(),
             shadow_matrix: Mat4::identity(),
             shadow_texture: None,
+            cascaded_shadow_map: None,
+            static_shadow_map: None,
             intensity,
             color,
             direction: *direction,
+            shadow_settings: ShadowSettings::default(),
         }
     }
 
@@ -43,6 +107,8 @@ impl DirectionalLight {
     pub fn clear_shadow_map(&mut self) {
         self.shadow_texture = None;
         self.shadow_matrix = Mat4::identity();
+        self.cascaded_shadow_map = None;
+        self.static_shadow_map = None;
     }
 
     ///
@@ -56,30 +122,13 @@ impl DirectionalLight {
         texture_size: u32,
         geometries: impl IntoIterator<Item = impl Geometry> + Clone,
     ) {
-        let up = compute_up_direction(self.direction);
-
-        let viewport = Viewport::new_at_origo(texture_size, texture_size);
-        let mut aabb = AxisAlignedBoundingBox::EMPTY;
-        for geometry in geometries.clone() {
-            aabb.expand_with_aabb(&geometry.aabb());
-        }
-        if aabb.is_empty() {
-            return;
-        }
-        let target = aabb.center();
-        let position = target - aabb.max().distance(aabb.min()) * self.direction;
-        let z_far = aabb.distance_max(&position);
-        let z_near = aabb.distance(&position);
-        let frustum_height = aabb.max().distance(aabb.min()); // TODO: more tight fit
-        let shadow_camera = Camera::new_orthographic(
-            viewport,
-            position,
-            target,
-            up,
-            frustum_height,
-            z_near,
-            z_far,
-        );
+        let shadow_camera = match self.shadow_camera(
+            texture_size,
+            geometries.clone().into_iter().map(|g| g.aabb()),
+        ) {
+            Some(camera) => camera,
+            None => return,
+        };
         let mut shadow_texture = DepthTexture2D::new::<f32>(
             &self.context,
             texture_size,
@@ -87,7 +136,7 @@ impl DirectionalLight {
             Wrapping::ClampToEdge,
             Wrapping::ClampToEdge,
         );
-        let depth_material = DepthMaterial {
+        let mut depth_material = DepthMaterial {
             render_states: RenderStates {
                 write_mask: WriteMask::DEPTH,
                 ..Default::default()
@@ -102,6 +151,8 @@ impl DirectionalLight {
                     .into_iter()
                     .filter(|g| shadow_camera.in_frustum(&g.aabb()))
                 {
+                    depth_material.alpha_mask = geometry.alpha_mask();
+                    depth_material.vertex_deformation = geometry.vertex_deformation();
                     render_with_material(
                         &self.context,
                         &shadow_camera,
@@ -115,6 +166,237 @@ impl DirectionalLight {
             .unwrap();
         self.shadow_texture = Some(shadow_texture);
         self.shadow_matrix = shadow_matrix(&shadow_camera);
+        self.cascaded_shadow_map = None;
+    }
+
+    ///
+    /// Generate a shadow map like [DirectionalLight::generate_shadow_map], except that the shadow casters are split into static and dynamic geometries.
+    /// The static geometries are rendered into a separate shadow map which is cached and reused in subsequent calls,
+    /// so only the dynamic geometries are rendered each time this method is called.
+    /// The cached shadow map is rendered again when the light direction, the texture size or the static geometries change,
+    /// where a static geometry is considered changed if the number of static geometries, their bounding boxes, the cutout or alpha value of their alpha masks or the shader source of their vertex deformations have changed.
+    /// Use [DirectionalLight::invalidate_static_shadow_map] to force the cached shadow map to be rendered again, for example if a static geometry is modified without changing its bounding box
+    /// or if the alpha mask texture or the uniforms or textures of a vertex deformation of a static geometry have changed.
+    ///
+    /// The shadow map is fitted to the bounding box of all of the geometries when the cached shadow map is rendered,
+    /// so the cached shadow map is also rendered again when a dynamic geometry moves outside of that bounding box.
+    ///
+    pub fn generate_cached_shadow_map(
+        &mut self,
+        texture_size: u32,
+        static_geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+        dynamic_geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+    ) {
+        let direction = self.direction.normalize();
+        let key = static_shadow_map_key(
+            &[direction.x, direction.y, direction.z],
+            texture_size,
+            static_geometries.clone(),
+        );
+        let mut static_shadow_map = self.static_shadow_map.take();
+        let mut shadow_texture = self.shadow_texture.take();
+        let result = generate_cached_shadow_map(
+            &self.context,
+            &mut shadow_texture,
+            &mut static_shadow_map,
+            key,
+            static_geometries,
+            dynamic_geometries,
+            |bounds| self.shadow_camera(texture_size, [bounds]),
+        );
+        self.static_shadow_map = static_shadow_map;
+        self.shadow_texture = shadow_texture;
+        if let Some(shadow_camera) = result {
+            self.shadow_matrix = shadow_matrix(&shadow_camera);
+            self.cascaded_shadow_map = None;
+        }
+    }
+
+    ///
+    /// Discards the cached shadow map containing the static geometries, which forces it to be rendered again the next time [DirectionalLight::generate_cached_shadow_map] is called.
+    ///
+    pub fn invalidate_static_shadow_map(&mut self) {
+        self.static_shadow_map = None;
+    }
+
+    ///
+    /// Returns an orthographic camera which views the given bounding boxes from the direction of the light or `None` if all of the bounding boxes are empty.
+    ///
+    fn shadow_camera(
+        &self,
+        texture_size: u32,
+        aabbs: impl IntoIterator<Item = AxisAlignedBoundingBox>,
+    ) -> Option<Camera> {
+        let up = compute_up_direction(self.direction);
+
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
+        let mut aabb = AxisAlignedBoundingBox::EMPTY;
+        for other in aabbs {
+            aabb.expand_with_aabb(&other);
+        }
+        if aabb.is_empty() {
+            return None;
+        }
+        let target = aabb.center();
+        let position = target - aabb.max().distance(aabb.min()) * self.direction;
+        let z_far = aabb.distance_max(&position);
+        let z_near = aabb.distance(&position);
+        let frustum_height = aabb.max().distance(aabb.min()); // TODO: more tight fit
+        Some(Camera::new_orthographic(
+            viewport,
+            position,
+            target,
+            up,
+            frustum_height,
+            z_near,
+            z_far,
+        ))
+    }
+
+    ///
+    /// Generate a cascaded shadow map which is used to simulate shadows from the directional light onto the geometries given as input.
+    /// The view frustum of the given camera is split into `cascade_count` cascades using the given split scheme
+    /// and each cascade is rendered into a layer of a shadow map with the given texture size.
+    /// This gives high resolution shadows close to the camera and lower resolution shadows far away from the camera,
+    /// which is useful for large scenes where a [single shadow map](DirectionalLight::generate_shadow_map) becomes too blurry.
+    ///
+    /// The shadow map only covers the frustum of the given camera, so it needs to be generated again whenever the camera moves.
+    /// The shadows end at the far plane of the camera or the farthest geometry, whichever is closest.
+    /// It is recomended that the texture size is power of 2.
+    ///
+    /// # Panic
+    /// Will panic if the cascade count is zero or larger than [MAX_SHADOW_CASCADES].
+    ///
+    pub fn generate_cascaded_shadow_map(
+        &mut self,
+        texture_size: u32,
+        cascade_count: u32,
+        split_scheme: CascadeSplitScheme,
+        camera: &Camera,
+        geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+    ) {
+        if cascade_count == 0 || cascade_count > MAX_SHADOW_CASCADES {
+            panic!(
+                "the number of shadow cascades must be between 1 and {}, got {}",
+                MAX_SHADOW_CASCADES, cascade_count
+            );
+        }
+        let direction = self.direction.normalize();
+        let up = compute_up_direction(direction);
+        let right = direction.cross(up).normalize();
+        let up = right.cross(direction);
+
+        let mut aabb = AxisAlignedBoundingBox::EMPTY;
+        for geometry in geometries.clone() {
+            aabb.expand_with_aabb(&geometry.aabb());
+        }
+        if aabb.is_empty() {
+            return;
+        }
+        let z_near = camera.z_near().max(0.001);
+        let z_far = camera
+            .z_far()
+            .min(aabb.distance_max(camera.position()))
+            .max(z_near * 1.01);
+        let split_distances = split_scheme.split_distances(z_near, z_far, cascade_count);
+        let aabb_corners = [
+            aabb.min(),
+            vec3(aabb.max().x, aabb.min().y, aabb.min().z),
+            vec3(aabb.min().x, aabb.max().y, aabb.min().z),
+            vec3(aabb.min().x, aabb.min().y, aabb.max().z),
+            vec3(aabb.max().x, aabb.max().y, aabb.min().z),
+            vec3(aabb.max().x, aabb.min().y, aabb.max().z),
+            vec3(aabb.min().x, aabb.max().y, aabb.max().z),
+            aabb.max(),
+        ];
+
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
+        let mut shadow_texture = DepthTexture2DArray::new::<f32>(
+            &self.context,
+            texture_size,
+            texture_size,
+            cascade_count,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        );
+        let mut depth_material = DepthMaterial {
+            render_states: RenderStates {
+                write_mask: WriteMask::DEPTH,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut matrices = Vec::new();
+        let mut bias_scales = Vec::new();
+        let mut cascade_near = z_near;
+        for (layer, cascade_far) in split_distances.iter().enumerate() {
+            let corners = frustum_corners(camera, cascade_near, *cascade_far);
+            let center = corners.iter().fold(vec3(0.0, 0.0, 0.0), |acc, c| acc + c) / 8.0;
+            let radius = corners
+                .iter()
+                .map(|c| c.distance(center))
+                .fold(0.0f32, f32::max)
+                .max(0.001);
+
+            // Snap the center to the texels of the shadow map to avoid flickering when the camera moves
+            let texel_size = 2.0 * radius / texture_size as f32;
+            let snap = |v: f32| (v / texel_size).floor() * texel_size;
+            let center = right * snap(center.dot(right))
+                + up * snap(center.dot(up))
+                + direction * center.dot(direction);
+
+            // Move the shadow camera back so that all geometries between the light and the cascade are casting shadows
+            let back_distance = aabb_corners
+                .iter()
+                .map(|c| (center - c).dot(direction))
+                .fold(radius, f32::max);
+            let position = center - back_distance * direction;
+            let shadow_camera = Camera::new_orthographic(
+                viewport,
+                position,
+                center,
+                up,
+                2.0 * radius,
+                0.0,
+                back_distance + radius,
+            );
+            shadow_texture
+                .as_depth_target(layer as u32)
+                .clear(ClearState::default())
+                .write::<RendererError>(|| {
+                    for geometry in geometries
+                        .clone()
+                        .into_iter()
+                        .filter(|g| shadow_camera.in_frustum(&g.aabb()))
+                    {
+                        depth_material.alpha_mask = geometry.alpha_mask();
+                        depth_material.vertex_deformation = geometry.vertex_deformation();
+                        render_with_material(
+                            &self.context,
+                            &shadow_camera,
+                            &geometry,
+                            &depth_material,
+                            &[],
+                        );
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            matrices.push(shadow_matrix(&shadow_camera));
+            // The shadow bias is relative to the size of 64 texels instead of the depth range, so the bias follows the resolution of each cascade
+            bias_scales.push(64.0 * 2.0 * radius / texture_size as f32 / (back_distance + radius));
+            cascade_near = *cascade_far;
+        }
+        self.cascaded_shadow_map = Some(CascadedShadowMap {
+            texture: shadow_texture,
+            matrices,
+            split_distances,
+            bias_scales,
+            view_position: *camera.position(),
+            view_direction: camera.view_direction(),
+        });
+        self.shadow_texture = None;
+        self.shadow_matrix = Mat4::identity();
     }
 
     ///
@@ -123,26 +405,67 @@ impl DirectionalLight {
     pub fn shadow_map(&self) -> Option<&DepthTexture2D> {
         self.shadow_texture.as_ref()
     }
+
+    ///
+    /// Returns a reference to the cascaded shadow map if it has been generated, where each layer contains one cascade.
+    ///
+    pub fn cascaded_shadow_map(&self) -> Option<&DepthTexture2DArray> {
+        self.cascaded_shadow_map.as_ref().map(|c| &c.texture)
+    }
+}
+
+///
+/// Returns the eight corners of the part of the camera frustum between the two given distances from the camera.
+///
+fn frustum_corners(camera: &Camera, near: f32, far: f32) -> [Vec3; 8] {
+    let view_direction = camera.view_direction();
+    let mut corners = [vec3(0.0, 0.0, 0.0); 8];
+    for (i, (u, v)) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+        .into_iter()
+        .enumerate()
+    {
+        let origin = camera.position_at_uv_coordinates((u, v));
+        let direction = camera.view_direction_at_uv_coordinates((u, v));
+        let offset = (origin - camera.position()).dot(view_direction);
+        let cos_angle = direction.dot(view_direction);
+        corners[2 * i] = origin + direction * (near - offset) / cos_angle;
+        corners[2 * i + 1] = origin + direction * (far - offset) / cos_angle;
+    }
+    corners
+}
+
+impl DirectionalLight {
+    fn shadow_shader_source(&self, i: u32) -> Option<String> {
+        if self.cascaded_shadow_map.is_some() {
+            Some(cascaded_shadow_map_shader_source(
+                &self.shadow_settings,
+                i,
+                MAX_SHADOW_CASCADES,
+            ))
+        } else if self.shadow_texture.is_some() {
+            Some(shadow_map_2d_shader_source(&self.shadow_settings, i))
+        } else {
+            None
+        }
+    }
 }
 
 impl Light for DirectionalLight {
     fn shader_source(&self, i: u32) -> String {
-        if self.shadow_texture.is_some() {
+        if let Some(shadow) = self.shadow_shader_source(i) {
             format!(
                 "
-                    uniform sampler2D shadowMap{};
-                    uniform mat4 shadowMVP{};
-
-                    uniform vec3 color{};
-                    uniform vec3 direction{};
+                    {}
+                    uniform vec3 color{i};
+                    uniform vec3 direction{i};
 
-                    vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                    vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
                     {{
-                        return calculate_light(color{}, -direction{}, surface_color, view_direction, normal, metallic, roughness)
-                            * calculate_shadow(-direction{}, normal, shadowMap{}, shadowMVP{}, position);
+                        return calculate_light(color{i}, -direction{i}, surface_color, view_direction, normal, metallic, roughness)
+                            * calculate_shadow{i}(-direction{i}, normal, position);
                     }}
 
-                ", i, i, i, i, i, i, i, i, i, i)
+                ", shadow)
         } else {
             format!(
                 "
@@ -157,10 +480,50 @@ impl Light for DirectionalLight {
                 ", i, i, i, i, i)
         }
     }
+    fn incoming_light_shader_source(&self, i: u32) -> Option<String> {
+        let visibility = if self.shadow_texture.is_some() || self.cascaded_shadow_map.is_some() {
+            format!("calculate_shadow{i}(light_direction, normal, position)")
+        } else {
+            "1.0".to_string()
+        };
+        Some(format!(
+            "
+                vec3 calculate_incoming_light{i}(vec3 position, vec3 normal, out vec3 light_direction, out float visibility)
+                {{
+                    light_direction = -direction{i};
+                    visibility = {visibility};
+                    return color{i};
+                }}
+
+            "
+        ))
+    }
+
     fn use_uniforms(&self, program: &Program, i: u32) {
         if let Some(ref tex) = self.shadow_texture {
             program.use_depth_texture(&format!("shadowMap{}", i), tex);
             program.use_uniform(&format!("shadowMVP{}", i), self.shadow_matrix);
+            self.shadow_settings.use_uniforms(program, i);
+        }
+        if let Some(ref cascaded) = self.cascaded_shadow_map {
+            let mut splits = [f32::MAX; MAX_SHADOW_CASCADES as usize];
+            splits[..cascaded.split_distances.len()].copy_from_slice(&cascaded.split_distances);
+            let mut bias_scales = [1.0; MAX_SHADOW_CASCADES as usize];
+            bias_scales[..cascaded.bias_scales.len()].copy_from_slice(&cascaded.bias_scales);
+            program.use_depth_texture_array(&format!("shadowMap{}", i), &cascaded.texture);
+            program.use_uniform_array(&format!("shadowMVP{}", i), &cascaded.matrices);
+            program.use_uniform(&format!("cascadeSplits{}", i), Vec4::from(splits));
+            program.use_uniform(&format!("cascadeBiasScales{}", i), Vec4::from(bias_scales));
+            program.use_uniform(
+                &format!("cascadeCount{}", i),
+                cascaded.split_distances.len() as i32,
+            );
+            program.use_uniform(&format!("cascadeViewPosition{}", i), cascaded.view_position);
+            program.use_uniform(
+                &format!("cascadeViewDirection{}", i),
+                cascaded.view_direction,
+            );
+            self.shadow_settings.use_uniforms(program, i);
         }
         program.use_uniform(
             &format!("color{}", i),
@@ -170,6 +533,95 @@ impl Light for DirectionalLight {
     }
 
     fn id(&self) -> LightId {
-        LightId::DirectionalLight(self.shadow_texture.is_some())
+        let shadow = self.shadow_texture.is_some() || self.cascaded_shadow_map.is_some();
+        LightId::DirectionalLight(
+            self.shadow_texture.is_some(),
+            self.cascaded_shadow_map.is_some(),
+            if shadow {
+                self.shadow_settings.filter.id()
+            } else {
+                0
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1.0e-3 * b.abs(), "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn uniform_split_distances() {
+        assert_approx_eq(
+            &CascadeSplitScheme::Uniform.split_distances(1.0, 101.0, 4),
+            &[26.0, 51.0, 76.0, 101.0],
+        );
+    }
+
+    #[test]
+    fn logarithmic_split_distances() {
+        assert_approx_eq(
+            &CascadeSplitScheme::Logarithmic.split_distances(1.0, 10000.0, 4),
+            &[10.0, 100.0, 1000.0, 10000.0],
+        );
+    }
+
+    #[test]
+    fn practical_split_distances() {
+        let uniform = CascadeSplitScheme::Uniform.split_distances(0.1, 100.0, 3);
+        let logarithmic = CascadeSplitScheme::Logarithmic.split_distances(0.1, 100.0, 3);
+        assert_approx_eq(
+            &CascadeSplitScheme::Practical(0.0).split_distances(0.1, 100.0, 3),
+            &uniform,
+        );
+        assert_approx_eq(
+            &CascadeSplitScheme::Practical(1.0).split_distances(0.1, 100.0, 3),
+            &logarithmic,
+        );
+        assert_approx_eq(
+            &CascadeSplitScheme::Practical(2.0).split_distances(0.1, 100.0, 3),
+            &logarithmic,
+        );
+        let practical = CascadeSplitScheme::Practical(0.5).split_distances(0.1, 100.0, 3);
+        for i in 0..3 {
+            assert!(logarithmic[i] <= practical[i] && practical[i] <= uniform[i]);
+        }
+        assert!((practical[2] - 100.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn split_distances_with_zero_near_plane() {
+        let splits = CascadeSplitScheme::Logarithmic.split_distances(0.0, 100.0, 2);
+        assert!(splits.iter().all(|split| split.is_finite() && *split > 0.0));
+        assert!((splits[1] - 100.0).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn frustum_corners_at_distances() {
+        let camera = Camera::new_perspective(
+            Viewport::new_at_origo(200, 100),
+            vec3(1.0, 2.0, 3.0),
+            vec3(1.0, 2.0, -7.0),
+            vec3(0.0, 1.0, 0.0),
+            degrees(60.0),
+            0.1,
+            100.0,
+        );
+        let corners = frustum_corners(&camera, 2.0, 20.0);
+        for (i, corner) in corners.iter().enumerate() {
+            let distance = (corner - camera.position()).dot(camera.view_direction());
+            let expected = if i % 2 == 0 { 2.0 } else { 20.0 };
+            assert!((distance - expected).abs() < 1.0e-3);
+        }
+        // The far corners span the field of view vertically
+        let height = (corners[5] - corners[1]).magnitude();
+        assert!((height - 2.0 * 20.0 * degrees(30.0).tan()).abs() < 1.0e-2);
     }
 }
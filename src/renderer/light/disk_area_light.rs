@@ -0,0 +1,117 @@
+use crate::core::*;
+use crate::renderer::light::*;
+use crate::renderer::*;
+use std::sync::Arc;
+
+///
+/// A disk shaped area light which shines from one side of the disk, ie. in the given direction.
+/// The lighting is evaluated using linearly transformed cosines (LTC) which gives physically based highlights
+/// when using the Cook-Torrance [LightingModel] with the GGX normal distribution function.
+///
+pub struct DiskAreaLight {
+    lookup_tables: Arc<LtcLookupTables>,
+    /// The intensity of the light. This allows for higher intensity than 1 which can be used to simulate high intensity light sources like the sun.
+    pub intensity: f32,
+    /// The base color of the light.
+    pub color: Srgba,
+    /// The position of the center of the disk.
+    pub position: Vec3,
+    /// The direction the light shines, ie. the normal of the disk.
+    pub direction: Vec3,
+    /// The radius of the disk.
+    pub radius: f32,
+}
+
+impl DiskAreaLight {
+    /// Constructs a new disk shaped area light.
+    pub fn new(
+        context: &Context,
+        intensity: f32,
+        color: Srgba,
+        position: &Vec3,
+        direction: &Vec3,
+        radius: f32,
+    ) -> DiskAreaLight {
+        DiskAreaLight {
+            lookup_tables: LtcLookupTables::shared(context),
+            intensity,
+            color,
+            position: *position,
+            direction: *direction,
+            radius,
+        }
+    }
+}
+
+impl Light for DiskAreaLight {
+    fn shader_source(&self, i: u32) -> String {
+        format!(
+            "
+                {}
+                uniform vec3 color{i};
+                uniform vec3 position{i};
+                uniform vec3 axisX{i};
+                uniform vec3 axisY{i};
+
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 p = position{i} - position;
+                    if (dot(p, cross(axisX{i}, axisY{i})) <= 0.0) {{
+                        return vec3(0.0);
+                    }}
+
+                    vec2 uv = ltc_uv(dot(normal, view_direction), roughness);
+                    vec4 t = texture(ltcMatrix{i}, uv);
+                    mat3 Minv = mat3(vec3(t.x, 0.0, t.y), vec3(0.0, 1.0, 0.0), vec3(t.z, 0.0, t.w));
+
+                    float diffuse = ltc_evaluate_disk(ltc_transform(mat3(1.0), normal, view_direction), p, axisX{i}, axisY{i});
+                    float specular = ltc_evaluate_disk(ltc_transform(Minv, normal, view_direction), p, axisX{i}, axisY{i});
+                    return calculate_area_light(color{i}, diffuse, specular, texture(ltcAmplitude{i}, uv).xy, surface_color, metallic);
+                }}
+
+            ",
+            LtcLookupTables::fragment_shader_source(i)
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        self.lookup_tables.use_uniforms(program, i);
+        let direction = self.direction.normalize();
+        let axis_y = compute_up_direction(direction);
+        let axis_x = direction.cross(axis_y);
+        // The disk is approximated by a regular polygon with 16 edges in the shader, so the radius is scaled such that the area is preserved
+        let edge_count = 16.0f32;
+        let radius = self.radius
+            * (std::f32::consts::PI
+                / (0.5 * edge_count * (2.0 * std::f32::consts::PI / edge_count).sin()))
+            .sqrt();
+        program.use_uniform(
+            &format!("color{}", i),
+            self.color.to_linear_srgb().truncate() * self.intensity,
+        );
+        program.use_uniform(&format!("position{}", i), self.position);
+        program.use_uniform(&format!("axisX{}", i), axis_x * radius);
+        program.use_uniform(&format!("axisY{}", i), axis_y * radius);
+    }
+
+    fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        if aabb.is_empty() || aabb.is_infinite() {
+            return true;
+        }
+        // The light only shines in front of the light plane, so the box is not affected if all corners are behind it
+        let (min, max) = (aabb.min(), aabb.max());
+        let direction = self.direction.normalize();
+        (0..8).any(|c| {
+            let corner = vec3(
+                if c & 1 == 0 { min.x } else { max.x },
+                if c & 2 == 0 { min.y } else { max.y },
+                if c & 4 == 0 { min.z } else { max.z },
+            );
+            (corner - self.position).dot(direction) > 0.0
+        })
+    }
+
+    fn id(&self) -> LightId {
+        LightId::DiskAreaLight
+    }
+}
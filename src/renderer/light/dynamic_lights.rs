@@ -0,0 +1,293 @@
+use crate::core::*;
+use crate::renderer::*;
+
+/// The maximum number of directional lights in [DynamicLights].
+pub const MAX_DYNAMIC_DIRECTIONAL_LIGHTS: usize = 4;
+/// The maximum number of point lights in [DynamicLights].
+pub const MAX_DYNAMIC_POINT_LIGHTS: usize = 16;
+/// The maximum number of spot lights in [DynamicLights].
+pub const MAX_DYNAMIC_SPOT_LIGHTS: usize = 8;
+
+///
+/// A set of directional, point and spot lights where the lights of each kind are sent to the shader as uniform arrays together with the number of lights.
+/// The shader therefore only depends on the maximum number of lights of each kind ([MAX_DYNAMIC_DIRECTIONAL_LIGHTS], [MAX_DYNAMIC_POINT_LIGHTS] and [MAX_DYNAMIC_SPOT_LIGHTS])
+/// and not on the exact set of lights, so adding or removing a light at runtime does not require a new shader to be compiled.
+///
+/// The lights in the set are specified using [DynamicLights::update].
+/// The dynamic lights can be used in the same way as any other light, for example with [PhysicalMaterial] and [DeferredPhysicalMaterial].
+///
+/// **Note:** The lights in the set do not cast shadows.
+///
+#[derive(Clone, Debug, Default)]
+pub struct DynamicLights {
+    directional_colors: Vec<Vec3>,
+    directional_directions: Vec<Vec3>,
+    point_colors: Vec<Vec3>,
+    point_attenuations: Vec<Vec3>,
+    point_positions: Vec<Vec3>,
+    spot_colors: Vec<Vec3>,
+    spot_attenuations: Vec<Vec3>,
+    spot_positions: Vec<Vec3>,
+    spot_directions: Vec<Vec3>,
+    spot_cutoffs: Vec<f32>,
+}
+
+impl DynamicLights {
+    ///
+    /// Constructs a new set of dynamic lights containing the given lights.
+    ///
+    /// # Panic
+    /// Will panic if the number of lights of a kind is larger than the maximum number of lights of that kind.
+    ///
+    pub fn new<'a>(
+        directional_lights: impl IntoIterator<Item = &'a DirectionalLight>,
+        point_lights: impl IntoIterator<Item = &'a PointLight>,
+        spot_lights: impl IntoIterator<Item = &'a SpotLight>,
+    ) -> Self {
+        let mut dynamic_lights = Self::default();
+        dynamic_lights.update(directional_lights, point_lights, spot_lights);
+        dynamic_lights
+    }
+
+    ///
+    /// Updates the set of lights to the given lights.
+    /// Should be called whenever the lights have changed.
+    ///
+    /// # Panic
+    /// Will panic if the number of lights of a kind is larger than the maximum number of lights of that kind.
+    ///
+    pub fn update<'a>(
+        &mut self,
+        directional_lights: impl IntoIterator<Item = &'a DirectionalLight>,
+        point_lights: impl IntoIterator<Item = &'a PointLight>,
+        spot_lights: impl IntoIterator<Item = &'a SpotLight>,
+    ) {
+        *self = Self::default();
+        for light in directional_lights {
+            self.directional_colors
+                .push(light.color.to_linear_srgb().truncate() * light.intensity);
+            self.directional_directions
+                .push(light.direction.normalize());
+        }
+        for light in point_lights {
+            self.point_colors
+                .push(light.color.to_linear_srgb().truncate() * light.intensity);
+            self.point_attenuations.push(vec3(
+                light.attenuation.constant,
+                light.attenuation.linear,
+                light.attenuation.quadratic,
+            ));
+            self.point_positions.push(light.position);
+        }
+        for light in spot_lights {
+            self.spot_colors
+                .push(light.color.to_linear_srgb().truncate() * light.intensity);
+            self.spot_attenuations.push(vec3(
+                light.attenuation.constant,
+                light.attenuation.linear,
+                light.attenuation.quadratic,
+            ));
+            self.spot_positions.push(light.position);
+            self.spot_directions.push(light.direction.normalize());
+            self.spot_cutoffs.push(light.cutoff.0);
+        }
+        self.check_capacity();
+    }
+
+    ///
+    /// Panics if the number of lights of a kind is larger than the maximum number of lights of that kind.
+    ///
+    fn check_capacity(&self) {
+        if self.directional_colors.len() > MAX_DYNAMIC_DIRECTIONAL_LIGHTS
+            || self.point_colors.len() > MAX_DYNAMIC_POINT_LIGHTS
+            || self.spot_colors.len() > MAX_DYNAMIC_SPOT_LIGHTS
+        {
+            panic!(
+                "the number of dynamic lights (directional: {}, point: {}, spot: {}) exceeds the maximum (directional: {}, point: {}, spot: {})",
+                self.directional_colors.len(),
+                self.point_colors.len(),
+                self.spot_colors.len(),
+                MAX_DYNAMIC_DIRECTIONAL_LIGHTS,
+                MAX_DYNAMIC_POINT_LIGHTS,
+                MAX_DYNAMIC_SPOT_LIGHTS
+            );
+        }
+    }
+}
+
+impl Light for DynamicLights {
+    fn shader_source(&self, i: u32) -> String {
+        format!(
+            "
+                uniform int directionalLightCount{i};
+                uniform vec3 directionalLightColors{i}[{max_directional}];
+                uniform vec3 directionalLightDirections{i}[{max_directional}];
+
+                uniform int pointLightCount{i};
+                uniform vec3 pointLightColors{i}[{max_point}];
+                uniform vec3 pointLightAttenuations{i}[{max_point}];
+                uniform vec3 pointLightPositions{i}[{max_point}];
+
+                uniform int spotLightCount{i};
+                uniform vec3 spotLightColors{i}[{max_spot}];
+                uniform vec3 spotLightAttenuations{i}[{max_spot}];
+                uniform vec3 spotLightPositions{i}[{max_spot}];
+                uniform vec3 spotLightDirections{i}[{max_spot}];
+                uniform float spotLightCutoffs{i}[{max_spot}];
+
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 color = vec3(0.0);
+                    for (int j = 0; j < directionalLightCount{i}; j++)
+                    {{
+                        color += calculate_light(directionalLightColors{i}[j], -directionalLightDirections{i}[j], surface_color, view_direction, normal, metallic, roughness);
+                    }}
+                    for (int j = 0; j < pointLightCount{i}; j++)
+                    {{
+                        vec3 light_direction = pointLightPositions{i}[j] - position;
+                        float distance = length(light_direction);
+                        light_direction = light_direction / distance;
+
+                        vec3 light_color = attenuate(pointLightColors{i}[j], pointLightAttenuations{i}[j], distance);
+                        color += calculate_light(light_color, light_direction, surface_color, view_direction, normal, metallic, roughness);
+                    }}
+                    for (int j = 0; j < spotLightCount{i}; j++)
+                    {{
+                        vec3 light_direction = spotLightPositions{i}[j] - position;
+                        float distance = length(light_direction);
+                        light_direction = light_direction / distance;
+
+                        float angle = acos(dot(-light_direction, spotLightDirections{i}[j]));
+                        float cutoff = spotLightCutoffs{i}[j];
+                        if (angle < cutoff) {{
+                            vec3 light_color = attenuate(spotLightColors{i}[j], spotLightAttenuations{i}[j], distance);
+                            color += calculate_light(light_color, light_direction, surface_color, view_direction, normal,
+                                metallic, roughness) * (1.0 - smoothstep(0.75 * cutoff, cutoff, angle));
+                        }}
+                    }}
+                    return color;
+                }}
+
+            ",
+            max_directional = MAX_DYNAMIC_DIRECTIONAL_LIGHTS,
+            max_point = MAX_DYNAMIC_POINT_LIGHTS,
+            max_spot = MAX_DYNAMIC_SPOT_LIGHTS,
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        let padded = |values: &[Vec3], max: usize| {
+            let mut values = values.to_vec();
+            values.resize(max, Vec3::zero());
+            values
+        };
+        program.use_uniform(
+            &format!("directionalLightCount{}", i),
+            self.directional_colors.len() as i32,
+        );
+        program.use_uniform_array(
+            &format!("directionalLightColors{}", i),
+            &padded(&self.directional_colors, MAX_DYNAMIC_DIRECTIONAL_LIGHTS),
+        );
+        program.use_uniform_array(
+            &format!("directionalLightDirections{}", i),
+            &padded(&self.directional_directions, MAX_DYNAMIC_DIRECTIONAL_LIGHTS),
+        );
+
+        program.use_uniform(
+            &format!("pointLightCount{}", i),
+            self.point_colors.len() as i32,
+        );
+        program.use_uniform_array(
+            &format!("pointLightColors{}", i),
+            &padded(&self.point_colors, MAX_DYNAMIC_POINT_LIGHTS),
+        );
+        program.use_uniform_array(
+            &format!("pointLightAttenuations{}", i),
+            &padded(&self.point_attenuations, MAX_DYNAMIC_POINT_LIGHTS),
+        );
+        program.use_uniform_array(
+            &format!("pointLightPositions{}", i),
+            &padded(&self.point_positions, MAX_DYNAMIC_POINT_LIGHTS),
+        );
+
+        program.use_uniform(
+            &format!("spotLightCount{}", i),
+            self.spot_colors.len() as i32,
+        );
+        program.use_uniform_array(
+            &format!("spotLightColors{}", i),
+            &padded(&self.spot_colors, MAX_DYNAMIC_SPOT_LIGHTS),
+        );
+        program.use_uniform_array(
+            &format!("spotLightAttenuations{}", i),
+            &padded(&self.spot_attenuations, MAX_DYNAMIC_SPOT_LIGHTS),
+        );
+        program.use_uniform_array(
+            &format!("spotLightPositions{}", i),
+            &padded(&self.spot_positions, MAX_DYNAMIC_SPOT_LIGHTS),
+        );
+        program.use_uniform_array(
+            &format!("spotLightDirections{}", i),
+            &padded(&self.spot_directions, MAX_DYNAMIC_SPOT_LIGHTS),
+        );
+        let mut spot_cutoffs = self.spot_cutoffs.clone();
+        spot_cutoffs.resize(MAX_DYNAMIC_SPOT_LIGHTS, 0.0);
+        program.use_uniform_array(&format!("spotLightCutoffs{}", i), &spot_cutoffs);
+    }
+
+    fn id(&self) -> LightId {
+        LightId::DynamicLights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dynamic_lights(directional: usize, point: usize, spot: usize) -> DynamicLights {
+        DynamicLights {
+            directional_colors: vec![vec3(1.0, 1.0, 1.0); directional],
+            point_colors: vec![vec3(1.0, 1.0, 1.0); point],
+            spot_colors: vec![vec3(1.0, 1.0, 1.0); spot],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn capacity() {
+        dynamic_lights(0, 0, 0).check_capacity();
+        dynamic_lights(
+            MAX_DYNAMIC_DIRECTIONAL_LIGHTS,
+            MAX_DYNAMIC_POINT_LIGHTS,
+            MAX_DYNAMIC_SPOT_LIGHTS,
+        )
+        .check_capacity();
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the maximum")]
+    fn too_many_directional_lights() {
+        dynamic_lights(MAX_DYNAMIC_DIRECTIONAL_LIGHTS + 1, 0, 0).check_capacity();
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the maximum")]
+    fn too_many_point_lights() {
+        dynamic_lights(0, MAX_DYNAMIC_POINT_LIGHTS + 1, 0).check_capacity();
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the maximum")]
+    fn too_many_spot_lights() {
+        dynamic_lights(0, 0, MAX_DYNAMIC_SPOT_LIGHTS + 1).check_capacity();
+    }
+
+    #[test]
+    fn shader_source_is_independent_of_the_lights() {
+        assert_eq!(
+            dynamic_lights(0, 0, 0).shader_source(0),
+            dynamic_lights(2, 5, 1).shader_source(0)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::cpu_environment::rgba_data;
 use crate::core::*;
 use crate::renderer::*;
 
@@ -15,20 +16,55 @@ pub struct Environment {
     pub brdf_map: Texture2D,
 }
 
+/// The number of mip levels in the [Environment::prefilter_map], each containing the prefiltered color for a roughness from 0 to 1 in steps of 0.25.
+pub(super) const PREFILTER_MIP_LEVELS: u32 = 5;
+
+///
+/// Settings for the precomputation of an [Environment], ie. the resolutions of the precomputed maps and the number of samples used to compute each texel.
+/// Lower resolutions and fewer samples decrease the time it takes to compute the maps at the cost of quality.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnvironmentSettings {
+    /// The lighting model used when computing the prefilter map and the BRDF lookup table.
+    pub lighting_model: LightingModel,
+    /// The width and height of each side of the irradiance map.
+    pub irradiance_size: u32,
+    /// The approximate number of samples of the environment map used to compute each texel of the irradiance map.
+    pub irradiance_sample_count: u32,
+    /// The width and height of each side of the largest mip level of the prefilter map. Must be at least 16 to contain all mip levels.
+    pub prefilter_size: u32,
+    /// The number of samples of the environment map used to compute each texel of the prefilter map.
+    pub prefilter_sample_count: u32,
+    /// The width and height of the BRDF lookup table.
+    pub brdf_size: u32,
+    /// The number of samples used to compute each texel of the BRDF lookup table.
+    pub brdf_sample_count: u32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            lighting_model: LightingModel::Cook(
+                NormalDistributionFunction::TrowbridgeReitzGGX,
+                GeometryFunction::SmithSchlickGGX,
+            ),
+            irradiance_size: 32,
+            irradiance_sample_count: 40000,
+            prefilter_size: 128,
+            prefilter_sample_count: 1024,
+            brdf_size: 512,
+            brdf_sample_count: 1024,
+        }
+    }
+}
+
 impl Environment {
     ///
     /// Computes the maps needed for physically based rendering with lighting from an environment from the given environment map.
     /// A default Cook-Torrance lighting model is used.
     ///
     pub fn new(context: &Context, environment_map: &TextureCubeMap) -> Self {
-        Self::new_with_lighting_model(
-            context,
-            environment_map,
-            LightingModel::Cook(
-                NormalDistributionFunction::TrowbridgeReitzGGX,
-                GeometryFunction::SmithSchlickGGX,
-            ),
-        )
+        Self::new_with_settings(context, environment_map, EnvironmentSettings::default())
     }
 
     ///
@@ -39,19 +75,29 @@ impl Environment {
         environment_map: &TextureCubeMap,
         lighting_model: LightingModel,
     ) -> Self {
-        // Diffuse
-        let irradiance_size = 32;
-        let mut irradiance_map = TextureCubeMap::new_empty::<[f16; 4]>(
+        Self::new_with_settings(
             context,
-            irradiance_size,
-            irradiance_size,
-            Interpolation::Linear,
-            Interpolation::Linear,
-            Some(Interpolation::Linear),
-            Wrapping::ClampToEdge,
-            Wrapping::ClampToEdge,
-            Wrapping::ClampToEdge,
-        );
+            environment_map,
+            EnvironmentSettings {
+                lighting_model,
+                ..Default::default()
+            },
+        )
+    }
+
+    ///
+    /// Computes the maps needed for physically based rendering with lighting from an environment from the given environment map and with the given settings.
+    ///
+    pub fn new_with_settings(
+        context: &Context,
+        environment_map: &TextureCubeMap,
+        settings: EnvironmentSettings,
+    ) -> Self {
+        let lighting_model = settings.lighting_model;
+
+        // Diffuse
+        let irradiance_size = settings.irradiance_size;
+        let mut irradiance_map = new_cube_map(context, irradiance_size);
         {
             let viewport = Viewport::new_at_origo(irradiance_size, irradiance_size);
             for side in CubeMapSide::iter() {
@@ -62,6 +108,7 @@ impl Environment {
                         &IrradianceMaterial {
                             environment_map,
                             side,
+                            sample_count: settings.irradiance_sample_count,
                         },
                         &Camera::new_2d(viewport),
                         &[],
@@ -70,21 +117,10 @@ impl Environment {
         }
 
         // Prefilter
-        let prefilter_size = 128;
-        let mut prefilter_map = TextureCubeMap::new_empty::<[f16; 4]>(
-            context,
-            prefilter_size,
-            prefilter_size,
-            Interpolation::Linear,
-            Interpolation::Linear,
-            Some(Interpolation::Linear),
-            Wrapping::ClampToEdge,
-            Wrapping::ClampToEdge,
-            Wrapping::ClampToEdge,
-        );
+        let prefilter_size = settings.prefilter_size.max(1 << (PREFILTER_MIP_LEVELS - 1));
+        let mut prefilter_map = new_cube_map(context, prefilter_size);
         {
-            let max_mip_levels = 5;
-            for mip in 0..max_mip_levels {
+            for mip in 0..PREFILTER_MIP_LEVELS {
                 for side in CubeMapSide::iter() {
                     let sides = [side];
                     let color_target = prefilter_map.as_color_target(&sides, Some(mip));
@@ -98,7 +134,8 @@ impl Environment {
                                 environment_map,
                                 side,
                                 mip,
-                                max_mip_levels,
+                                max_mip_levels: PREFILTER_MIP_LEVELS,
+                                sample_count: settings.prefilter_sample_count,
                             },
                             &Camera::new_2d(viewport),
                             &[],
@@ -110,8 +147,8 @@ impl Environment {
         // BRDF
         let mut brdf_map = Texture2D::new_empty::<[f32; 2]>(
             context,
-            512,
-            512,
+            settings.brdf_size,
+            settings.brdf_size,
             Interpolation::Linear,
             Interpolation::Linear,
             None,
@@ -123,7 +160,10 @@ impl Environment {
             .as_color_target(None)
             .clear(ClearState::default())
             .apply_screen_material(
-                &BrdfMaterial { lighting_model },
+                &BrdfMaterial {
+                    lighting_model,
+                    sample_count: settings.brdf_sample_count,
+                },
                 &Camera::new_2d(viewport),
                 &[],
             );
@@ -134,6 +174,143 @@ impl Environment {
             brdf_map,
         }
     }
+
+    ///
+    /// Constructs an environment from maps which have previously been computed, for example using [Environment::to_cpu_environment].
+    /// Returns an error if the maps do not have the expected number of sides and mip levels or if the sizes do not match.
+    ///
+    pub fn from_cpu_environment(
+        context: &Context,
+        cpu_environment: &CpuEnvironment,
+    ) -> Result<Self, RendererError> {
+        cpu_environment.validate()?;
+        let mut irradiance_map = new_cube_map(context, cpu_environment.irradiance_map[0].width);
+        fill_cube_map_mip_level(&mut irradiance_map, 0, &cpu_environment.irradiance_map);
+        let mut prefilter_map = new_cube_map(context, cpu_environment.prefilter_map[0][0].width);
+        for (mip, sides) in cpu_environment.prefilter_map.iter().enumerate() {
+            fill_cube_map_mip_level(&mut prefilter_map, mip as u32, sides);
+        }
+        let brdf_map = Texture2D::new(
+            context,
+            &CpuTexture {
+                data: TextureData::RgF32(
+                    rgba_data(&cpu_environment.brdf_map)
+                        .into_iter()
+                        .map(|c| [c[0], c[1]])
+                        .collect(),
+                ),
+                min_filter: Interpolation::Linear,
+                mag_filter: Interpolation::Linear,
+                mip_map_filter: None,
+                wrap_s: Wrapping::ClampToEdge,
+                wrap_t: Wrapping::ClampToEdge,
+                ..cpu_environment.brdf_map.clone()
+            },
+        );
+        Ok(Self {
+            irradiance_map,
+            prefilter_map,
+            brdf_map,
+        })
+    }
+
+    ///
+    /// Loads an environment from a file in the format described in [CpuEnvironment], for example saved using [CpuEnvironment::save].
+    ///
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(
+        context: &Context,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, RendererError> {
+        Self::from_cpu_environment(context, &CpuEnvironment::load(path)?)
+    }
+
+    ///
+    /// Reads the irradiance map, all mip levels of the prefilter map and the BRDF lookup table back from the GPU,
+    /// so that they can be saved and used to construct the environment using [Environment::from_cpu_environment] without computing the maps again.
+    ///
+    pub fn to_cpu_environment(&mut self) -> CpuEnvironment {
+        let irradiance_map = read_cube_map_mip_level(&mut self.irradiance_map, 0);
+        let prefilter_map = (0..PREFILTER_MIP_LEVELS)
+            .map(|mip| read_cube_map_mip_level(&mut self.prefilter_map, mip))
+            .collect();
+        let (width, height) = (self.brdf_map.width(), self.brdf_map.height());
+        let brdf_data = self
+            .brdf_map
+            .as_color_target(None)
+            .read::<[f32; 4]>()
+            .into_iter()
+            .map(|c| [c[0], c[1]])
+            .collect();
+        CpuEnvironment {
+            irradiance_map,
+            prefilter_map,
+            brdf_map: CpuTexture {
+                data: TextureData::RgF32(brdf_data),
+                width,
+                height,
+                mip_map_filter: None,
+                wrap_s: Wrapping::ClampToEdge,
+                wrap_t: Wrapping::ClampToEdge,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn new_cube_map(context: &Context, size: u32) -> TextureCubeMap {
+    TextureCubeMap::new_empty::<[f16; 4]>(
+        context,
+        size,
+        size,
+        Interpolation::Linear,
+        Interpolation::Linear,
+        Some(Interpolation::Linear),
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    )
+}
+
+fn fill_cube_map_mip_level(cube_map: &mut TextureCubeMap, mip_level: u32, sides: &[CpuTexture]) {
+    let data = sides
+        .iter()
+        .map(|side| {
+            rgba_data(side)
+                .into_iter()
+                .map(|c| c.map(f16::from_f32))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    cube_map.fill_mip_level(
+        mip_level, &data[0], &data[1], &data[2], &data[3], &data[4], &data[5],
+    );
+}
+
+fn read_cube_map_mip_level(cube_map: &mut TextureCubeMap, mip_level: u32) -> Vec<CpuTexture> {
+    CubeMapSide::iter()
+        .map(|side| {
+            let sides = [side];
+            let color_target = cube_map.as_color_target(&sides, Some(mip_level));
+            let (width, height) = (color_target.width(), color_target.height());
+            let data = color_target.read::<[f32; 4]>();
+            // The rows are flipped when reading, but not when filling a cube map
+            let data = data
+                .chunks(width as usize)
+                .rev()
+                .flatten()
+                .map(|c| c.map(f16::from_f32))
+                .collect();
+            CpuTexture {
+                data: TextureData::RgbaF16(data),
+                width,
+                height,
+                wrap_s: Wrapping::ClampToEdge,
+                wrap_t: Wrapping::ClampToEdge,
+                ..Default::default()
+            }
+        })
+        .collect()
 }
 
 struct PrefilterMaterial<'a> {
@@ -142,6 +319,7 @@ struct PrefilterMaterial<'a> {
     side: CubeMapSide,
     mip: u32,
     max_mip_levels: u32,
+    sample_count: u32,
 }
 
 impl Material for PrefilterMaterial<'_> {
@@ -173,6 +351,7 @@ impl Material for PrefilterMaterial<'_> {
             self.mip as f32 / (self.max_mip_levels as f32 - 1.0),
         );
         program.use_uniform("resolution", self.environment_map.width() as f32);
+        program.use_uniform("sampleCount", self.sample_count);
         program.use_uniform("direction", self.side.direction());
         program.use_uniform("up", self.side.up());
     }
@@ -188,6 +367,7 @@ impl Material for PrefilterMaterial<'_> {
 
 struct BrdfMaterial {
     lighting_model: LightingModel,
+    sample_count: u32,
 }
 
 impl Material for BrdfMaterial {
@@ -212,7 +392,9 @@ impl Material for BrdfMaterial {
         }
     }
 
-    fn use_uniforms(&self, _program: &Program, _camera: &Camera, _lights: &[&dyn Light]) {}
+    fn use_uniforms(&self, program: &Program, _camera: &Camera, _lights: &[&dyn Light]) {
+        program.use_uniform("sampleCount", self.sample_count);
+    }
 
     fn render_states(&self) -> RenderStates {
         RenderStates::default()
@@ -226,6 +408,7 @@ impl Material for BrdfMaterial {
 struct IrradianceMaterial<'a> {
     environment_map: &'a TextureCubeMap,
     side: CubeMapSide,
+    sample_count: u32,
 }
 
 impl Material for IrradianceMaterial<'_> {
@@ -252,6 +435,11 @@ impl Material for IrradianceMaterial<'_> {
         program.use_texture_cube("environmentMap", self.environment_map);
         program.use_uniform("direction", self.side.direction());
         program.use_uniform("up", self.side.up());
+        // The samples are distributed evenly in the two angles covering a hemisphere, ie. 2pi times pi/2
+        program.use_uniform(
+            "sampleDelta",
+            std::f32::consts::PI / (self.sample_count.max(1) as f32).sqrt(),
+        );
     }
 
     fn render_states(&self) -> RenderStates {
@@ -0,0 +1,329 @@
+use crate::core::*;
+use crate::renderer::*;
+
+/// The number of vertical angles in the lookup texture created by [IesProfile::lookup_texture].
+const IES_LOOKUP_VERTICAL_SIZE: usize = 256;
+/// The number of horizontal angles in the lookup texture created by [IesProfile::lookup_texture].
+const IES_LOOKUP_HORIZONTAL_SIZE: usize = 64;
+
+///
+/// A photometric profile describing the luminous intensity of a light in all directions,
+/// parsed from an IES (LM-63) file as provided by most manufacturers of luminaires.
+/// Use [IesProfile::lookup_texture] to create a texture which can be used as the [SpotLight::ies_profile].
+///
+/// The profile is assumed to use type C photometry, which is by far the most common,
+/// where the vertical angle is measured from the direction of the light (nadir)
+/// and the horizontal angle is measured around the direction of the light.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct IesProfile {
+    /// The vertical angles in degrees in strictly increasing order, in the range `[0, 180]`.
+    pub vertical_angles: Vec<f32>,
+    /// The horizontal angles in degrees in strictly increasing order, in the range `[0, 360]`.
+    /// The profile is symmetric around the direction of the light if there is only one horizontal angle,
+    /// symmetric in each quadrant if the last angle is 90 and symmetric about the 0-180 degree plane if the last angle is 180.
+    pub horizontal_angles: Vec<f32>,
+    /// The luminous intensity in candela for each combination of horizontal and vertical angle,
+    /// ordered such that all vertical angles for the first horizontal angle are first, then all vertical angles for the second horizontal angle and so on.
+    pub candela: Vec<f32>,
+}
+
+impl IesProfile {
+    ///
+    /// Parses the content of an IES (LM-63-1995 or LM-63-2002) file.
+    ///
+    pub fn parse(text: &str) -> Result<Self, RendererError> {
+        let invalid = |message: &str| RendererError::InvalidIesProfile(message.to_string());
+
+        // Skip the keywords until the tilt line
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .map(|line| line.trim())
+            .find(|line| line.starts_with("TILT="))
+            .ok_or_else(|| invalid("missing TILT line"))?;
+        let mut values = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse::<f32>()
+                    .map_err(|_| invalid(&format!("invalid number {}", value)))
+            });
+        let mut next = || {
+            values
+                .next()
+                .unwrap_or_else(|| Err(invalid("unexpected end of file")))
+        };
+        let count = |value: f32| {
+            if value.is_finite() && value >= 0.0 && value.fract() == 0.0 {
+                Ok(value as usize)
+            } else {
+                Err(invalid(&format!("invalid count {}", value)))
+            }
+        };
+
+        if tilt == "TILT=INCLUDE" {
+            // The tilt data describes how the output changes when the lamp is tilted which is not supported, so it is skipped
+            next()?;
+            let value_count = count(next()?)?
+                .checked_mul(2)
+                .ok_or_else(|| invalid("too many tilt angles"))?;
+            for _ in 0..value_count {
+                next()?;
+            }
+        }
+
+        let _lamp_count = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = count(next()?)?;
+        let horizontal_count = count(next()?)?;
+        let photometric_type = next()?;
+        for _ in 0..4 {
+            // Units type, width, length and height
+            next()?;
+        }
+        let ballast_factor = next()?;
+        let ballast_lamp_factor = next()?;
+        let _input_watts = next()?;
+        if photometric_type != 1.0 {
+            return Err(invalid("only type C photometry is supported"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid("the number of angles must be positive"));
+        }
+        let candela_count = vertical_count
+            .checked_mul(horizontal_count)
+            .ok_or_else(|| invalid("too many angles"))?;
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let scale = multiplier * ballast_factor * ballast_lamp_factor;
+        let candela = (0..candela_count)
+            .map(|_| next().map(|c| c * scale))
+            .collect::<Result<Vec<_>, _>>()?;
+        if vertical_angles.windows(2).any(|w| w[0] >= w[1])
+            || horizontal_angles.windows(2).any(|w| w[0] >= w[1])
+        {
+            return Err(invalid("the angles must be in strictly increasing order"));
+        }
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+        })
+    }
+
+    ///
+    /// Returns the luminous intensity in candela in the direction given by the vertical and horizontal angle in degrees.
+    /// The intensity is linearly interpolated between the angles in the profile and is zero outside the range of vertical angles.
+    ///
+    pub fn intensity(&self, vertical_angle: f32, horizontal_angle: f32) -> f32 {
+        let vertical_count = self.vertical_angles.len();
+        let last = *self.horizontal_angles.last().unwrap();
+        let mut horizontal_angle = horizontal_angle.rem_euclid(360.0);
+        if last <= 90.0 {
+            horizontal_angle %= 180.0;
+            if horizontal_angle > 90.0 {
+                horizontal_angle = 180.0 - horizontal_angle;
+            }
+        } else if last <= 180.0 && horizontal_angle > 180.0 {
+            horizontal_angle = 360.0 - horizontal_angle;
+        }
+        if vertical_angle < self.vertical_angles[0]
+            || vertical_angle > self.vertical_angles[vertical_count - 1]
+        {
+            return 0.0;
+        }
+        let (h0, h1, th) = interpolation(&self.horizontal_angles, horizontal_angle);
+        let (v0, v1, tv) = interpolation(&self.vertical_angles, vertical_angle);
+        let value = |h: usize, v: usize| self.candela[h * vertical_count + v];
+        let c0 = value(h0, v0) * (1.0 - tv) + value(h0, v1) * tv;
+        let c1 = value(h1, v0) * (1.0 - tv) + value(h1, v1) * tv;
+        c0 * (1.0 - th) + c1 * th
+    }
+
+    ///
+    /// Returns the maximum luminous intensity in candela.
+    ///
+    pub fn max_intensity(&self) -> f32 {
+        self.candela.iter().copied().fold(0.0, f32::max)
+    }
+
+    ///
+    /// Creates a lookup texture which can be used as the [SpotLight::ies_profile].
+    /// The intensities in the texture are divided by the [IesProfile::max_intensity], so the brightest direction has the intensity of the light.
+    ///
+    pub fn lookup_texture(&self, context: &Context) -> Texture2DRef {
+        let max_intensity = self.max_intensity().max(f32::EPSILON);
+        let mut data = vec![0.0f32; IES_LOOKUP_VERTICAL_SIZE * IES_LOOKUP_HORIZONTAL_SIZE];
+        for y in 0..IES_LOOKUP_HORIZONTAL_SIZE {
+            let horizontal_angle = (y as f32 + 0.5) / IES_LOOKUP_HORIZONTAL_SIZE as f32 * 360.0;
+            // The rows are reversed since the rows are flipped when filling the texture
+            let row = IES_LOOKUP_HORIZONTAL_SIZE - 1 - y;
+            for x in 0..IES_LOOKUP_VERTICAL_SIZE {
+                let vertical_angle = (x as f32 + 0.5) / IES_LOOKUP_VERTICAL_SIZE as f32 * 180.0;
+                data[row * IES_LOOKUP_VERTICAL_SIZE + x] =
+                    self.intensity(vertical_angle, horizontal_angle) / max_intensity;
+            }
+        }
+        let mut texture = Texture2D::new_empty::<f32>(
+            context,
+            IES_LOOKUP_VERTICAL_SIZE as u32,
+            IES_LOOKUP_HORIZONTAL_SIZE as u32,
+            Interpolation::Linear,
+            Interpolation::Linear,
+            None,
+            Wrapping::ClampToEdge,
+            Wrapping::Repeat,
+        );
+        texture.fill(&data);
+        Texture2DRef::from_texture(texture)
+    }
+}
+
+///
+/// Returns the indices of the two angles surrounding the given angle and the interpolation factor between them.
+///
+fn interpolation(angles: &[f32], angle: f32) -> (usize, usize, f32) {
+    let i = angles.partition_point(|a| *a <= angle);
+    if i == 0 {
+        (0, 0, 0.0)
+    } else if i == angles.len() {
+        (i - 1, i - 1, 0.0)
+    } else {
+        let t = (angle - angles[i - 1]) / (angles[i] - angles[i - 1]);
+        (i - 1, i, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = "IESNA:LM-63-2002
+[MANUFAC] Test
+TILT=NONE
+1 1000 1 3 2 1 1 0 0 0
+1 1 100
+0 45 90
+0 90
+100 50 0
+200 100 0
+";
+
+    fn is_invalid(result: Result<IesProfile, RendererError>) -> bool {
+        matches!(result, Err(RendererError::InvalidIesProfile(_)))
+    }
+
+    #[test]
+    fn parse_minimal() {
+        let profile = IesProfile::parse(MINIMAL).unwrap();
+        assert_eq!(profile.vertical_angles, vec![0.0, 45.0, 90.0]);
+        assert_eq!(profile.horizontal_angles, vec![0.0, 90.0]);
+        assert_eq!(profile.candela, vec![100.0, 50.0, 0.0, 200.0, 100.0, 0.0]);
+        assert_eq!(profile.max_intensity(), 200.0);
+    }
+
+    #[test]
+    fn parse_multiplier_and_ballast_factors() {
+        let text = MINIMAL
+            .replace("1 1000 1 3 2", "1 1000 2 3 2")
+            .replace("1 1 100", "0.5 3 100");
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.candela, vec![300.0, 150.0, 0.0, 600.0, 300.0, 0.0]);
+    }
+
+    #[test]
+    fn parse_tilt_include() {
+        let text = MINIMAL.replace("TILT=NONE\n", "TILT=INCLUDE\n1\n3\n0 45 90\n1 0.9 0.8\n");
+        assert_eq!(
+            IesProfile::parse(&text).unwrap(),
+            IesProfile::parse(MINIMAL).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_bad_counts() {
+        for counts in [
+            "-3 2",
+            "3 -2",
+            "2.5 2",
+            "3 1e30",
+            "1e30 1e30",
+            "0 2",
+            "3 NaN",
+        ] {
+            let text = MINIMAL.replace("1 1000 1 3 2", &format!("1 1000 1 {}", counts));
+            assert!(is_invalid(IesProfile::parse(&text)), "{}", counts);
+        }
+        let text = MINIMAL.replace("TILT=NONE\n", "TILT=INCLUDE\n1\n1e30\n");
+        assert!(is_invalid(IesProfile::parse(&text)));
+        let text = MINIMAL.replace("TILT=NONE\n", "TILT=INCLUDE\n1\n-1\n");
+        assert!(is_invalid(IesProfile::parse(&text)));
+    }
+
+    #[test]
+    fn parse_non_increasing_angles() {
+        assert!(is_invalid(IesProfile::parse(
+            &MINIMAL.replace("0 45 90", "0 90 45")
+        )));
+        assert!(is_invalid(IesProfile::parse(
+            &MINIMAL.replace("0 45 90", "0 45 45")
+        )));
+        assert!(is_invalid(IesProfile::parse(
+            &MINIMAL.replace("0 90\n", "90 0\n")
+        )));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(is_invalid(IesProfile::parse("IESNA:LM-63-2002\n")));
+        assert!(is_invalid(IesProfile::parse(
+            &MINIMAL.replace("200 100 0\n", "")
+        )));
+        assert!(is_invalid(IesProfile::parse(
+            &MINIMAL.replace("100 50 0", "100 fifty 0")
+        )));
+        // Type A photometry
+        assert!(is_invalid(IesProfile::parse(
+            &MINIMAL.replace("3 2 1 1", "3 2 3 1")
+        )));
+    }
+
+    #[test]
+    fn intensity() {
+        let profile = IesProfile::parse(MINIMAL).unwrap();
+        assert_eq!(profile.intensity(0.0, 0.0), 100.0);
+        assert_eq!(profile.intensity(22.5, 0.0), 75.0);
+        assert_eq!(profile.intensity(45.0, 45.0), 75.0);
+        assert_eq!(profile.intensity(22.5, 45.0), 112.5);
+        // Outside the vertical angles
+        assert_eq!(profile.intensity(120.0, 0.0), 0.0);
+        // The last horizontal angle is 90, so the profile is symmetric in each quadrant
+        assert_eq!(profile.intensity(0.0, 90.0), 200.0);
+        assert_eq!(profile.intensity(0.0, 180.0), 100.0);
+        assert_eq!(profile.intensity(0.0, 270.0), 200.0);
+        assert_eq!(profile.intensity(0.0, 135.0), profile.intensity(0.0, 45.0));
+        assert_eq!(profile.intensity(0.0, -45.0), profile.intensity(0.0, 45.0));
+    }
+
+    #[test]
+    fn intensity_symmetric() {
+        let profile = IesProfile::parse(
+            &MINIMAL
+                .replace("3 2 1 1", "3 1 1 1")
+                .replace("0 90\n", "0\n")
+                .replace("200 100 0\n", ""),
+        )
+        .unwrap();
+        for horizontal_angle in [0.0, 45.0, 180.0, 300.0] {
+            assert_eq!(profile.intensity(45.0, horizontal_angle), 50.0);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// A regular grid of light probes, where each probe stores the light arriving at the position of the probe as [SphericalHarmonics].
+/// When used as a light, the diffuse ambient light at a surface is interpolated from the eight nearest probes,
+/// which for example gives correct ambient light inside a room instead of the irradiance from the sky everywhere.
+/// Surfaces outside the grid use the nearest probes on the boundary of the grid.
+///
+/// The light probe grid only contributes diffuse light, so it is often combined with an [AmbientLight] with an [Environment] for the specular light.
+///
+pub struct LightProbeGrid {
+    texture: Texture3D,
+    probes: Vec<SphericalHarmonics>,
+    aabb: AxisAlignedBoundingBox,
+    counts: [u32; 3],
+    /// The intensity of the light. This allows for higher intensity than 1 which can be used to simulate high intensity light sources like the sun.
+    pub intensity: f32,
+    /// The base color of the light.
+    pub color: Srgba,
+}
+
+impl LightProbeGrid {
+    ///
+    /// Constructs a new light probe grid with the given number of probes along each axis, placed evenly within the given bounding box
+    /// such that the probes at the boundary of the grid are at the boundary of the box.
+    /// The probes are given in order of increasing x, then y and then z coordinate, see [LightProbeGrid::probe_positions].
+    ///
+    /// # Panic
+    /// Will panic if a count is zero or if the number of probes does not match the counts.
+    ///
+    pub fn new(
+        context: &Context,
+        aabb: AxisAlignedBoundingBox,
+        counts: [u32; 3],
+        probes: &[SphericalHarmonics],
+    ) -> Self {
+        if counts.contains(&0) || probes.len() != (counts[0] * counts[1] * counts[2]) as usize {
+            panic!(
+                "the number of probes ({}) must match the number of probes along each axis ({:?})",
+                probes.len(),
+                counts
+            );
+        }
+        // The coefficients are stored in a 3D texture where each coefficient is a block of layers along the z-axis
+        let [nx, ny, nz] = counts.map(|c| c as usize);
+        let mut data = vec![[f16::ZERO; 4]; nx * ny * nz * 9];
+        for (i, probe) in probes.iter().enumerate() {
+            let (x, y, z) = (i % nx, (i / nx) % ny, i / (nx * ny));
+            for (k, c) in probe.coefficients.iter().enumerate() {
+                data[((k * nz + z) * ny + y) * nx + x] = [
+                    f16::from_f32(c.x),
+                    f16::from_f32(c.y),
+                    f16::from_f32(c.z),
+                    f16::ONE,
+                ];
+            }
+        }
+        let mut texture = Texture3D::new_empty::<[f16; 4]>(
+            context,
+            counts[0],
+            counts[1],
+            counts[2] * 9,
+            Interpolation::Linear,
+            Interpolation::Linear,
+            None,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        );
+        texture.fill(&data);
+        Self {
+            texture,
+            probes: probes.to_vec(),
+            aabb,
+            counts,
+            intensity: 1.0,
+            color: Srgba::WHITE,
+        }
+    }
+
+    ///
+    /// Constructs a new light probe grid like [LightProbeGrid::new], where each probe is captured using [SphericalHarmonics::capture]
+    /// by rendering the given objects with the given lights into a cube map with the given texture size.
+    ///
+    pub fn capture(
+        context: &Context,
+        aabb: AxisAlignedBoundingBox,
+        counts: [u32; 3],
+        texture_size: u32,
+        objects: impl IntoIterator<Item = impl Object> + Clone,
+        lights: &[&dyn Light],
+    ) -> Self {
+        let probes = Self::probe_positions(aabb, counts)
+            .into_iter()
+            .map(|position| {
+                SphericalHarmonics::capture(
+                    context,
+                    position,
+                    texture_size,
+                    objects.clone(),
+                    lights,
+                )
+            })
+            .collect::<Vec<_>>();
+        Self::new(context, aabb, counts, &probes)
+    }
+
+    ///
+    /// Returns the positions of the probes in a grid with the given number of probes along each axis placed within the given bounding box,
+    /// in order of increasing x, then y and then z coordinate.
+    ///
+    pub fn probe_positions(aabb: AxisAlignedBoundingBox, counts: [u32; 3]) -> Vec<Vec3> {
+        let step = |i: u32, axis: usize| {
+            if counts[axis] > 1 {
+                i as f32 / (counts[axis] - 1) as f32
+            } else {
+                0.5
+            }
+        };
+        let (min, size) = (aabb.min(), aabb.size());
+        let mut positions = Vec::new();
+        for z in 0..counts[2] {
+            for y in 0..counts[1] {
+                for x in 0..counts[0] {
+                    positions.push(vec3(
+                        min.x + step(x, 0) * size.x,
+                        min.y + step(y, 1) * size.y,
+                        min.z + step(z, 2) * size.z,
+                    ));
+                }
+            }
+        }
+        positions
+    }
+
+    ///
+    /// Returns the probes in order of increasing x, then y and then z coordinate, see [LightProbeGrid::probe_positions].
+    ///
+    pub fn probes(&self) -> &[SphericalHarmonics] {
+        &self.probes
+    }
+
+    ///
+    /// Returns the bounding box containing the probes.
+    ///
+    pub fn aabb(&self) -> AxisAlignedBoundingBox {
+        self.aabb
+    }
+
+    ///
+    /// Returns the number of probes along each axis.
+    ///
+    pub fn counts(&self) -> [u32; 3] {
+        self.counts
+    }
+}
+
+impl Light for LightProbeGrid {
+    fn shader_source(&self, i: u32) -> String {
+        format!(
+            "
+                uniform sampler3D probeGrid{i};
+                uniform vec3 probeGridMin{i};
+                uniform vec3 probeGridSize{i};
+                uniform vec3 probeGridColor{i};
+
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 counts = vec3(textureSize(probeGrid{i}, 0)) / vec3(1.0, 1.0, 9.0);
+                    vec3 grid_position = clamp((position - probeGridMin{i}) / max(probeGridSize{i}, vec3(0.0001)), 0.0, 1.0) * (counts - 1.0);
+                    vec2 uv = (grid_position.xy + 0.5) / counts.xy;
+                    float z0 = floor(grid_position.z);
+                    float z1 = min(z0 + 1.0, counts.z - 1.0);
+                    float t = grid_position.z - z0;
+                    vec3 coefficients[9];
+                    for (int k = 0; k < 9; k++) {{
+                        // Hardware interpolation in the xy-plane and manual interpolation along the z-axis, since the coefficients are stored after each other along the z-axis
+                        vec3 c0 = texture(probeGrid{i}, vec3(uv, (float(k) * counts.z + z0 + 0.5) / (9.0 * counts.z))).rgb;
+                        vec3 c1 = texture(probeGrid{i}, vec3(uv, (float(k) * counts.z + z1 + 0.5) / (9.0 * counts.z))).rgb;
+                        coefficients[k] = mix(c0, c1, t);
+                    }}
+                    vec3 irradiance = max(spherical_harmonics_irradiance(coefficients, normal), vec3(0.0));
+                    return occlusion * probeGridColor{i} * irradiance * diffuse_reflectance(surface_color, metallic);
+                }}
+
+            "
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        program.use_texture_3d(&format!("probeGrid{}", i), &self.texture);
+        program.use_uniform(&format!("probeGridMin{}", i), self.aabb.min());
+        program.use_uniform(&format!("probeGridSize{}", i), self.aabb.size());
+        program.use_uniform(
+            &format!("probeGridColor{}", i),
+            self.color.to_linear_srgb().truncate() * self.intensity,
+        );
+    }
+
+    fn id(&self) -> LightId {
+        LightId::LightProbeGrid
+    }
+}
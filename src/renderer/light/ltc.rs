@@ -0,0 +1,84 @@
+use crate::core::*;
+use std::sync::Arc;
+
+const LTC_LOOKUP_TABLE_SIZE: u32 = 64;
+
+#[cfg(test)]
+mod fit;
+
+///
+/// The lookup tables used for evaluating area lights with linearly transformed cosines (LTC),
+/// see [Real-Time Polygonal-Light Shading with Linearly Transformed Cosines](https://eheitzresearch.wordpress.com/415-2/).
+/// The tables are fitted to the GGX distribution and parameterized by the roughness and `sqrt(1 - cos(theta))` where `theta` is the angle between the normal and the view direction.
+///
+/// The tables are generated by the fit in `ltc/fit.rs` and are part of this crate under the same license.
+/// Each table is stored as little endian `f32` values with 64 rows of 64 texels, where the roughness increases along a row and `sqrt(1 - cos(theta))` increases from row to row.
+/// `ltc_matrix.bin` contains the four elements `(m00, m20, m02, m22)` of the inverse LTC matrix normalized such that `m11` is one, and the other elements are zero,
+/// and `ltc_amplitude.bin` contains the magnitude of the BRDF and the Fresnel term.
+///
+pub(super) struct LtcLookupTables {
+    /// The four non-trivial elements of the inverse LTC matrix.
+    matrix: Texture2D,
+    /// The magnitude of the BRDF and the Fresnel term.
+    amplitude: Texture2D,
+}
+
+impl LtcLookupTables {
+    ///
+    /// Returns the lookup tables for the given context, which are shared by all area lights and only created and uploaded when no other area light is using them.
+    ///
+    pub fn shared(context: &Context) -> Arc<Self> {
+        context.shared_resource(|context| Self {
+            matrix: lookup_table::<[f32; 4]>(context, include_bytes!("ltc/ltc_matrix.bin")),
+            amplitude: lookup_table::<[f32; 2]>(context, include_bytes!("ltc/ltc_amplitude.bin")),
+        })
+    }
+
+    pub fn fragment_shader_source(i: u32) -> String {
+        format!(
+            "
+                uniform sampler2D ltcMatrix{i};
+                uniform sampler2D ltcAmplitude{i};
+            "
+        )
+    }
+
+    pub fn use_uniforms(&self, program: &Program, i: u32) {
+        program.use_texture(&format!("ltcMatrix{}", i), &self.matrix);
+        program.use_texture(&format!("ltcAmplitude{}", i), &self.amplitude);
+    }
+}
+
+fn lookup_table<T: TextureDataType + Default + Copy + AsMut<[f32]>>(
+    context: &Context,
+    bytes: &[u8],
+) -> Texture2D {
+    let size = LTC_LOOKUP_TABLE_SIZE as usize;
+    let channels = bytes.len() / (size * size * 4);
+    let mut data = vec![T::default(); size * size];
+    for (i, value) in data.iter_mut().enumerate() {
+        // The rows are reversed since the rows are flipped when filling the texture
+        let index = (size - 1 - i / size) * size + i % size;
+        for (c, v) in value.as_mut().iter_mut().enumerate() {
+            let offset = 4 * (index * channels + c);
+            *v = f32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]);
+        }
+    }
+    let mut texture = Texture2D::new_empty::<T>(
+        context,
+        LTC_LOOKUP_TABLE_SIZE,
+        LTC_LOOKUP_TABLE_SIZE,
+        Interpolation::Linear,
+        Interpolation::Linear,
+        None,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    );
+    texture.fill(&data);
+    texture
+}
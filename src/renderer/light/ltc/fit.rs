@@ -0,0 +1,465 @@
+//!
+//! Fits the linearly transformed cosines to the GGX distribution which generates the lookup tables `ltc_matrix.bin` and `ltc_amplitude.bin`,
+//! following the fitting procedure described in [Real-Time Polygonal-Light Shading with Linearly Transformed Cosines](https://eheitzresearch.wordpress.com/415-2/)
+//! by Eric Heitz, Jonathan Dupuy, Stephen Hill and David Neubelt.
+//! The tables are generated by running `cargo test --release generate_ltc_lookup_tables -- --ignored`.
+//!
+
+use std::f64::consts::PI;
+
+const SIZE: usize = super::LTC_LOOKUP_TABLE_SIZE as usize;
+
+/// The number of samples in each dimension used when computing the fitting error.
+const ERROR_SAMPLES: usize = 32;
+
+/// The number of samples in each dimension used when computing the amplitude and average direction of the distribution.
+const AMPLITUDE_SAMPLES: usize = 64;
+
+type Vector = [f64; 3];
+/// A row major 3x3 matrix.
+type Matrix = [[f64; 3]; 3];
+
+fn add(a: Vector, b: Vector) -> Vector {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(a: Vector, s: f64) -> Vector {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn dot(a: Vector, b: Vector) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: Vector) -> f64 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: Vector) -> Vector {
+    scale(a, 1.0 / length(a))
+}
+
+fn transform(m: &Matrix, v: Vector) -> Vector {
+    [dot(m[0], v), dot(m[1], v), dot(m[2], v)]
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 3]; 3];
+    for (r, row) in result.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[r][k] * b[k][c]).sum();
+        }
+    }
+    result
+}
+
+fn determinant(m: &Matrix) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+fn inverse(m: &Matrix) -> Matrix {
+    let d = determinant(m);
+    let mut result = [[0.0; 3]; 3];
+    for (r, row) in result.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+            let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+            *value = (m[c0][r0] * m[c1][r1] - m[c0][r1] * m[c1][r0]) / d;
+        }
+    }
+    result
+}
+
+fn smith_lambda(alpha: f64, cos_theta: f64) -> f64 {
+    if cos_theta >= 1.0 {
+        return 0.0;
+    }
+    let tan_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt() / cos_theta;
+    let a = 1.0 / alpha / tan_theta;
+    0.5 * (-1.0 + (1.0 + 1.0 / (a * a)).sqrt())
+}
+
+///
+/// Returns the GGX BRDF times the cosine for the given view and light direction and the probability density of sampling the light direction using [ggx_sample].
+///
+fn ggx_evaluate(view: Vector, light: Vector, alpha: f64) -> (f64, f64) {
+    if view[2] <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let g2 = if light[2] <= 0.0 {
+        0.0
+    } else {
+        1.0 / (1.0 + smith_lambda(alpha, view[2]) + smith_lambda(alpha, light[2]))
+    };
+    let h = normalize(add(view, light));
+    let slope_x = h[0] / h[2];
+    let slope_y = h[1] / h[2];
+    let d = 1.0 / (1.0 + (slope_x * slope_x + slope_y * slope_y) / alpha / alpha);
+    let d = d * d / (PI * alpha * alpha * h[2].powi(4));
+    let pdf = (d * h[2] / 4.0 / dot(view, h)).abs();
+    (d * g2 / 4.0 / view[2], pdf)
+}
+
+fn ggx_sample(view: Vector, alpha: f64, u1: f64, u2: f64) -> Vector {
+    let phi = 2.0 * PI * u1;
+    let r = alpha * (u2 / (1.0 - u2)).sqrt();
+    let n = normalize([-r * phi.cos(), -r * phi.sin(), 1.0]);
+    add(scale(view, -1.0), scale(n, 2.0 * dot(n, view)))
+}
+
+///
+/// A clamped cosine distribution transformed by the matrix `M = [x y z] * [[m11, 0, m13], [0, m22, 0], [0, 0, 1]]`.
+///
+#[derive(Clone)]
+struct Ltc {
+    m11: f64,
+    m22: f64,
+    m13: f64,
+    amplitude: f64,
+    x: Vector,
+    y: Vector,
+    z: Vector,
+    m: Matrix,
+    inverse_m: Matrix,
+    determinant_m: f64,
+}
+
+impl Ltc {
+    fn new() -> Self {
+        let mut ltc = Self {
+            m11: 1.0,
+            m22: 1.0,
+            m13: 0.0,
+            amplitude: 1.0,
+            x: [1.0, 0.0, 0.0],
+            y: [0.0, 1.0, 0.0],
+            z: [0.0, 0.0, 1.0],
+            m: [[0.0; 3]; 3],
+            inverse_m: [[0.0; 3]; 3],
+            determinant_m: 1.0,
+        };
+        ltc.update();
+        ltc
+    }
+
+    fn update(&mut self) {
+        let basis = [
+            [self.x[0], self.y[0], self.z[0]],
+            [self.x[1], self.y[1], self.z[1]],
+            [self.x[2], self.y[2], self.z[2]],
+        ];
+        let scaling = [
+            [self.m11, 0.0, self.m13],
+            [0.0, self.m22, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        self.m = multiply(&basis, &scaling);
+        self.inverse_m = inverse(&self.m);
+        self.determinant_m = determinant(&self.m).abs();
+    }
+
+    fn set_parameters(&mut self, parameters: [f64; 3], isotropic: bool) {
+        self.m11 = parameters[0].max(1e-7);
+        self.m22 = if isotropic {
+            self.m11
+        } else {
+            parameters[1].max(1e-7)
+        };
+        self.m13 = if isotropic { 0.0 } else { parameters[2] };
+        self.update();
+    }
+
+    fn evaluate(&self, light: Vector) -> f64 {
+        let original = normalize(transform(&self.inverse_m, light));
+        let transformed = transform(&self.m, original);
+        let l = length(transformed);
+        let jacobian = self.determinant_m / (l * l * l);
+        self.amplitude * (original[2].max(0.0) / PI) / jacobian
+    }
+
+    fn sample(&self, u1: f64, u2: f64) -> Vector {
+        let theta = u1.sqrt().acos();
+        let phi = 2.0 * PI * u2;
+        normalize(transform(
+            &self.m,
+            [
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ],
+        ))
+    }
+
+    ///
+    /// Returns the error between this distribution and the GGX distribution using multiple importance sampling of both distributions.
+    ///
+    fn error(&self, view: Vector, alpha: f64) -> f64 {
+        let mut error = 0.0;
+        for j in 0..ERROR_SAMPLES {
+            for i in 0..ERROR_SAMPLES {
+                let u1 = (i as f64 + 0.5) / ERROR_SAMPLES as f64;
+                let u2 = (j as f64 + 0.5) / ERROR_SAMPLES as f64;
+                for light in [self.sample(u1, u2), ggx_sample(view, alpha, u1, u2)] {
+                    let (brdf, brdf_pdf) = ggx_evaluate(view, light, alpha);
+                    let ltc = self.evaluate(light);
+                    let ltc_pdf = ltc / self.amplitude;
+                    error += (brdf - ltc).abs().powi(3) / (ltc_pdf + brdf_pdf);
+                }
+            }
+        }
+        error / (ERROR_SAMPLES * ERROR_SAMPLES) as f64
+    }
+}
+
+///
+/// Returns the amplitude, the Fresnel term and the average direction of the GGX distribution.
+///
+fn ggx_moments(view: Vector, alpha: f64) -> (f64, f64, Vector) {
+    let mut amplitude = 0.0;
+    let mut fresnel = 0.0;
+    let mut direction = [0.0; 3];
+    for j in 0..AMPLITUDE_SAMPLES {
+        for i in 0..AMPLITUDE_SAMPLES {
+            let u1 = (i as f64 + 0.5) / AMPLITUDE_SAMPLES as f64;
+            let u2 = (j as f64 + 0.5) / AMPLITUDE_SAMPLES as f64;
+            let light = ggx_sample(view, alpha, u1, u2);
+            let (brdf, pdf) = ggx_evaluate(view, light, alpha);
+            if pdf > 0.0 {
+                let weight = brdf / pdf;
+                let h = normalize(add(view, light));
+                amplitude += weight;
+                fresnel += weight * (1.0 - dot(view, h).max(0.0)).powi(5);
+                direction = add(direction, scale(light, weight));
+            }
+        }
+    }
+    let count = (AMPLITUDE_SAMPLES * AMPLITUDE_SAMPLES) as f64;
+    direction[1] = 0.0;
+    (amplitude / count, fresnel / count, normalize(direction))
+}
+
+///
+/// Minimizes the given function using the Nelder-Mead method starting from the given point.
+///
+fn nelder_mead(
+    start: [f64; 3],
+    delta: f64,
+    tolerance: f64,
+    f: impl Fn([f64; 3]) -> f64,
+) -> [f64; 3] {
+    const MAX_ITERATIONS: usize = 100;
+    let mut points = [start; 4];
+    for (i, point) in points.iter_mut().skip(1).enumerate() {
+        point[i] += delta;
+    }
+    let mut values = points.map(&f);
+    let lowest =
+        |values: &[f64; 4]| (0..4).fold(0, |l, i| if values[i] < values[l] { i } else { l });
+    for _ in 0..MAX_ITERATIONS {
+        let lo = lowest(&values);
+        let hi = (0..4).fold(0, |h, i| if values[i] > values[h] { i } else { h });
+        let next_hi = (0..4)
+            .filter(|i| *i != hi)
+            .fold(if hi == 0 { 1 } else { 0 }, |h, i| {
+                if values[i] > values[h] {
+                    i
+                } else {
+                    h
+                }
+            });
+        if (values[hi] - values[lo]).abs() < tolerance {
+            break;
+        }
+        let mut centroid = [0.0; 3];
+        for (_, point) in points.iter().enumerate().filter(|(i, _)| *i != hi) {
+            centroid = add(centroid, *point);
+        }
+        let centroid = centroid.map(|v| v / 3.0);
+        let along = |t: f64| add(centroid, scale(add(points[hi], scale(centroid, -1.0)), t));
+
+        let reflected = along(-1.0);
+        let reflected_value = f(reflected);
+        if reflected_value < values[lo] {
+            let expanded = along(-2.0);
+            let expanded_value = f(expanded);
+            if expanded_value < reflected_value {
+                points[hi] = expanded;
+                values[hi] = expanded_value;
+            } else {
+                points[hi] = reflected;
+                values[hi] = reflected_value;
+            }
+            continue;
+        }
+        if reflected_value < values[next_hi] {
+            points[hi] = reflected;
+            values[hi] = reflected_value;
+            continue;
+        }
+        let contracted = along(if reflected_value < values[hi] {
+            -0.5
+        } else {
+            0.5
+        });
+        let contracted_value = f(contracted);
+        if contracted_value < values[hi].min(reflected_value) {
+            points[hi] = contracted;
+            values[hi] = contracted_value;
+            continue;
+        }
+        let lowest_point = points[lo];
+        for i in (0..4).filter(|i| *i != lo) {
+            points[i] = add(
+                lowest_point,
+                scale(add(points[i], scale(lowest_point, -1.0)), 0.5),
+            );
+            values[i] = f(points[i]);
+        }
+    }
+    points[lowest(&values)]
+}
+
+///
+/// Fits the given distribution to the GGX distribution with the given roughness and view direction, starting from the current parameters of the distribution
+/// if the view direction is not along the normal.
+///
+fn fit(ltc: &mut Ltc, roughness: f64, view: Vector, start: Option<[f64; 2]>) -> (f64, f64) {
+    let alpha = (roughness * roughness).max(0.00001);
+    let (amplitude, fresnel, direction) = ggx_moments(view, alpha);
+    ltc.amplitude = amplitude;
+    let isotropic = start.is_some();
+    if let Some([m11, m22]) = start {
+        // The distribution is isotropic when the view direction is along the normal
+        ltc.x = [1.0, 0.0, 0.0];
+        ltc.y = [0.0, 1.0, 0.0];
+        ltc.z = [0.0, 0.0, 1.0];
+        ltc.m11 = m11;
+        ltc.m22 = m22;
+        ltc.m13 = 0.0;
+    } else {
+        ltc.x = [direction[2], 0.0, -direction[0]];
+        ltc.y = [0.0, 1.0, 0.0];
+        ltc.z = direction;
+    }
+    ltc.update();
+    let base = ltc.clone();
+    let parameters = nelder_mead([ltc.m11, ltc.m22, ltc.m13], 0.05, 1e-5, |parameters| {
+        let mut ltc = base.clone();
+        ltc.set_parameters(parameters, isotropic);
+        ltc.error(view, alpha)
+    });
+    ltc.set_parameters(parameters, isotropic);
+    (amplitude, fresnel)
+}
+
+///
+/// Returns the view direction for the given index into the lookup tables, which is parameterized by `sqrt(1 - cos(theta))`.
+///
+fn view_direction(t: usize) -> Vector {
+    let x = t as f64 / (SIZE - 1) as f64;
+    let theta = (1.0 - x * x).acos().min(1.57);
+    [theta.sin(), 0.0, theta.cos()]
+}
+
+///
+/// Returns the inverse of the given matrix normalized such that the middle element is one, stored as the four elements which are not trivially zero or one.
+///
+fn inverse_matrix_elements(m: &Matrix) -> [f32; 4] {
+    let inverse_m = inverse(m);
+    let s = inverse_m[1][1];
+    [
+        inverse_m[0][0],
+        inverse_m[2][0],
+        inverse_m[0][2],
+        inverse_m[2][2],
+    ]
+    .map(|v| (v / s) as f32)
+}
+
+///
+/// Fits the whole lookup tables, where each fit starts from the previous fit, going from high to low roughness and along the view angle.
+///
+fn fit_lookup_tables() -> (Vec<[f32; 4]>, Vec<[f32; 2]>) {
+    let mut matrices = vec![[[0.0; 3]; 3]; SIZE * SIZE];
+    let mut amplitudes = vec![[0.0; 2]; SIZE * SIZE];
+    let mut ltc = Ltc::new();
+    for a in (0..SIZE).rev() {
+        for t in 0..SIZE {
+            let start = if t > 0 {
+                None
+            } else if a == SIZE - 1 {
+                Some([1.0, 1.0])
+            } else {
+                let previous = &matrices[a + 1];
+                Some([previous[0][0], previous[1][1]])
+            };
+            let roughness = a as f64 / (SIZE - 1) as f64;
+            let (amplitude, fresnel) = fit(&mut ltc, roughness, view_direction(t), start);
+            matrices[a + t * SIZE] = ltc.m;
+            amplitudes[a + t * SIZE] = [amplitude as f32, fresnel as f32];
+        }
+    }
+    (
+        matrices.iter().map(inverse_matrix_elements).collect(),
+        amplitudes,
+    )
+}
+
+fn to_bytes<const N: usize>(data: &[[f32; N]]) -> Vec<u8> {
+    data.iter()
+        .flatten()
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+}
+
+fn from_bytes<const N: usize>(bytes: &[u8]) -> Vec<[f32; N]> {
+    bytes
+        .chunks_exact(4 * N)
+        .map(|chunk| {
+            std::array::from_fn(|i| f32::from_le_bytes(chunk[4 * i..4 * i + 4].try_into().unwrap()))
+        })
+        .collect()
+}
+
+#[test]
+#[ignore]
+fn generate_ltc_lookup_tables() {
+    let (matrices, amplitudes) = fit_lookup_tables();
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/src/renderer/light/ltc");
+    std::fs::write(format!("{}/ltc_matrix.bin", directory), to_bytes(&matrices)).unwrap();
+    std::fs::write(
+        format!("{}/ltc_amplitude.bin", directory),
+        to_bytes(&amplitudes),
+    )
+    .unwrap();
+}
+
+#[test]
+fn ltc_amplitude_matches_fit() {
+    let amplitudes = from_bytes::<2>(include_bytes!("ltc_amplitude.bin"));
+    assert_eq!(amplitudes.len(), SIZE * SIZE);
+    for (a, t) in [(0, 0), (10, 40), (32, 32), (63, 5), (50, 63)] {
+        let roughness = a as f64 / (SIZE - 1) as f64;
+        let alpha = (roughness * roughness).max(0.00001);
+        let (amplitude, fresnel, _) = ggx_moments(view_direction(t), alpha);
+        let expected = amplitudes[a + t * SIZE];
+        assert!((expected[0] - amplitude as f32).abs() < 1e-5);
+        assert!((expected[1] - fresnel as f32).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn ltc_matrix_matches_fit() {
+    let matrices = from_bytes::<4>(include_bytes!("ltc_matrix.bin"));
+    assert_eq!(matrices.len(), SIZE * SIZE);
+    // The first fit, which is the highest roughness with the view direction along the normal, does not depend on other fits
+    let mut ltc = Ltc::new();
+    fit(&mut ltc, 1.0, view_direction(0), Some([1.0, 1.0]));
+    let expected = matrices[SIZE - 1];
+    let actual = inverse_matrix_elements(&ltc.m);
+    for i in 0..4 {
+        assert!((expected[i] - actual[i]).abs() < 1e-4);
+    }
+}
@@ -1,10 +1,16 @@
 use crate::core::*;
+use crate::renderer::light::*;
 use crate::renderer::*;
 
 ///
 /// A light which shines from the given position in all directions.
+/// The light will cast shadows if you [generate a shadow map](PointLight::generate_shadow_map).
 ///
 pub struct PointLight {
+    context: Context,
+    shadow_texture: Option<DepthTextureCubeMap>,
+    shadow_z_near: f32,
+    shadow_z_far: f32,
     /// The intensity of the light. This allows for higher intensity than 1 which can be used to simulate high intensity light sources like the sun.
     pub intensity: f32,
     /// The base color of the light.
@@ -13,47 +19,217 @@ pub struct PointLight {
     pub position: Vec3,
     /// The [Attenuation] of the light.
     pub attenuation: Attenuation,
+    /// The [ShadowSettings] used when the light casts shadows.
+    pub shadow_settings: ShadowSettings,
 }
 
 impl PointLight {
     /// Constructs a new point light.
     pub fn new(
-        _context: &Context,
+        context: &Context,
         intensity: f32,
         color: Srgba,
         position: &Vec3,
         attenuation: Attenuation,
     ) -> PointLight {
         PointLight {
+            context: context.clone(),
+            shadow_texture: None,
+            shadow_z_near: 0.0,
+            shadow_z_far: 0.0,
             intensity,
             color,
             position: *position,
             attenuation,
+            shadow_settings: ShadowSettings::default(),
         }
     }
+
+    ///
+    /// Clear the shadow map, effectively disable the shadow.
+    /// Only necessary if you want to disable the shadow, if you want to update the shadow, just use [PointLight::generate_shadow_map].
+    ///
+    pub fn clear_shadow_map(&mut self) {
+        self.shadow_texture = None;
+        self.shadow_z_near = 0.0;
+        self.shadow_z_far = 0.0;
+    }
+
+    ///
+    /// Generate a shadow map which is used to simulate shadows from the point light onto the geometries given as input.
+    /// The shadow map is a cube map, where each side is rendered from the position of the light, so the geometries are rendered six times.
+    /// It is recomended that the texture size is power of 2.
+    /// If the shadows are too low resolution (the edges between shadow and non-shadow are pixelated) try to increase the texture size.
+    ///
+    pub fn generate_shadow_map(
+        &mut self,
+        texture_size: u32,
+        geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+    ) {
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
+
+        let mut z_far = 0.0f32;
+        let mut z_near = f32::MAX;
+        for geometry in geometries.clone() {
+            let aabb = geometry.aabb();
+            if !aabb.is_empty() {
+                z_far = z_far.max(aabb.distance_max(&self.position));
+                z_near = z_near.min(aabb.distance(&self.position));
+            }
+        }
+        if z_far <= 0.0 {
+            return;
+        }
+        // The distance along the view direction of a cube map side can be up to sqrt(3) times smaller than the euclidean distance
+        let z_near = (z_near / 3.0f32.sqrt()).max(0.01);
+
+        let mut shadow_texture = DepthTextureCubeMap::new::<f32>(
+            &self.context,
+            texture_size,
+            texture_size,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        );
+        let mut depth_material = DepthMaterial {
+            render_states: RenderStates {
+                write_mask: WriteMask::DEPTH,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for side in CubeMapSide::iter() {
+            let shadow_camera = Camera::new_perspective(
+                viewport,
+                self.position,
+                self.position + side.direction(),
+                side.up(),
+                degrees(90.0),
+                z_near,
+                z_far,
+            );
+            shadow_texture
+                .as_depth_target(side)
+                .clear(ClearState::default())
+                .write::<RendererError>(|| {
+                    for geometry in geometries
+                        .clone()
+                        .into_iter()
+                        .filter(|g| shadow_camera.in_frustum(&g.aabb()))
+                    {
+                        depth_material.alpha_mask = geometry.alpha_mask();
+                        depth_material.vertex_deformation = geometry.vertex_deformation();
+                        render_with_material(
+                            &self.context,
+                            &shadow_camera,
+                            &geometry,
+                            &depth_material,
+                            &[],
+                        );
+                    }
+                    Ok(())
+                })
+                .unwrap();
+        }
+        self.shadow_texture = Some(shadow_texture);
+        self.shadow_z_near = z_near;
+        self.shadow_z_far = z_far;
+    }
+
+    ///
+    /// Returns a reference to the shadow map if it has been generated.
+    ///
+    pub fn shadow_map(&self) -> Option<&DepthTextureCubeMap> {
+        self.shadow_texture.as_ref()
+    }
+
+    ///
+    /// Returns the distance from the light where the contribution of the light becomes negligible.
+    /// Surfaces further away than this distance are not lit by this light.
+    ///
+    pub fn influence_radius(&self) -> f32 {
+        self.attenuation.influence_radius(
+            max_intensity(self.color, self.intensity),
+            LIGHT_INFLUENCE_THRESHOLD,
+        )
+    }
 }
 
 impl Light for PointLight {
     fn shader_source(&self, i: u32) -> String {
-        format!(
-        "
-            uniform vec3 color{};
-            uniform vec3 attenuation{};
-            uniform vec3 position{};
-
-            vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
-            {{
-                vec3 light_direction = position{} - position;
-                float distance = length(light_direction);
-                light_direction = light_direction / distance;
-
-                vec3 light_color = attenuate(color{}, attenuation{}, distance);
-                return calculate_light(light_color, light_direction, surface_color, view_direction, normal, metallic, roughness);
-            }}
-        
-        ", i, i, i, i, i, i, i)
+        if self.shadow_texture.is_some() {
+            format!(
+            "
+                uniform vec3 color{};
+                uniform vec3 attenuation{};
+                uniform vec3 position{};
+
+                {}
+
+                vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 light_direction = position{} - position;
+                    float distance = length(light_direction);
+                    light_direction = light_direction / distance;
+
+                    vec3 light_color = attenuate(color{}, attenuation{}, distance);
+                    return calculate_light(light_color, light_direction, surface_color, view_direction, normal, metallic, roughness)
+                        * calculate_shadow{}(light_direction, normal, position);
+                }}
+
+            ", i, i, i, cube_shadow_map_shader_source(&self.shadow_settings, i), i, i, i, i, i)
+        } else {
+            format!(
+            "
+                uniform vec3 color{};
+                uniform vec3 attenuation{};
+                uniform vec3 position{};
+
+                vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 light_direction = position{} - position;
+                    float distance = length(light_direction);
+                    light_direction = light_direction / distance;
+
+                    vec3 light_color = attenuate(color{}, attenuation{}, distance);
+                    return calculate_light(light_color, light_direction, surface_color, view_direction, normal, metallic, roughness);
+                }}
+
+            ", i, i, i, i, i, i, i)
+        }
     }
+    fn incoming_light_shader_source(&self, i: u32) -> Option<String> {
+        let visibility = if self.shadow_texture.is_some() {
+            format!("visibility = calculate_shadow{i}(light_direction, normal, position);")
+        } else {
+            String::new()
+        };
+        Some(format!(
+            "
+                vec3 calculate_incoming_light{i}(vec3 position, vec3 normal, out vec3 light_direction, out float visibility)
+                {{
+                    light_direction = position{i} - position;
+                    float distance = length(light_direction);
+                    light_direction = light_direction / distance;
+
+                    visibility = 1.0;
+                    {visibility}
+                    return attenuate(color{i}, attenuation{i}, distance);
+                }}
+
+            "
+        ))
+    }
+
     fn use_uniforms(&self, program: &Program, i: u32) {
+        if let Some(ref tex) = self.shadow_texture {
+            program.use_depth_texture_cube(&format!("shadowMap{}", i), tex);
+            program.use_uniform(
+                &format!("shadowNearFar{}", i),
+                vec2(self.shadow_z_near, self.shadow_z_far),
+            );
+            self.shadow_settings.use_uniforms(program, i);
+        }
         program.use_uniform(
             &format!("color{}", i),
             self.color.to_linear_srgb().truncate() * self.intensity,
@@ -69,7 +245,18 @@ impl Light for PointLight {
         program.use_uniform(&format!("position{}", i), self.position);
     }
 
+    fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        aabb.is_empty() || aabb.distance(&self.position) <= self.influence_radius()
+    }
+
     fn id(&self) -> LightId {
-        LightId::PointLight
+        LightId::PointLight(
+            self.shadow_texture.is_some(),
+            if self.shadow_texture.is_some() {
+                self.shadow_settings.filter.id()
+            } else {
+                0
+            },
+        )
     }
 }
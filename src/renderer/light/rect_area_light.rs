@@ -0,0 +1,123 @@
+use crate::core::*;
+use crate::renderer::light::*;
+use crate::renderer::*;
+use std::sync::Arc;
+
+///
+/// A rectangular area light which shines from one side of the rectangle, ie. in the given direction.
+/// The lighting is evaluated using linearly transformed cosines (LTC) which gives physically based highlights
+/// when using the Cook-Torrance [LightingModel] with the GGX normal distribution function.
+///
+pub struct RectAreaLight {
+    lookup_tables: Arc<LtcLookupTables>,
+    /// The intensity of the light. This allows for higher intensity than 1 which can be used to simulate high intensity light sources like the sun.
+    pub intensity: f32,
+    /// The base color of the light.
+    pub color: Srgba,
+    /// The position of the center of the rectangle.
+    pub position: Vec3,
+    /// The direction the light shines, ie. the normal of the rectangle.
+    pub direction: Vec3,
+    /// The up direction of the rectangle which together with the direction specifies the orientation of the rectangle.
+    pub up: Vec3,
+    /// The size of the rectangle along the horizontal axis.
+    pub width: f32,
+    /// The size of the rectangle along the up direction.
+    pub height: f32,
+}
+
+impl RectAreaLight {
+    /// Constructs a new rectangular area light.
+    pub fn new(
+        context: &Context,
+        intensity: f32,
+        color: Srgba,
+        position: &Vec3,
+        direction: &Vec3,
+        up: &Vec3,
+        width: f32,
+        height: f32,
+    ) -> RectAreaLight {
+        RectAreaLight {
+            lookup_tables: LtcLookupTables::shared(context),
+            intensity,
+            color,
+            position: *position,
+            direction: *direction,
+            up: *up,
+            width,
+            height,
+        }
+    }
+}
+
+impl Light for RectAreaLight {
+    fn shader_source(&self, i: u32) -> String {
+        format!(
+            "
+                {}
+                uniform vec3 color{i};
+                uniform vec3 position{i};
+                uniform vec3 axisX{i};
+                uniform vec3 axisY{i};
+
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 p = position{i} - position;
+                    if (dot(p, cross(axisX{i}, axisY{i})) <= 0.0) {{
+                        return vec3(0.0);
+                    }}
+                    vec3 p0 = p - axisX{i} - axisY{i};
+                    vec3 p1 = p + axisX{i} - axisY{i};
+                    vec3 p2 = p + axisX{i} + axisY{i};
+                    vec3 p3 = p - axisX{i} + axisY{i};
+
+                    vec2 uv = ltc_uv(dot(normal, view_direction), roughness);
+                    vec4 t = texture(ltcMatrix{i}, uv);
+                    mat3 Minv = mat3(vec3(t.x, 0.0, t.y), vec3(0.0, 1.0, 0.0), vec3(t.z, 0.0, t.w));
+
+                    float diffuse = ltc_evaluate_quad(ltc_transform(mat3(1.0), normal, view_direction), p0, p1, p2, p3);
+                    float specular = ltc_evaluate_quad(ltc_transform(Minv, normal, view_direction), p0, p1, p2, p3);
+                    return calculate_area_light(color{i}, diffuse, specular, texture(ltcAmplitude{i}, uv).xy, surface_color, metallic);
+                }}
+
+            ",
+            LtcLookupTables::fragment_shader_source(i)
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        self.lookup_tables.use_uniforms(program, i);
+        let direction = self.direction.normalize();
+        let axis_y = (self.up - direction * self.up.dot(direction)).normalize();
+        let axis_x = direction.cross(axis_y);
+        program.use_uniform(
+            &format!("color{}", i),
+            self.color.to_linear_srgb().truncate() * self.intensity,
+        );
+        program.use_uniform(&format!("position{}", i), self.position);
+        program.use_uniform(&format!("axisX{}", i), axis_x * 0.5 * self.width);
+        program.use_uniform(&format!("axisY{}", i), axis_y * 0.5 * self.height);
+    }
+
+    fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        if aabb.is_empty() || aabb.is_infinite() {
+            return true;
+        }
+        // The light only shines in front of the light plane, so the box is not affected if all corners are behind it
+        let (min, max) = (aabb.min(), aabb.max());
+        let direction = self.direction.normalize();
+        (0..8).any(|c| {
+            let corner = vec3(
+                if c & 1 == 0 { min.x } else { max.x },
+                if c & 2 == 0 { min.y } else { max.y },
+                if c & 4 == 0 { min.z } else { max.z },
+            );
+            (corner - self.position).dot(direction) > 0.0
+        })
+    }
+
+    fn id(&self) -> LightId {
+        LightId::RectAreaLight
+    }
+}
@@ -0,0 +1,61 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// A local light probe which stores the light arriving at a point as an [Environment], for example the reflections inside a room.
+/// Add the probe to [AmbientLight::reflection_probes] to use it instead of the [AmbientLight::environment] for surfaces inside the bounding box of the probe.
+///
+/// Since the probe is captured at a single point, the reflections are corrected by intersecting the reflected direction with the bounding box of the probe (box projection),
+/// which gives correct reflections for surfaces at any position inside the box as long as the box approximates the surrounding geometry, for example the walls of a room.
+///
+pub struct ReflectionProbe {
+    /// The light arriving at the position of the probe.
+    pub environment: Environment,
+    /// The position where the light was captured.
+    pub position: Vec3,
+    /// The bounding box in which the probe affects surfaces, including surfaces on the boundary of the box. It is also used as an approximation of the surrounding geometry when correcting the reflections.
+    pub aabb: AxisAlignedBoundingBox,
+    /// The distance outside the bounding box over which the probe fades out and is blended with overlapping probes and the [AmbientLight::environment].
+    pub blend_distance: f32,
+}
+
+impl ReflectionProbe {
+    ///
+    /// Constructs a new reflection probe from the given environment map, captured at the given position, which affects surfaces inside the given bounding box.
+    /// The environment map is prefiltered using [Environment::new].
+    ///
+    pub fn new(
+        context: &Context,
+        environment_map: &TextureCubeMap,
+        position: Vec3,
+        aabb: AxisAlignedBoundingBox,
+    ) -> Self {
+        let size = aabb.size();
+        Self {
+            environment: Environment::new(context, environment_map),
+            position,
+            aabb,
+            blend_distance: 0.1 * size.x.min(size.y).min(size.z),
+        }
+    }
+
+    ///
+    /// Constructs a new reflection probe like [ReflectionProbe::new] by rendering the given objects with the given lights from the given position,
+    /// see [capture_cube_map].
+    ///
+    pub fn capture(
+        context: &Context,
+        position: Vec3,
+        aabb: AxisAlignedBoundingBox,
+        texture_size: u32,
+        objects: impl IntoIterator<Item = impl Object> + Clone,
+        lights: &[&dyn Light],
+    ) -> Self {
+        Self::new(
+            context,
+            &capture_cube_map(context, position, texture_size, objects, lights),
+            position,
+            aabb,
+        )
+    }
+}
@@ -16,6 +16,67 @@ vec3 fresnel_schlick_roughness(vec3 F0, float cosTheta, float roughness)
     return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(saturate(1.0 - cosTheta), 5.0);
 }
 
+// The parameters of the material extensions, which are not arguments to the lighting functions of the lights
+// and therefore are set by the material before calculating the lighting
+#ifdef USE_SPECULAR
+// The reflectance at normal incidence of the non-metallic part of the material
+vec3 dielectric_specular = vec3(0.04);
+#endif
+
+#ifdef USE_CLEARCOAT
+float clearcoat_factor = 0.0;
+float clearcoat_roughness = 0.0;
+vec3 clearcoat_normal = vec3(0.0, 0.0, 1.0);
+#endif
+
+#ifdef USE_SHEEN
+vec3 sheen_color = vec3(0.0);
+float sheen_roughness = 0.0;
+#endif
+
+#ifdef USE_TRANSMISSION
+// The fraction of the light which is transmitted through the non-metallic part of the material instead of being diffusely reflected
+float transmission_factor = 0.0;
+#endif
+
+#ifdef USE_PARALLAX_SHADOW
+// Defined by the material, returns the fraction of the light from the given direction which is not blocked by the height field of the material
+float parallax_shadow(vec3 L);
+#endif
+
+#ifdef USE_TOON
+// The number of discrete bands of the diffuse light, if not using a ramp texture
+float toon_bands = 3.0;
+vec3 toon_specular_color = vec3(0.0);
+float toon_glossiness = 16.0;
+vec3 toon_rim_color = vec3(0.0);
+float toon_rim_amount = 0.7;
+#ifdef USE_TOON_RAMP
+// Maps the diffuse light intensity to the quantized light, sampled horizontally
+uniform sampler2D toonRamp;
+#endif
+#endif
+
+// compute the reflectance at normal incidence, for non-metals a constant base specular factor of 0.04 grey is used unless specified by the material
+vec3 specular_reflectance(vec3 surface_color, float metallic)
+{
+#ifdef USE_SPECULAR
+    return mix(dielectric_specular, surface_color, metallic);
+#else
+    return mix(vec3(0.04), surface_color, metallic);
+#endif
+}
+
+// compute the diffuse reflectance, which is zero for metals and for the part of the light which is transmitted through the material
+vec3 diffuse_reflectance(vec3 surface_color, float metallic)
+{
+#ifdef USE_TRANSMISSION
+    return mix(surface_color, vec3(0.0), metallic) * (1.0 - transmission_factor);
+#else
+    return mix(surface_color, vec3(0.0), metallic);
+#endif
+}
+
 
 // following functions are copies of UE4
 // for computing cook-torrance specular lighting terms
@@ -98,15 +159,36 @@ vec3 cooktorrance_specular(in float NdL, in float NdV, in float NdH, in vec3 spe
     return specular_fresnel * G * D / (4.0 * NdV * NdL);
 }
 
+#ifdef USE_TOON
+// compute the quantized diffuse light and the hard edged specular and rim light used for toon shading
+vec3 calculate_toon_light(vec3 light_color, vec3 L, vec3 surface_color, vec3 V, vec3 N)
+{
+    float NdL = dot(N, L);
+#ifdef USE_TOON_RAMP
+    vec3 diffuse = surface_color * texture(toonRamp, vec2(saturate(NdL), 0.5)).rgb / PI;
+#else
+    vec3 diffuse = surface_color * ceil(saturate(NdL) * toon_bands) / toon_bands / PI;
+#endif
+    float NdH = max(0.0, dot(N, normalize(L + V)));
+    float specular = step(0.0, NdL) * smoothstep(0.005, 0.01, pow(NdH, toon_glossiness * toon_glossiness));
+    float rim = (1.0 - max(0.0, dot(N, V))) * pow(saturate(NdL), 0.1);
+    rim = smoothstep(toon_rim_amount - 0.01, toon_rim_amount + 0.01, rim);
+    return (diffuse + specular * toon_specular_color + rim * toon_rim_color) * light_color;
+}
+#endif
+
 vec3 calculate_light(vec3 light_color, vec3 L, vec3 surface_color, vec3 V, vec3 N, float metallic, float roughness)
 {
+#ifdef USE_TOON
+    return calculate_toon_light(light_color, L, surface_color, V, N);
+#endif
+
     // compute material reflectance
     float NdL = max(0.001, dot(N, L));
     float NdV = max(0.001, dot(N, V));
 
-    // mix between metal and non-metal material, for non-metal
-    // constant base specular factor of 0.04 grey is used
-    vec3 F0 = mix(vec3(0.04), surface_color, metallic);
+    // mix between metal and non-metal material
+    vec3 F0 = specular_reflectance(surface_color, metallic);
 
 #ifdef PHONG
     // specular reflectance with PHONG
@@ -131,10 +213,37 @@ vec3 calculate_light(vec3 light_color, vec3 L, vec3 surface_color, vec3 V, vec3
 
     // diffuse is common for any model
     vec3 diffuse_fresnel = 1.0 - specular_fresnel;
-    vec3 diffuse = diffuse_fresnel * mix(surface_color, vec3(0.0), metallic) / PI;
+    vec3 diffuse = diffuse_fresnel * diffuse_reflectance(surface_color, metallic) / PI;
+
+    vec3 color = (diffuse + specular) * light_color * NdL;
+
+#ifdef USE_SHEEN
+    // sheen layer on top of the base layer using the Charlie distribution and the Neubelt visibility term
+    vec3 Hs = normalize(L + V);
+    float sheen_alpha = max(sheen_roughness * sheen_roughness, 0.001);
+    float sin2 = max(1.0 - pow(max(dot(N, Hs), 0.0), 2.0), 0.0);
+    float sheen_D = (2.0 + 1.0 / sheen_alpha) * pow(sin2, 0.5 / sheen_alpha) / (2.0 * PI);
+    float sheen_V = 1.0 / (4.0 * (NdL + NdV - NdL * NdV));
+    color = color * (1.0 - 0.157 * max(sheen_color.r, max(sheen_color.g, sheen_color.b))) + sheen_color * sheen_D * sheen_V * light_color * NdL;
+#endif
+
+#ifdef USE_CLEARCOAT
+    // clearcoat layer on top of the base layer, which is a dielectric with an index of refraction of 1.5
+    vec3 Hc = normalize(L + V);
+    float CNdL = max(0.001, dot(clearcoat_normal, L));
+    float CNdV = max(0.001, dot(clearcoat_normal, V));
+    float CNdH = max(0.001, dot(clearcoat_normal, Hc));
+    float clearcoat_fresnel = clearcoat_factor * fresnel_schlick(vec3(0.04), max(0.001, dot(Hc, V))).x;
+    float clearcoat_specular = D_GGX(clearcoat_roughness, CNdH) * G_schlick(clearcoat_roughness, CNdV, CNdL) / (4.0 * CNdV * CNdL);
+    color = color * (1.0 - clearcoat_fresnel) + clearcoat_fresnel * clearcoat_specular * light_color * CNdL;
+#endif
+
+#ifdef USE_PARALLAX_SHADOW
+    color *= parallax_shadow(L);
+#endif
 
     // final result
-    return (diffuse + specular) * light_color * NdL;
+    return color;
 }
 
 vec3 attenuate(vec3 light_color, vec3 attenuation, float distance)
@@ -146,39 +255,185 @@ vec3 attenuate(vec3 light_color, vec3 attenuation, float distance)
     return light_color / max(1.0, att);
 }
 
+// A Poisson disk with 16 samples used for filtering shadow map lookups
+const vec2 POISSON_DISK[16] = vec2[](
+    vec2( -0.94201624, -0.39906216 ),
+    vec2( 0.94558609, -0.76890725 ),
+    vec2( -0.094184101, -0.92938870 ),
+    vec2( 0.34495938, 0.29387760 ),
+    vec2( -0.91588581, 0.45771432 ),
+    vec2( -0.81544232, -0.87912464 ),
+    vec2( -0.38277543, 0.27676845 ),
+    vec2( 0.97484398, 0.75648379 ),
+    vec2( 0.44323325, -0.97511554 ),
+    vec2( 0.53742981, -0.47373420 ),
+    vec2( -0.26496911, -0.41893023 ),
+    vec2( 0.79197514, 0.19090188 ),
+    vec2( -0.24188840, 0.99706507 ),
+    vec2( -0.81409955, 0.91437590 ),
+    vec2( 0.19984126, 0.78641367 ),
+    vec2( 0.14383161, -0.14100790 )
+);
+
+// Returns 1.0 if the given depth stored in a shadow map does not occlude the reference depth and 0.0 otherwise
+float shadow_compare(float shadow_cast_distance, float reference)
+{
+    return shadow_cast_distance > 0.999 || shadow_cast_distance > reference ? 1.0 : 0.0;
+}
+
+// Returns the depth bias which is the constant depth bias or the slope scaled depth bias if that is larger
+float shadow_depth_bias(vec3 bias, vec3 lightDirection, vec3 normal)
+{
+    float cos_angle = clamp(dot(normal, lightDirection), 0.0, 1.0);
+    float tan_angle = sqrt(1.0 - cos_angle * cos_angle) / max(cos_angle, 0.2);
+    return max(bias.y * tan_angle, bias.x);
+}
+
+// Returns the shadow map uv coordinates and the depth of the given position offset along the normal by the normal bias
+vec3 shadow_coordinates(mat4 shadowMVP, float normal_bias, vec3 normal, vec3 position)
+{
+    vec4 shadow_coord = shadowMVP * vec4(position + normal_bias * normal, 1.0);
+    return shadow_coord.xyz / shadow_coord.w;
+}
+
+// Kept for backwards compatibility with custom shaders, the built-in lights use the shadow functions defined by the lights with their shadow settings.
+// Returns 1.0 if the position with the given shadow coordinates offset by the given uv offset is not in shadow and 0.0 otherwise.
 float is_visible(vec3 lightDirection, vec3 normal, sampler2D shadowMap, vec4 shadow_coord, vec2 offset)
 {
     vec2 uv = (shadow_coord.xy + offset)/shadow_coord.w;
     if(uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
         return 1.0;
     }
-    float shadow_cast_distance = texture(shadowMap, uv).x;
-    if(shadow_cast_distance > 0.999) {
-        return 1.0;
-    }
-    // Adjust shadow bias based on surface normal and light direction
     float bias = max(0.05 * (1.0 - dot(normal, lightDirection)), 0.005);
-    float true_distance = (shadow_coord.z - bias)/shadow_coord.w;
-    return shadow_cast_distance > true_distance ? 1.0 : 0.0;
+    return shadow_compare(texture(shadowMap, uv).x, (shadow_coord.z - bias)/shadow_coord.w);
 }
 
+// Kept for backwards compatibility with custom shaders, the built-in lights use the shadow functions defined by the lights with their shadow settings.
+// Returns the visibility of the given position using a fixed four sample filter.
 float calculate_shadow(vec3 lightDirection, vec3 normal, sampler2D shadowMap, mat4 shadowMVP, vec3 position)
 {
     vec4 shadow_coord = shadowMVP * vec4(position, 1.);
     float visibility = 0.0;
-    vec2 poissonDisk[4] = vec2[](
-                                 vec2( -0.94201624, -0.39906216 ),
-                                 vec2( 0.94558609, -0.76890725 ),
-                                 vec2( -0.094184101, -0.92938870 ),
-                                 vec2( 0.34495938, 0.29387760 )
-                                 );
     for (int i=0;i<4;i++)
     {
-        visibility += is_visible(lightDirection, normal, shadowMap, shadow_coord, poissonDisk[i] * 0.001f);
+        visibility += is_visible(lightDirection, normal, shadowMap, shadow_coord, POISSON_DISK[i] * 0.001f);
     }
     return visibility * 0.25;
 }
 
+// Returns the depth stored in a cube shadow map of a position in the given direction from the light
+float cube_shadow_depth(vec2 nearFar, vec3 shadow_direction)
+{
+    // The depth is stored along the view direction of the cube map side, ie. the major axis of the direction
+    vec3 a = abs(shadow_direction);
+    float z = max(a.x, max(a.y, a.z));
+    float near = nearFar.x;
+    float far = nearFar.y;
+    float clip_z = (far + near) / (far - near) * z - 2.0 * far * near / (far - near);
+    return 0.5 * clip_z / z + 0.5;
+}
+
+// Returns the index of the cascade that contains the given view depth or the cascade count if it is outside all cascades
+int shadow_cascade(vec4 splits, int count, float view_depth)
+{
+    for (int i = 0; i < count; i++)
+    {
+        if (view_depth < splits[i]) {
+            return i;
+        }
+    }
+    return count;
+}
+
+// Returns how much the next cascade should be blended in at the given view depth to hide the seam between the cascades
+float shadow_cascade_blend(vec4 splits, int count, int cascade, float view_depth)
+{
+    if (cascade >= count - 1) {
+        return 0.0;
+    }
+    float cascade_start = cascade == 0 ? 0.0 : splits[cascade - 1];
+    float blend_start = splits[cascade] - 0.1 * (splits[cascade] - cascade_start);
+    return smoothstep(blend_start, splits[cascade], view_depth);
+}
+
+// Returns the lookup position in the linearly transformed cosines (LTC) lookup tables for the given surface
+vec2 ltc_uv(float NdV, float roughness)
+{
+    const float LUT_SIZE = 64.0;
+    vec2 uv = vec2(roughness, sqrt(1.0 - saturate(NdV)));
+    return uv * (LUT_SIZE - 1.0) / LUT_SIZE + 0.5 / LUT_SIZE;
+}
+
+// Returns the transform from a position relative to the surface to the space of the clamped cosine distribution
+// where the distribution is transformed by the given inverse LTC matrix
+mat3 ltc_transform(mat3 Minv, vec3 N, vec3 V)
+{
+    vec3 T1 = V - N * dot(V, N);
+    T1 = dot(T1, T1) > 0.000001 ? normalize(T1) : normalize(cross(N, abs(N.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0)));
+    vec3 T2 = cross(N, T1);
+    return Minv * transpose(mat3(T1, T2, N));
+}
+
+// Returns the vector form factor of the edge between the two given directions
+vec3 ltc_edge_form_factor(vec3 v1, vec3 v2)
+{
+    // Rational fit of theta / (2 * PI * sin(theta)) where theta is the angle between the directions
+    float x = dot(v1, v2);
+    float y = abs(x);
+    float a = 0.8543985 + (0.4965155 + 0.0145206 * y) * y;
+    float b = 3.4175940 + (4.1616724 + y) * y;
+    float v = a / b;
+    float theta_sintheta = x > 0.0 ? v : 0.5 * inversesqrt(max(1.0 - x * x, 1e-7)) - v;
+    return cross(v1, v2) * theta_sintheta;
+}
+
+// Returns the form factor of a polygon with the given vector form factor where the part of the polygon below the horizon is clipped away
+float ltc_clipped_form_factor(vec3 form_factor)
+{
+    float l = length(form_factor);
+    return max((l * l + form_factor.z) / (l + 1.0), 0.0);
+}
+
+// Returns the integral of the transformed cosine distribution over the quad with the given corners relative to the surface position
+float ltc_evaluate_quad(mat3 transform, vec3 p0, vec3 p1, vec3 p2, vec3 p3)
+{
+    vec3 l0 = normalize(transform * p0);
+    vec3 l1 = normalize(transform * p1);
+    vec3 l2 = normalize(transform * p2);
+    vec3 l3 = normalize(transform * p3);
+    vec3 form_factor = ltc_edge_form_factor(l0, l1) + ltc_edge_form_factor(l1, l2)
+        + ltc_edge_form_factor(l2, l3) + ltc_edge_form_factor(l3, l0);
+    return ltc_clipped_form_factor(form_factor);
+}
+
+// Returns the integral of the transformed cosine distribution over the disk with the given center relative to the surface position
+// and the given axes, where the disk is approximated by a regular polygon
+float ltc_evaluate_disk(mat3 transform, vec3 center, vec3 axis_x, vec3 axis_y)
+{
+    const int EDGE_COUNT = 16;
+    vec3 first = normalize(transform * (center + axis_x));
+    vec3 previous = first;
+    vec3 form_factor = vec3(0.0);
+    for (int i = 1; i < EDGE_COUNT; i++)
+    {
+        float angle = 2.0 * PI * float(i) / float(EDGE_COUNT);
+        vec3 current = normalize(transform * (center + cos(angle) * axis_x + sin(angle) * axis_y));
+        form_factor += ltc_edge_form_factor(previous, current);
+        previous = current;
+    }
+    form_factor += ltc_edge_form_factor(previous, first);
+    return ltc_clipped_form_factor(form_factor);
+}
+
+// Returns the light reflected from an area light with the given color, where the diffuse and specular form factors are calculated using LTC
+vec3 calculate_area_light(vec3 light_color, float diffuse_form_factor, float specular_form_factor, vec2 ltc_amplitude, vec3 surface_color, float metallic)
+{
+    vec3 F0 = specular_reflectance(surface_color, metallic);
+    vec3 specular = specular_form_factor * (F0 * ltc_amplitude.x + (1.0 - F0) * ltc_amplitude.y);
+    vec3 diffuse = diffuse_form_factor * diffuse_reflectance(surface_color, metallic);
+    return (diffuse + specular) * light_color;
+}
+
 vec3 ImportanceSampleGGX(vec2 Xi, vec3 N, float roughness)
 {
 	float a = roughness*roughness;
@@ -201,3 +456,63 @@ vec3 ImportanceSampleGGX(vec2 Xi, vec3 N, float roughness)
 	vec3 sampleVec = tangent * H.x + bitangent * H.y + N * H.z;
 	return normalize(sampleVec);
 }
+
+// Returns the intensity stored in an IES profile lookup texture in the given direction from the light,
+// where axis_z is the direction of the light and axis_x and axis_y are the directions of the 0 and 90 degree horizontal angles
+float ies_intensity(sampler2D profile, vec3 direction, vec3 axis_x, vec3 axis_y, vec3 axis_z)
+{
+    float vertical_angle = acos(clamp(dot(direction, axis_z), -1.0, 1.0));
+    float horizontal_angle = atan(dot(direction, axis_y), dot(direction, axis_x));
+    return texture(profile, vec2(vertical_angle / PI, horizontal_angle / (2.0 * PI))).r;
+}
+
+// Returns the color of a cookie texture projected onto the given position using the given projection matrix or black if the position is outside the projection
+vec3 cookie_color(sampler2D cookie, mat3 tex_transform, mat4 cookie_mvp, vec3 position)
+{
+    vec4 coords = cookie_mvp * vec4(position, 1.0);
+    if (coords.w <= 0.0) {
+        return vec3(0.0);
+    }
+    vec2 uv = coords.xy / coords.w;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        return vec3(0.0);
+    }
+    return texture(cookie, (tex_transform * vec3(uv, 1.0)).xy).rgb;
+}
+
+// Returns the irradiance divided by pi arriving at a surface with the given normal from the light represented by the given L2 spherical harmonics coefficients
+vec3 spherical_harmonics_irradiance(vec3 coefficients[9], vec3 n)
+{
+    return coefficients[0] * 0.282095
+        + (coefficients[1] * n.y + coefficients[2] * n.z + coefficients[3] * n.x) * 0.488603 * (2.0 / 3.0)
+        + (coefficients[4] * 1.092548 * n.x * n.y
+            + coefficients[5] * 1.092548 * n.y * n.z
+            + coefficients[6] * 0.315392 * (3.0 * n.z * n.z - 1.0)
+            + coefficients[7] * 1.092548 * n.x * n.z
+            + coefficients[8] * 0.546274 * (n.x * n.x - n.y * n.y)) * 0.25;
+}
+
+// Returns the weight of a reflection probe at the given position, which is one inside the box and fades to zero over the blend distance outside the box
+float reflection_probe_weight(vec3 position, vec3 box_min, vec3 box_max, float blend_distance)
+{
+    float distance = length(max(box_min - position, 0.0) + max(position - box_max, 0.0));
+    return 1.0 - saturate(distance / max(blend_distance, 0.0001));
+}
+
+// Returns the direction from the probe position to the point where the given ray from a position inside the box hits the box
+vec3 box_projected_direction(vec3 position, vec3 direction, vec3 box_min, vec3 box_max, vec3 probe_position)
+{
+    vec3 p = clamp(position, box_min, box_max);
+    vec3 first_plane = (box_max - p) / direction;
+    vec3 second_plane = (box_min - p) / direction;
+    vec3 furthest_plane = max(first_plane, second_plane);
+    float distance = min(furthest_plane.x, min(furthest_plane.y, furthest_plane.z));
+    return p + direction * distance - probe_position;
+}
+
+// The Henyey-Greenstein phase function, ie. the fraction of light scattered by a medium with the given anisotropy at an angle with the given cosine
+float henyey_greenstein(float cos_theta, float anisotropy)
+{
+    float g2 = anisotropy * anisotropy;
+    return (1.0 - g2) / (4.0 * PI * pow(max(1.0 + g2 - 2.0 * anisotropy * cos_theta, 0.0001), 1.5));
+}
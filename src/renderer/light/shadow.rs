@@ -0,0 +1,470 @@
+use crate::core::*;
+use crate::renderer::*;
+
+/// The maximum number of samples used by the [ShadowFilter::PoissonPcf] and [ShadowFilter::Pcss] filters.
+pub const MAX_SHADOW_SAMPLES: u32 = 16;
+
+/// The maximum kernel size used by the [ShadowFilter::Pcf] filter, which then tests 49 texels.
+pub const MAX_PCF_KERNEL_SIZE: u32 = 7;
+
+///
+/// The filter used when looking up in a shadow map, which determines how soft the edges of the shadows are.
+/// All filters are more expensive to evaluate than [ShadowFilter::Hard], the price increasing with the number of samples.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadowFilter {
+    /// A single lookup in the shadow map, which gives hard and pixelated shadow edges.
+    Hard,
+    /// Percentage closer filtering (PCF) which tests a grid of `kernel_size` x `kernel_size` texels around the lookup position.
+    Pcf {
+        /// The width and height of the grid of tested texels, at most [MAX_PCF_KERNEL_SIZE].
+        kernel_size: u32,
+    },
+    /// Percentage closer filtering (PCF) which tests `sample_count` positions distributed in a Poisson disk around the lookup position.
+    /// This gives smoother edges than [ShadowFilter::Pcf] with the same number of samples.
+    PoissonPcf {
+        /// The number of samples, at most [MAX_SHADOW_SAMPLES].
+        sample_count: u32,
+        /// The radius of the Poisson disk in texels.
+        radius: f32,
+    },
+    /// Percentage closer soft shadows (PCSS) which gives contact hardening shadows,
+    /// ie. the shadows are sharp close to the shadow caster and become softer further away.
+    Pcss {
+        /// The number of samples used both when searching for shadow casters and when filtering, at most [MAX_SHADOW_SAMPLES].
+        sample_count: u32,
+        /// The size of the light source in texels. A larger light size gives softer shadows.
+        light_size: f32,
+    },
+}
+
+impl ShadowFilter {
+    pub(in crate::renderer) fn id(&self) -> u8 {
+        match self {
+            Self::Hard => 0,
+            Self::Pcf { .. } => 1,
+            Self::PoissonPcf { .. } => 2,
+            Self::Pcss { .. } => 3,
+        }
+    }
+}
+
+///
+/// Settings for the shadows cast by a light, ie. how the shadow map is filtered and biased.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// The filter used when looking up in the shadow map.
+    pub filter: ShadowFilter,
+    /// A constant bias added to the depth before comparing with the shadow map, which removes shadow acne (self-shadowing artifacts).
+    /// The bias is relative to the depth range of the shadow map and is scaled by the radius of the filter in texels.
+    pub depth_bias: f32,
+    /// A bias added to the depth before comparing with the shadow map which is scaled by the tangent of the angle between the surface normal and the light direction.
+    /// The bias is relative to the depth range of the shadow map, is scaled by the radius of the filter in texels and is only used if it is larger than [ShadowSettings::depth_bias].
+    pub slope_bias: f32,
+    /// The distance in world space to offset the position along the surface normal before looking up in the shadow map.
+    /// This removes shadow acne without moving the shadows away from the shadow casters, but makes the shadows thinner.
+    pub normal_bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            filter: ShadowFilter::PoissonPcf {
+                sample_count: 4,
+                radius: 1.0,
+            },
+            depth_bias: 0.005,
+            slope_bias: 0.02,
+            normal_bias: 0.0,
+        }
+    }
+}
+
+impl ShadowSettings {
+    ///
+    /// Returns the shader source defining the function `float shadow_filter{i}(float depth, float bias)`
+    /// which returns the visibility of a position with the given depth.
+    /// The depth bias is scaled by the radius of the filter, since the filter compares with depths further away from the lookup position.
+    /// It requires that the function `float shadow_depth{i}(vec2 texel_offset)` returning the depth stored in the shadow map
+    /// at the given offset from the lookup position is defined.
+    ///
+    pub(in crate::renderer) fn fragment_shader_source(&self, i: u32) -> String {
+        let body = match self.filter {
+            ShadowFilter::Hard => "return shadow_compare(shadow_depth{i}(vec2(0.0)), depth - bias);",
+            ShadowFilter::Pcf { .. } => "
+                int kernel_size = int(shadowFilter{i}.x);
+                float half_size = 0.5 * float(kernel_size - 1);
+                float reference = depth - bias * max(half_size, 1.0);
+                float visibility = 0.0;
+                for (int x = 0; x < kernel_size; x++) {
+                    for (int y = 0; y < kernel_size; y++) {
+                        visibility += shadow_compare(shadow_depth{i}(vec2(float(x), float(y)) - half_size), reference);
+                    }
+                }
+                return visibility / float(kernel_size * kernel_size);",
+            ShadowFilter::PoissonPcf { .. } => "
+                int sample_count = int(shadowFilter{i}.x);
+                float reference = depth - bias * max(shadowFilter{i}.y, 1.0);
+                float visibility = 0.0;
+                for (int s = 0; s < sample_count; s++) {
+                    visibility += shadow_compare(shadow_depth{i}(POISSON_DISK[s] * shadowFilter{i}.y), reference);
+                }
+                return visibility / float(sample_count);",
+            ShadowFilter::Pcss { .. } => "
+                int sample_count = int(shadowFilter{i}.x);
+                float light_size = shadowFilter{i}.y;
+
+                // Find the average depth of the shadow casters, self-shadowing only results in a small filter radius
+                float reference = depth - bias;
+                float blocker_depth = 0.0;
+                int blocker_count = 0;
+                for (int s = 0; s < sample_count; s++) {
+                    float d = shadow_depth{i}(POISSON_DISK[s] * light_size);
+                    if (d < reference) {
+                        blocker_depth += d;
+                        blocker_count++;
+                    }
+                }
+                if (blocker_count == 0) {
+                    return 1.0;
+                }
+                blocker_depth /= float(blocker_count);
+
+                // Filter with a radius proportional to the distance between the shadow caster and the receiver
+                float radius = clamp(light_size * (depth - blocker_depth) / max(blocker_depth, 0.0001), 1.0, light_size);
+                reference = depth - bias * radius;
+                float visibility = 0.0;
+                for (int s = 0; s < sample_count; s++) {
+                    visibility += shadow_compare(shadow_depth{i}(POISSON_DISK[s] * radius), reference);
+                }
+                return visibility / float(sample_count);",
+        };
+        format!(
+            "
+                uniform vec3 shadowBias{{i}};
+                uniform vec2 shadowFilter{{i}};
+
+                float shadow_filter{{i}}(float depth, float bias)
+                {{
+                    {}
+                }}
+            ",
+            body
+        )
+        .replace("{i}", &i.to_string())
+    }
+
+    pub(in crate::renderer) fn use_uniforms(&self, program: &Program, i: u32) {
+        program.use_uniform(
+            &format!("shadowBias{}", i),
+            vec3(self.depth_bias, self.slope_bias, self.normal_bias),
+        );
+        let parameters = match self.filter {
+            ShadowFilter::Hard => vec2(0.0, 0.0),
+            ShadowFilter::Pcf { kernel_size } => {
+                vec2(kernel_size.clamp(1, MAX_PCF_KERNEL_SIZE) as f32, 0.0)
+            }
+            ShadowFilter::PoissonPcf {
+                sample_count,
+                radius,
+            } => vec2(sample_count.clamp(1, MAX_SHADOW_SAMPLES) as f32, radius),
+            ShadowFilter::Pcss {
+                sample_count,
+                light_size,
+            } => vec2(sample_count.clamp(1, MAX_SHADOW_SAMPLES) as f32, light_size),
+        };
+        program.use_uniform_if_required(&format!("shadowFilter{}", i), parameters);
+    }
+}
+
+///
+/// Returns the shader source for looking up in a [DepthTexture2D] shadow map.
+/// Defines the function `float calculate_shadow{i}(vec3 light_direction, vec3 normal, vec3 position)`.
+///
+pub(super) fn shadow_map_2d_shader_source(settings: &ShadowSettings, i: u32) -> String {
+    format!(
+        "
+            uniform sampler2D shadowMap{{i}};
+            uniform mat4 shadowMVP{{i}};
+            vec2 shadow_uv{{i}};
+
+            float shadow_depth{{i}}(vec2 texel_offset)
+            {{
+                vec2 uv = shadow_uv{{i}} + texel_offset / vec2(textureSize(shadowMap{{i}}, 0));
+                if(uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {{
+                    return 1.0;
+                }}
+                return texture(shadowMap{{i}}, uv).x;
+            }}
+
+            {}
+
+            float calculate_shadow{{i}}(vec3 light_direction, vec3 normal, vec3 position)
+            {{
+                vec3 coords = shadow_coordinates(shadowMVP{{i}}, shadowBias{{i}}.z, normal, position);
+                shadow_uv{{i}} = coords.xy;
+                return shadow_filter{{i}}(coords.z, shadow_depth_bias(shadowBias{{i}}, light_direction, normal));
+            }}
+        ",
+        settings.fragment_shader_source(i)
+    )
+    .replace("{i}", &i.to_string())
+}
+
+///
+/// Returns the shader source for looking up in a [DepthTexture2DArray] shadow map where each layer contains a cascade.
+/// Defines the function `float calculate_shadow{i}(vec3 light_direction, vec3 normal, vec3 position)`.
+///
+pub(super) fn cascaded_shadow_map_shader_source(
+    settings: &ShadowSettings,
+    i: u32,
+    max_cascades: u32,
+) -> String {
+    format!(
+        "
+            uniform sampler2DArray shadowMap{{i}};
+            uniform mat4 shadowMVP{{i}}[{}];
+            uniform vec4 cascadeSplits{{i}};
+            uniform vec4 cascadeBiasScales{{i}};
+            uniform int cascadeCount{{i}};
+            uniform vec3 cascadeViewPosition{{i}};
+            uniform vec3 cascadeViewDirection{{i}};
+            vec2 shadow_uv{{i}};
+            int shadow_layer{{i}};
+
+            float shadow_depth{{i}}(vec2 texel_offset)
+            {{
+                vec2 uv = shadow_uv{{i}} + texel_offset / vec2(textureSize(shadowMap{{i}}, 0).xy);
+                if(uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {{
+                    return 1.0;
+                }}
+                return texture(shadowMap{{i}}, vec3(uv, float(shadow_layer{{i}}))).x;
+            }}
+
+            {}
+
+            float calculate_cascade_shadow{{i}}(int cascade, vec3 light_direction, vec3 normal, vec3 position)
+            {{
+                vec3 coords = shadow_coordinates(shadowMVP{{i}}[cascade], shadowBias{{i}}.z, normal, position);
+                shadow_uv{{i}} = coords.xy;
+                shadow_layer{{i}} = cascade;
+                float bias = cascadeBiasScales{{i}}[cascade] * shadow_depth_bias(shadowBias{{i}}, light_direction, normal);
+                return shadow_filter{{i}}(coords.z, bias);
+            }}
+
+            float calculate_shadow{{i}}(vec3 light_direction, vec3 normal, vec3 position)
+            {{
+                float view_depth = dot(position - cascadeViewPosition{{i}}, cascadeViewDirection{{i}});
+                int cascade = shadow_cascade(cascadeSplits{{i}}, cascadeCount{{i}}, view_depth);
+                if (cascade == cascadeCount{{i}}) {{
+                    return 1.0;
+                }}
+                float visibility = calculate_cascade_shadow{{i}}(cascade, light_direction, normal, position);
+                float blend = shadow_cascade_blend(cascadeSplits{{i}}, cascadeCount{{i}}, cascade, view_depth);
+                if (blend > 0.0) {{
+                    visibility = mix(visibility, calculate_cascade_shadow{{i}}(cascade + 1, light_direction, normal, position), blend);
+                }}
+                return visibility;
+            }}
+        ",
+        max_cascades,
+        settings.fragment_shader_source(i)
+    )
+    .replace("{i}", &i.to_string())
+}
+
+///
+/// Returns the shader source for looking up in a [DepthTextureCubeMap] shadow map rendered from the light position `position{i}`.
+/// Defines the function `float calculate_shadow{i}(vec3 light_direction, vec3 normal, vec3 position)`.
+///
+pub(super) fn cube_shadow_map_shader_source(settings: &ShadowSettings, i: u32) -> String {
+    format!(
+        "
+            uniform samplerCube shadowMap{{i}};
+            uniform vec2 shadowNearFar{{i}};
+            vec3 shadow_direction{{i}};
+
+            float shadow_depth{{i}}(vec2 texel_offset)
+            {{
+                vec3 d = shadow_direction{{i}};
+                vec3 tangent = normalize(cross(d, abs(d.y) < 0.99 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0)));
+                vec3 bitangent = normalize(cross(d, tangent));
+                float texel_size = 2.0 / float(textureSize(shadowMap{{i}}, 0).x);
+                return texture(shadowMap{{i}}, d + (tangent * texel_offset.x + bitangent * texel_offset.y) * texel_size).x;
+            }}
+
+            {}
+
+            float calculate_shadow{{i}}(vec3 light_direction, vec3 normal, vec3 position)
+            {{
+                vec3 d = position + shadowBias{{i}}.z * normal - position{{i}};
+                vec3 a = abs(d);
+                shadow_direction{{i}} = d / max(a.x, max(a.y, a.z));
+                return shadow_filter{{i}}(cube_shadow_depth(shadowNearFar{{i}}, d), shadow_depth_bias(shadowBias{{i}}, light_direction, normal));
+            }}
+        ",
+        settings.fragment_shader_source(i)
+    )
+    .replace("{i}", &i.to_string())
+}
+
+///
+/// A shadow map containing only the static shadow casters of a light together with the camera used to render it.
+/// The shadow map is reused when generating a cached shadow map as long as the key is unchanged and all shadow casters are inside the bounds covered by the shadow map.
+///
+pub(super) struct StaticShadowMap {
+    texture: DepthTexture2D,
+    camera: Camera,
+    bounds: AxisAlignedBoundingBox,
+    key: Vec<f32>,
+}
+
+///
+/// Returns a key which changes when the given light parameters, texture size or the number, bounding boxes, alpha masks or vertex deformations of the given geometries changes.
+/// Only the cutout and alpha value of an alpha mask and the shader source of a vertex deformation are part of the key,
+/// so changing the alpha mask texture or the uniforms or textures of a vertex deformation does not change the key.
+///
+pub(super) fn static_shadow_map_key(
+    light_parameters: &[f32],
+    texture_size: u32,
+    geometries: impl IntoIterator<Item = impl Geometry>,
+) -> Vec<f32> {
+    let mut key = light_parameters.to_vec();
+    key.push(texture_size as f32);
+    for geometry in geometries {
+        let aabb = geometry.aabb();
+        key.extend([aabb.min().x, aabb.min().y, aabb.min().z]);
+        key.extend([aabb.max().x, aabb.max().y, aabb.max().z]);
+        match geometry.alpha_mask() {
+            Some(alpha_mask) => key.extend([
+                1.0,
+                alpha_mask.cutout,
+                alpha_mask.alpha,
+                alpha_mask.texture.is_some() as u8 as f32,
+            ]),
+            None => key.push(0.0),
+        }
+        match geometry.vertex_deformation() {
+            // The id is split into parts which can be represented exactly by a f32
+            Some(vertex_deformation) => {
+                key.push(1.0);
+                key.extend((0..4).map(|i| (vertex_deformation.id() >> (16 * i)) as u16 as f32));
+            }
+            None => key.push(0.0),
+        }
+    }
+    key
+}
+
+///
+/// Generates a shadow map by copying the static shadow map and rendering the dynamic shadow casters on top.
+/// The shadow map is written to the given shadow texture, which is only allocated again if it does not exist or if the size has changed.
+/// The static shadow map is only rendered again, using the camera returned by `shadow_camera` for the bounds of all shadow casters,
+/// if it does not exist, the key has changed or a shadow caster has moved outside the bounds covered by the static shadow map.
+/// Returns the camera used to render the shadow map or `None` if there are no shadow casters, in which case the shadow texture is left unchanged.
+///
+pub(super) fn generate_cached_shadow_map(
+    context: &Context,
+    shadow_texture: &mut Option<DepthTexture2D>,
+    static_shadow_map: &mut Option<StaticShadowMap>,
+    key: Vec<f32>,
+    static_geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+    dynamic_geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+    shadow_camera: impl FnOnce(AxisAlignedBoundingBox) -> Option<Camera>,
+) -> Option<Camera> {
+    let mut bounds = AxisAlignedBoundingBox::EMPTY;
+    for geometry in static_geometries.clone() {
+        bounds.expand_with_aabb(&geometry.aabb());
+    }
+    for geometry in dynamic_geometries.clone() {
+        bounds.expand_with_aabb(&geometry.aabb());
+    }
+    if bounds.is_empty() {
+        return None;
+    }
+
+    let is_valid = static_shadow_map.as_ref().is_some_and(|s| {
+        s.key == key
+            && (0..3).all(|i| {
+                s.bounds.min()[i] <= bounds.min()[i] && s.bounds.max()[i] >= bounds.max()[i]
+            })
+    });
+    if !is_valid {
+        let camera = shadow_camera(bounds)?;
+        let viewport = camera.viewport();
+        let mut texture = DepthTexture2D::new::<f32>(
+            context,
+            viewport.width,
+            viewport.height,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        );
+        texture.as_depth_target().clear(ClearState::default());
+        render_shadow_casters(context, &mut texture, &camera, static_geometries);
+        *static_shadow_map = Some(StaticShadowMap {
+            texture,
+            camera,
+            bounds,
+            key,
+        });
+    }
+    let static_shadow_map = static_shadow_map.as_ref().unwrap();
+
+    let camera = static_shadow_map.camera.clone();
+    let viewport = camera.viewport();
+    if shadow_texture
+        .as_ref()
+        .is_none_or(|t| t.width() != viewport.width || t.height() != viewport.height)
+    {
+        *shadow_texture = Some(DepthTexture2D::new::<f32>(
+            context,
+            viewport.width,
+            viewport.height,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        ));
+    }
+    let shadow_texture = shadow_texture.as_mut().unwrap();
+    shadow_texture.as_depth_target().apply_screen_effect(
+        &CopyEffect {
+            write_mask: WriteMask::DEPTH,
+            ..Default::default()
+        },
+        &camera,
+        &[],
+        None,
+        Some(DepthTexture::Single(&static_shadow_map.texture)),
+    );
+    render_shadow_casters(context, shadow_texture, &camera, dynamic_geometries);
+    Some(camera)
+}
+
+fn render_shadow_casters(
+    context: &Context,
+    texture: &mut DepthTexture2D,
+    shadow_camera: &Camera,
+    geometries: impl IntoIterator<Item = impl Geometry>,
+) {
+    let mut depth_material = DepthMaterial {
+        render_states: RenderStates {
+            write_mask: WriteMask::DEPTH,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    texture
+        .as_depth_target()
+        .write::<RendererError>(|| {
+            for geometry in geometries
+                .into_iter()
+                .filter(|g| shadow_camera.in_frustum(&g.aabb()))
+            {
+                depth_material.alpha_mask = geometry.alpha_mask();
+                depth_material.vertex_deformation = geometry.vertex_deformation();
+                render_with_material(context, shadow_camera, &geometry, &depth_material, &[]);
+            }
+            Ok(())
+        })
+        .unwrap();
+}
@@ -0,0 +1,342 @@
+use crate::core::*;
+use crate::renderer::*;
+
+/// The width and height of each side of the cube map when it is resampled before projecting it to spherical harmonics.
+const SAMPLE_SIZE: u32 = 32;
+
+///
+/// The light arriving at a point from all directions represented by the first three bands (L0, L1 and L2) of real spherical harmonics,
+/// which is a compact representation of low frequency lighting, for example the diffuse indirect light in a room.
+/// The nine coefficients are ordered by band and within each band from `m = -l` to `m = l`.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SphericalHarmonics {
+    /// The nine coefficients of the projection of the incoming radiance onto the spherical harmonics basis functions, one for each color channel.
+    pub coefficients: [Vec3; 9],
+}
+
+impl SphericalHarmonics {
+    ///
+    /// Projects the radiance stored in the given cube map onto spherical harmonics.
+    /// The colors in the cube map are assumed to be linear, ie. not in sRGB color space.
+    ///
+    pub fn from_cube_map(context: &Context, cube_map: &TextureCubeMap) -> Self {
+        let mut texture = Texture2D::new_empty::<[f32; 4]>(
+            context,
+            SAMPLE_SIZE,
+            SAMPLE_SIZE,
+            Interpolation::Nearest,
+            Interpolation::Nearest,
+            None,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        );
+        let viewport = Viewport::new_at_origo(SAMPLE_SIZE, SAMPLE_SIZE);
+        Self::project(
+            CubeMapSide::iter().map(|side| {
+                let pixels = texture
+                    .as_color_target(None)
+                    .clear(ClearState::default())
+                    .apply_screen_material(
+                        &CubeMapSampleMaterial { cube_map, side },
+                        &Camera::new_2d(viewport),
+                        &[],
+                    )
+                    .read::<[f32; 4]>();
+                (side, pixels)
+            }),
+            SAMPLE_SIZE,
+        )
+    }
+
+    ///
+    /// Projects the radiance given by the pixels of the sides of a cube map with the given size onto spherical harmonics.
+    /// The first row of pixels of each side is the top row.
+    ///
+    fn project(sides: impl IntoIterator<Item = (CubeMapSide, Vec<[f32; 4]>)>, size: u32) -> Self {
+        let mut coefficients = [Vec3::zero(); 9];
+        let mut total_weight = 0.0;
+        for (side, pixels) in sides {
+            let right = side.direction().cross(side.up());
+            for (i, pixel) in pixels.iter().enumerate() {
+                // The first row read from the render target is the top row, ie. v = 1
+                let u = 2.0 * ((i as u32 % size) as f32 + 0.5) / size as f32 - 1.0;
+                let v = 1.0 - 2.0 * ((i as u32 / size) as f32 + 0.5) / size as f32;
+                let direction = side.direction() + right * u + side.up() * v;
+                // The solid angle covered by the texel relative to the other texels
+                let weight = 1.0 / direction.magnitude2().powf(1.5);
+                let radiance = vec3(pixel[0], pixel[1], pixel[2]);
+                for (coefficient, basis) in coefficients
+                    .iter_mut()
+                    .zip(basis_functions(direction.normalize()))
+                {
+                    *coefficient += radiance * basis * weight;
+                }
+                total_weight += weight;
+            }
+        }
+        let scale = 4.0 * std::f32::consts::PI / total_weight;
+        Self {
+            coefficients: coefficients.map(|c| c * scale),
+        }
+    }
+
+    ///
+    /// Captures the light arriving at the given position by rendering the objects with the given lights into a cube map with the given texture size,
+    /// see [capture_cube_map], and then projecting the cube map onto spherical harmonics.
+    ///
+    pub fn capture(
+        context: &Context,
+        position: Vec3,
+        texture_size: u32,
+        objects: impl IntoIterator<Item = impl Object> + Clone,
+        lights: &[&dyn Light],
+    ) -> Self {
+        Self::from_cube_map(
+            context,
+            &capture_cube_map(context, position, texture_size, objects, lights),
+        )
+    }
+
+    ///
+    /// Returns the irradiance divided by pi arriving at a surface with the given normal,
+    /// ie. the color reflected by a white diffuse surface with the given normal.
+    ///
+    pub fn irradiance(&self, normal: Vec3) -> Vec3 {
+        // The cosine lobe convolution constants (pi, 2pi/3 and pi/4) divided by pi
+        const BAND_SCALES: [f32; 9] = [
+            1.0,
+            2.0 / 3.0,
+            2.0 / 3.0,
+            2.0 / 3.0,
+            0.25,
+            0.25,
+            0.25,
+            0.25,
+            0.25,
+        ];
+        self.coefficients
+            .iter()
+            .zip(basis_functions(normal.normalize()))
+            .zip(BAND_SCALES)
+            .fold(Vec3::zero(), |sum, ((c, basis), scale)| {
+                sum + c * basis * scale
+            })
+    }
+}
+
+///
+/// Returns the values of the nine spherical harmonics basis functions in the given direction.
+///
+fn basis_functions(d: Vec3) -> [f32; 9] {
+    [
+        0.282095,
+        0.488603 * d.y,
+        0.488603 * d.z,
+        0.488603 * d.x,
+        1.092548 * d.x * d.y,
+        1.092548 * d.y * d.z,
+        0.315392 * (3.0 * d.z * d.z - 1.0),
+        1.092548 * d.x * d.z,
+        0.546274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+///
+/// Renders the objects with the given lights into a cube map seen from the given position, where each side of the cube map has the given texture size.
+/// The colors in the cube map are linear and not tone mapped.
+///
+pub fn capture_cube_map(
+    context: &Context,
+    position: Vec3,
+    texture_size: u32,
+    objects: impl IntoIterator<Item = impl Object> + Clone,
+    lights: &[&dyn Light],
+) -> TextureCubeMap {
+    let mut z_far = 0.0f32;
+    for object in objects.clone() {
+        let aabb = object.aabb();
+        if !aabb.is_empty() && !aabb.is_infinite() {
+            z_far = z_far.max(aabb.distance_max(&position));
+        }
+    }
+    let z_far = if z_far > 0.0 { z_far } else { 1.0 };
+
+    let mut cube_map = TextureCubeMap::new_empty::<[f16; 4]>(
+        context,
+        texture_size,
+        texture_size,
+        Interpolation::Linear,
+        Interpolation::Linear,
+        Some(Interpolation::Linear),
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    );
+    let mut depth_texture = DepthTexture2D::new::<f32>(
+        context,
+        texture_size,
+        texture_size,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    );
+    let viewport = Viewport::new_at_origo(texture_size, texture_size);
+    for side in CubeMapSide::iter() {
+        let mut camera = Camera::new_perspective(
+            viewport,
+            position,
+            position + side.direction(),
+            side.up(),
+            degrees(90.0),
+            0.01,
+            z_far,
+        );
+        camera.disable_tone_and_color_mapping();
+        let sides = [side];
+        RenderTarget::new(
+            cube_map.as_color_target(&sides, None),
+            depth_texture.as_depth_target(),
+        )
+        .clear(ClearState::color_and_depth(0.0, 0.0, 0.0, 1.0, 1.0))
+        .render(&camera, objects.clone(), lights);
+    }
+    cube_map
+}
+
+struct CubeMapSampleMaterial<'a> {
+    cube_map: &'a TextureCubeMap,
+    side: CubeMapSide,
+}
+
+impl Material for CubeMapSampleMaterial<'_> {
+    fn fragment_shader_source(&self, _lights: &[&dyn Light]) -> String {
+        "
+            uniform samplerCube cubeMap;
+            uniform vec3 direction;
+            uniform vec3 up;
+
+            in vec2 uvs;
+
+            layout (location = 0) out vec4 outColor;
+
+            void main()
+            {
+                vec3 right = cross(direction, up);
+                vec3 N = normalize(up * (uvs.y - 0.5) * 2.0 + right * (uvs.x - 0.5) * 2.0 + direction);
+                outColor = vec4(texture(cubeMap, N).rgb, 1.0);
+            }
+        "
+        .to_string()
+    }
+
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::CubeMapSampleMaterial
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            uv: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, _camera: &Camera, _lights: &[&dyn Light]) {
+        program.use_texture_cube("cubeMap", self.cube_map);
+        program.use_uniform("direction", self.side.direction());
+        program.use_uniform("up", self.side.up());
+    }
+
+    fn render_states(&self) -> RenderStates {
+        RenderStates::default()
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Opaque
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 32;
+
+    fn project(radiance: impl Fn(CubeMapSide, Vec3) -> Vec3) -> SphericalHarmonics {
+        SphericalHarmonics::project(
+            CubeMapSide::iter().map(|side| {
+                let right = side.direction().cross(side.up());
+                let pixels = (0..SIZE * SIZE)
+                    .map(|i| {
+                        let u = 2.0 * ((i % SIZE) as f32 + 0.5) / SIZE as f32 - 1.0;
+                        let v = 1.0 - 2.0 * ((i / SIZE) as f32 + 0.5) / SIZE as f32;
+                        let direction = (side.direction() + right * u + side.up() * v).normalize();
+                        radiance(side, direction).extend(1.0).into()
+                    })
+                    .collect();
+                (side, pixels)
+            }),
+            SIZE,
+        )
+    }
+
+    fn assert_approx_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).magnitude() < 1.0e-2, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn project_constant_radiance() {
+        let color = vec3(0.5, 1.0, 2.0);
+        let sh = project(|_, _| color);
+        assert_approx_eq(
+            sh.coefficients[0],
+            color * 4.0 * std::f32::consts::PI * 0.282095,
+        );
+        for coefficient in &sh.coefficients[1..] {
+            assert_approx_eq(*coefficient, Vec3::zero());
+        }
+        for normal in [
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+            vec3(1.0, 1.0, 1.0),
+        ] {
+            assert_approx_eq(sh.irradiance(normal), color);
+        }
+    }
+
+    #[test]
+    fn project_linear_radiance() {
+        // The irradiance divided by pi from a radiance of a + b * dot(d, n) is a + 2/3 * b * dot(normal, n)
+        let sh = project(|_, direction| vec3(1.0, 1.0, 1.0) + vec3(0.0, 0.5, 1.0) * direction.y);
+        assert_approx_eq(
+            sh.coefficients[1],
+            vec3(0.0, 0.5, 1.0) * 0.488603 * 4.0 * std::f32::consts::PI / 3.0,
+        );
+        assert_approx_eq(sh.coefficients[2], Vec3::zero());
+        assert_approx_eq(sh.coefficients[3], Vec3::zero());
+        assert_approx_eq(
+            sh.irradiance(vec3(0.0, 1.0, 0.0)),
+            vec3(1.0, 1.0 + 1.0 / 3.0, 1.0 + 2.0 / 3.0),
+        );
+        assert_approx_eq(
+            sh.irradiance(vec3(0.0, -1.0, 0.0)),
+            vec3(1.0, 1.0 - 1.0 / 3.0, 1.0 - 2.0 / 3.0),
+        );
+        assert_approx_eq(sh.irradiance(vec3(1.0, 0.0, 0.0)), vec3(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn project_single_side() {
+        let sh = project(|side, _| {
+            if side == CubeMapSide::Front {
+                vec3(1.0, 1.0, 1.0)
+            } else {
+                Vec3::zero()
+            }
+        });
+        let front = CubeMapSide::Front.direction();
+        assert!(sh.irradiance(front).x > 0.3);
+        assert!(sh.irradiance(-front).x.abs() < 0.05);
+        let side = CubeMapSide::Top.direction();
+        assert!((sh.irradiance(side).x - sh.irradiance(-side).x).abs() < 1.0e-3);
+    }
+}
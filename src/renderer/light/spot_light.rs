@@ -10,6 +10,7 @@ pub struct SpotLight {
     context: Context,
     shadow_texture: Option<DepthTexture2D>,
     shadow_matrix: Mat4,
+    static_shadow_map: Option<StaticShadowMap>,
     /// The intensity of the light. This allows for higher intensity than 1 which can be used to simulate high intensity light sources like the sun.
     pub intensity: f32,
     /// The base color of the light.
@@ -22,6 +23,16 @@ pub struct SpotLight {
     pub cutoff: Radians,
     /// The [Attenuation] of the light.
     pub attenuation: Attenuation,
+    /// The [ShadowSettings] used when the light casts shadows.
+    pub shadow_settings: ShadowSettings,
+    /// An optional lookup texture created from an [IesProfile] using [IesProfile::lookup_texture], which shapes the intensity of the light depending on the direction.
+    /// The horizontal angle of the profile is measured from the direction given by [SpotLight::projection_up] around the direction of the light.
+    /// The light is still limited by the cutoff angle, so the cutoff should be large enough to contain the profile.
+    /// The transformation of the texture is not used, since the texture is looked up using the angles of the profile.
+    pub ies_profile: Option<Texture2DRef>,
+    /// An optional texture, often called a cookie, which is projected from the light in the direction of the light and modulates the color of the light.
+    /// The texture covers the square containing the cone given by the cutoff angle and the top of the texture points in the direction given by [SpotLight::projection_up].
+    pub cookie: Option<Texture2DRef>,
 }
 
 impl SpotLight {
@@ -38,6 +49,7 @@ impl SpotLight {
         SpotLight {
             context: context.clone(),
             shadow_texture: None,
+            static_shadow_map: None,
             intensity,
             color,
             position: *position,
@@ -45,6 +57,9 @@ impl SpotLight {
             cutoff: cutoff.into(),
             attenuation,
             shadow_matrix: Mat4::identity(),
+            shadow_settings: ShadowSettings::default(),
+            ies_profile: None,
+            cookie: None,
         }
     }
 
@@ -55,6 +70,7 @@ impl SpotLight {
     pub fn clear_shadow_map(&mut self) {
         self.shadow_texture = None;
         self.shadow_matrix = Mat4::identity();
+        self.static_shadow_map = None;
     }
 
     ///
@@ -67,31 +83,13 @@ impl SpotLight {
         texture_size: u32,
         geometries: impl IntoIterator<Item = impl Geometry> + Clone,
     ) {
-        let position = self.position;
-        let direction = self.direction;
-        let up = compute_up_direction(self.direction);
-
-        let viewport = Viewport::new_at_origo(texture_size, texture_size);
-
-        let mut z_far = 0.0f32;
-        let mut z_near = f32::MAX;
-        for geometry in geometries.clone() {
-            let aabb = geometry.aabb();
-            if !aabb.is_empty() {
-                z_far = z_far.max(aabb.distance_max(&self.position));
-                z_near = z_near.min(aabb.distance(&self.position));
-            }
-        }
-
-        let shadow_camera = Camera::new_perspective(
-            viewport,
-            position,
-            position + direction,
-            up,
-            self.cutoff,
-            z_near.max(0.01),
-            z_far,
-        );
+        let shadow_camera = match self.shadow_camera(
+            texture_size,
+            geometries.clone().into_iter().map(|g| g.aabb()),
+        ) {
+            Some(camera) => camera,
+            None => return,
+        };
         self.shadow_matrix = shadow_matrix(&shadow_camera);
 
         let mut shadow_texture = DepthTexture2D::new::<f32>(
@@ -101,7 +99,7 @@ impl SpotLight {
             Wrapping::ClampToEdge,
             Wrapping::ClampToEdge,
         );
-        let depth_material = DepthMaterial {
+        let mut depth_material = DepthMaterial {
             render_states: RenderStates {
                 write_mask: WriteMask::DEPTH,
                 ..Default::default()
@@ -116,6 +114,8 @@ impl SpotLight {
                     .into_iter()
                     .filter(|g| shadow_camera.in_frustum(&g.aabb()))
                 {
+                    depth_material.alpha_mask = geometry.alpha_mask();
+                    depth_material.vertex_deformation = geometry.vertex_deformation();
                     render_with_material(
                         &self.context,
                         &shadow_camera,
@@ -130,80 +130,245 @@ impl SpotLight {
         self.shadow_texture = Some(shadow_texture);
     }
 
+    ///
+    /// Generate a shadow map like [SpotLight::generate_shadow_map], except that the shadow casters are split into static and dynamic geometries.
+    /// The static geometries are rendered into a separate shadow map which is cached and reused in subsequent calls,
+    /// so only the dynamic geometries are rendered each time this method is called.
+    /// The cached shadow map is rendered again when the position, direction or cutoff of the light, the texture size or the static geometries change,
+    /// where a static geometry is considered changed if the number of static geometries, their bounding boxes, the cutout or alpha value of their alpha masks or the shader source of their vertex deformations have changed.
+    /// Use [SpotLight::invalidate_static_shadow_map] to force the cached shadow map to be rendered again, for example if a static geometry is modified without changing its bounding box
+    /// or if the alpha mask texture or the uniforms or textures of a vertex deformation of a static geometry have changed.
+    ///
+    /// The shadow map is fitted to the bounding box of all of the geometries when the cached shadow map is rendered,
+    /// so the cached shadow map is also rendered again when a dynamic geometry moves outside of that bounding box.
+    ///
+    pub fn generate_cached_shadow_map(
+        &mut self,
+        texture_size: u32,
+        static_geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+        dynamic_geometries: impl IntoIterator<Item = impl Geometry> + Clone,
+    ) {
+        let key = static_shadow_map_key(
+            &[
+                self.position.x,
+                self.position.y,
+                self.position.z,
+                self.direction.x,
+                self.direction.y,
+                self.direction.z,
+                self.cutoff.0,
+            ],
+            texture_size,
+            static_geometries.clone(),
+        );
+        let mut static_shadow_map = self.static_shadow_map.take();
+        let mut shadow_texture = self.shadow_texture.take();
+        let result = generate_cached_shadow_map(
+            &self.context,
+            &mut shadow_texture,
+            &mut static_shadow_map,
+            key,
+            static_geometries,
+            dynamic_geometries,
+            |bounds| self.shadow_camera(texture_size, [bounds]),
+        );
+        self.static_shadow_map = static_shadow_map;
+        self.shadow_texture = shadow_texture;
+        if let Some(shadow_camera) = result {
+            self.shadow_matrix = shadow_matrix(&shadow_camera);
+        }
+    }
+
+    ///
+    /// Discards the cached shadow map containing the static geometries, which forces it to be rendered again the next time [SpotLight::generate_cached_shadow_map] is called.
+    ///
+    pub fn invalidate_static_shadow_map(&mut self) {
+        self.static_shadow_map = None;
+    }
+
+    ///
+    /// Returns a perspective camera which views the given bounding boxes from the position of the light or `None` if all of the bounding boxes are empty.
+    ///
+    fn shadow_camera(
+        &self,
+        texture_size: u32,
+        aabbs: impl IntoIterator<Item = AxisAlignedBoundingBox>,
+    ) -> Option<Camera> {
+        let position = self.position;
+        let direction = self.direction;
+        let up = compute_up_direction(self.direction);
+
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
+
+        let mut z_far = 0.0f32;
+        let mut z_near = f32::MAX;
+        for aabb in aabbs {
+            if !aabb.is_empty() {
+                z_far = z_far.max(aabb.distance_max(&self.position));
+                z_near = z_near.min(aabb.distance(&self.position));
+            }
+        }
+
+        if z_far == 0.0 {
+            return None;
+        }
+        Some(Camera::new_perspective(
+            viewport,
+            position,
+            position + direction,
+            up,
+            self.cutoff,
+            z_near.max(0.01),
+            z_far,
+        ))
+    }
+
+    ///
+    /// Returns the direction perpendicular to the light direction which is used as the up direction when projecting the [SpotLight::cookie]
+    /// and as the direction of the 0 degree horizontal angle of the [SpotLight::ies_profile].
+    ///
+    pub fn projection_up(&self) -> Vec3 {
+        compute_up_direction(self.direction)
+    }
+
     ///
     /// Returns a reference to the shadow map if it has been generated.
     ///
     pub fn shadow_map(&self) -> Option<&DepthTexture2D> {
         self.shadow_texture.as_ref()
     }
+
+    ///
+    /// Returns the distance from the light where the contribution of the light becomes negligible.
+    /// Surfaces further away than this distance are not lit by this light.
+    ///
+    pub fn influence_radius(&self) -> f32 {
+        self.attenuation.influence_radius(
+            max_intensity(self.color, self.intensity),
+            LIGHT_INFLUENCE_THRESHOLD,
+        )
+    }
 }
 
-impl Light for SpotLight {
-    fn shader_source(&self, i: u32) -> String {
+impl SpotLight {
+    ///
+    /// Returns the uniforms and functions needed for the shadow, IES profile and cookie,
+    /// the code modulating the `result` of the light by the IES profile and cookie and the expression for the shadow.
+    ///
+    fn modulation_shader_source(&self, i: u32) -> (String, String, Option<String>) {
+        let mut source = String::new();
+        let mut modulation = String::new();
+        let mut shadow = None;
         if self.shadow_texture.is_some() {
-            format!(
+            source.push_str(&shadow_map_2d_shader_source(&self.shadow_settings, i));
+            shadow = Some(format!(
+                "calculate_shadow{i}(light_direction, normal, position)"
+            ));
+        }
+        if self.ies_profile.is_some() {
+            source.push_str(&format!(
+                "
+                    uniform sampler2D iesProfile{i};
+                    uniform vec3 projectionUp{i};
+                "
+            ));
+            modulation.push_str(&format!(
                 "
-                    uniform sampler2D shadowMap{};
-                    uniform mat4 shadowMVP{};
-
-                    uniform vec3 color{};
-                    uniform vec3 attenuation{};
-                    uniform vec3 position{};
-                    uniform float cutoff{};
-                    uniform vec3 direction{};
-                    vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
-                    {{
-                        vec3 light_direction = position{} - position;
-                        float distance = length(light_direction);
-                        light_direction = light_direction / distance;
-
-                        float angle = acos(dot(-light_direction, normalize(direction{})));
-                        float cutoff = cutoff{};
-
-                        vec3 result = vec3(0.0);
-                        if (angle < cutoff) {{
-                            vec3 light_color = attenuate(color{}, attenuation{}, distance);
-                            result = calculate_light(light_color, light_direction, surface_color, view_direction, normal,
-                                metallic, roughness) * (1.0 - smoothstep(0.75 * cutoff, cutoff, angle));
-                            result *= calculate_shadow(light_direction, normal, shadowMap{}, shadowMVP{}, position);
-                        }}
-                        return result;
+                    vec3 ies_direction = normalize(direction{i});
+                    result *= ies_intensity(iesProfile{i}, -light_direction, projectionUp{i}, cross(ies_direction, projectionUp{i}), ies_direction);
+                "
+            ));
+        }
+        if self.cookie.is_some() {
+            source.push_str(&format!(
+                "
+                    uniform sampler2D cookieTexture{i};
+                    uniform mat3 cookieTexTransform{i};
+                    uniform mat4 cookieMVP{i};
+                "
+            ));
+            modulation.push_str(&format!(
+                "result *= cookie_color(cookieTexture{i}, cookieTexTransform{i}, cookieMVP{i}, position);"
+            ));
+        }
+        (source, modulation, shadow)
+    }
+}
+
+impl Light for SpotLight {
+    fn shader_source(&self, i: u32) -> String {
+        let (source, modulation, shadow) = self.modulation_shader_source(i);
+        let shadow = shadow
+            .map(|shadow| format!("result *= {shadow};"))
+            .unwrap_or_default();
+        format!(
+            "
+                {source}
+
+                uniform vec3 color{i};
+                uniform vec3 attenuation{i};
+                uniform vec3 position{i};
+                uniform float cutoff{i};
+                uniform vec3 direction{i};
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    vec3 light_direction = position{i} - position;
+                    float distance = length(light_direction);
+                    light_direction = light_direction / distance;
+
+                    float angle = acos(dot(-light_direction, normalize(direction{i})));
+                    float cutoff = cutoff{i};
+
+                    vec3 result = vec3(0.0);
+                    if (angle < cutoff) {{
+                        vec3 light_color = attenuate(color{i}, attenuation{i}, distance);
+                        result = calculate_light(light_color, light_direction, surface_color, view_direction, normal,
+                            metallic, roughness) * (1.0 - smoothstep(0.75 * cutoff, cutoff, angle));
+                        {modulation}
+                        {shadow}
                     }}
+                    return result;
+                }}
 
-                ", i, i, i, i, i, i, i, i, i, i, i, i, i, i, i)
-        } else {
-            format!(
-                "
-                    uniform vec3 color{};
-                    uniform vec3 attenuation{};
-                    uniform vec3 position{};
-                    uniform float cutoff{};
-                    uniform vec3 direction{};
-                    vec3 calculate_lighting{}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
-                    {{
-                        vec3 light_direction = position{} - position;
-                        float distance = length(light_direction);
-                        light_direction = light_direction / distance;
-
-                        float angle = acos(dot(-light_direction, normalize(direction{})));
-                        float cutoff = cutoff{};
-
-                        vec3 result = vec3(0.0);
-                        if (angle < cutoff) {{
-                            vec3 light_color = attenuate(color{}, attenuation{}, distance);
-                            result = calculate_light(light_color, light_direction, surface_color, view_direction, normal,
-                                metallic, roughness) * (1.0 - smoothstep(0.75 * cutoff, cutoff, angle));
-                        }}
-                        return result;
+            "
+        )
+    }
+
+    fn incoming_light_shader_source(&self, i: u32) -> Option<String> {
+        let (_, modulation, shadow) = self.modulation_shader_source(i);
+        let visibility = shadow
+            .map(|shadow| format!("visibility = {shadow};"))
+            .unwrap_or_default();
+        Some(format!(
+            "
+                vec3 calculate_incoming_light{i}(vec3 position, vec3 normal, out vec3 light_direction, out float visibility)
+                {{
+                    light_direction = position{i} - position;
+                    float distance = length(light_direction);
+                    light_direction = light_direction / distance;
+
+                    float angle = acos(dot(-light_direction, normalize(direction{i})));
+                    float cutoff = cutoff{i};
+
+                    vec3 result = vec3(0.0);
+                    visibility = 1.0;
+                    if (angle < cutoff) {{
+                        result = attenuate(color{i}, attenuation{i}, distance) * (1.0 - smoothstep(0.75 * cutoff, cutoff, angle));
+                        {modulation}
+                        {visibility}
                     }}
+                    return result;
+                }}
 
-                ", i, i, i, i, i, i, i, i, i, i, i)
-        }
+            "
+        ))
     }
+
     fn use_uniforms(&self, program: &Program, i: u32) {
         if let Some(ref tex) = self.shadow_texture {
             program.use_depth_texture(&format!("shadowMap{}", i), tex);
             program.use_uniform(&format!("shadowMVP{}", i), self.shadow_matrix);
+            self.shadow_settings.use_uniforms(program, i);
         }
         program.use_uniform(
             &format!("color{}", i),
@@ -220,9 +385,130 @@ impl Light for SpotLight {
         program.use_uniform(&format!("position{}", i), self.position);
         program.use_uniform(&format!("direction{}", i), self.direction.normalize());
         program.use_uniform(&format!("cutoff{}", i), self.cutoff.0);
+        if let Some(ref ies_profile) = self.ies_profile {
+            program.use_texture(&format!("iesProfile{}", i), ies_profile);
+            program.use_uniform(&format!("projectionUp{}", i), self.projection_up());
+        }
+        if let Some(ref cookie) = self.cookie {
+            program.use_texture(&format!("cookieTexture{}", i), cookie);
+            program.use_uniform(&format!("cookieTexTransform{}", i), cookie.transformation);
+            let direction = self.direction.normalize();
+            let projection_camera = Camera::new_perspective(
+                Viewport::new_at_origo(1, 1),
+                self.position,
+                self.position + direction,
+                self.projection_up(),
+                radians((2.0 * self.cutoff.0).min(0.99 * std::f32::consts::PI)),
+                0.01,
+                1.0,
+            );
+            program.use_uniform(
+                &format!("cookieMVP{}", i),
+                shadow_matrix(&projection_camera),
+            );
+        }
+    }
+
+    fn affects(&self, aabb: &AxisAlignedBoundingBox) -> bool {
+        cone_affects(
+            self.position,
+            self.direction,
+            self.cutoff,
+            self.influence_radius(),
+            aabb,
+        )
     }
 
     fn id(&self) -> LightId {
-        LightId::SpotLight(self.shadow_texture.is_some())
+        LightId::SpotLight(
+            self.shadow_texture.is_some(),
+            self.ies_profile.is_some(),
+            self.cookie.is_some(),
+            if self.shadow_texture.is_some() {
+                self.shadow_settings.filter.id()
+            } else {
+                0
+            },
+        )
+    }
+}
+
+///
+/// Returns whether the cone with the apex at the given position, the given direction, cutoff angle and length might intersect the given bounding box.
+///
+fn cone_affects(
+    position: Vec3,
+    direction: Vec3,
+    cutoff: Radians,
+    length: f32,
+    aabb: &AxisAlignedBoundingBox,
+) -> bool {
+    if aabb.is_empty() || aabb.is_infinite() {
+        return true;
+    }
+    if aabb.distance(&position) > length {
+        return false;
+    }
+    // Test the cone against the bounding sphere of the box
+    let radius = 0.5 * aabb.size().magnitude();
+    let to_center = aabb.center() - position;
+    let distance = to_center.magnitude();
+    if distance <= radius {
+        return true;
+    }
+    let angle = to_center.angle(direction.normalize()).0;
+    angle - (radius / distance).asin() < cutoff.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(center: Vec3, size: f32) -> AxisAlignedBoundingBox {
+        let half = vec3(0.5 * size, 0.5 * size, 0.5 * size);
+        AxisAlignedBoundingBox::new_with_positions(&[center - half, center + half])
+    }
+
+    fn affects(aabb: &AxisAlignedBoundingBox) -> bool {
+        cone_affects(
+            vec3(0.0, 0.0, 0.0),
+            vec3(0.0, 0.0, -2.0),
+            degrees(30.0).into(),
+            10.0,
+            aabb,
+        )
+    }
+
+    #[test]
+    fn affects_boxes_inside_the_cone() {
+        assert!(affects(&cube(vec3(0.0, 0.0, -5.0), 1.0)));
+        assert!(affects(&cube(vec3(1.0, -1.0, -8.0), 1.0)));
+    }
+
+    #[test]
+    fn affects_boxes_partially_inside_the_cone() {
+        // The center is outside the cone, but the box overlaps the cone
+        assert!(affects(&cube(vec3(3.0, 0.0, -5.0), 2.0)));
+        // The box contains the apex
+        assert!(affects(&cube(vec3(0.0, 0.0, 0.0), 2.0)));
+    }
+
+    #[test]
+    fn does_not_affect_boxes_outside_the_cone() {
+        assert!(!affects(&cube(vec3(0.0, 0.0, 5.0), 1.0)));
+        assert!(!affects(&cube(vec3(5.0, 0.0, -1.0), 1.0)));
+        assert!(!affects(&cube(vec3(0.0, 6.0, -3.0), 1.0)));
+    }
+
+    #[test]
+    fn does_not_affect_boxes_beyond_the_length() {
+        assert!(!affects(&cube(vec3(0.0, 0.0, -15.0), 1.0)));
+        assert!(affects(&cube(vec3(0.0, 0.0, -10.0), 1.0)));
+    }
+
+    #[test]
+    fn affects_empty_and_infinite_boxes() {
+        assert!(affects(&AxisAlignedBoundingBox::EMPTY));
+        assert!(affects(&AxisAlignedBoundingBox::INFINITE));
     }
 }
@@ -0,0 +1,158 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// The opaque objects seen from a camera, which is used as the light transmitted through objects with a transmissive material, for example a [PhysicalMaterial] with [PhysicalMaterial::transmission],
+/// such that glass and liquids refract and tint the objects behind them.
+/// When used as a light, it does not contribute any light to other materials.
+///
+/// [RenderTarget::render] automatically renders the opaque objects into a transmission background when transmissive objects are present,
+/// so it is only needed when rendering transmissive objects in other ways, for example using [Object::render].
+/// Only one transmission background should be given to a material.
+/// Note that only the given objects are seen through the transmissive objects and not for example the clear color,
+/// so include a background like a [Skybox] in the objects.
+///
+pub struct TransmissionBackground {
+    context: Context,
+    texture: Texture2D,
+    depth_texture: DepthTexture2D,
+    view_projection: Mat4,
+}
+
+impl TransmissionBackground {
+    ///
+    /// Renders the given objects, which should be the opaque objects in the scene, into a new transmission background as seen from the given camera.
+    /// The background contains the light before any tone mapping and color mapping, which are instead applied by the transmissive materials.
+    ///
+    pub fn new(
+        context: &Context,
+        camera: &Camera,
+        objects: &[&dyn Object],
+        lights: &[&dyn Light],
+    ) -> Self {
+        let mut transmission_background = Self::new_empty(context, camera.viewport());
+        transmission_background.update(camera, objects, lights);
+        transmission_background
+    }
+
+    ///
+    /// Constructs a new empty transmission background with the size of the given viewport.
+    ///
+    pub(crate) fn new_empty(context: &Context, viewport: Viewport) -> Self {
+        Self {
+            context: context.clone(),
+            texture: new_texture(context, viewport),
+            depth_texture: new_depth_texture(context, viewport),
+            view_projection: Mat4::identity(),
+        }
+    }
+
+    ///
+    /// Renders the given objects into this transmission background as seen from the given camera, see [TransmissionBackground::new].
+    /// The textures are reused if the size of the camera viewport is unchanged.
+    ///
+    pub fn update(&mut self, camera: &Camera, objects: &[&dyn Object], lights: &[&dyn Light]) {
+        self.update_partially(camera.viewport().into(), camera, objects, lights)
+    }
+
+    ///
+    /// Renders the given objects into the part of this transmission background defined by the scissor box as seen from the given camera, see [TransmissionBackground::new].
+    /// The scissor box is given in the same coordinates as the camera viewport, ie. only the part of the background which is seen inside the scissor box when rendering with the camera is rendered.
+    /// The rest of the background is cleared, so no pixels from a previous update can be seen through the transmissive objects, for example when they refract the light from outside the scissor box.
+    /// The textures are reused if the size of the camera viewport is unchanged.
+    ///
+    pub fn update_partially(
+        &mut self,
+        scissor_box: ScissorBox,
+        camera: &Camera,
+        objects: &[&dyn Object],
+        lights: &[&dyn Light],
+    ) {
+        let viewport = Viewport::new_at_origo(camera.viewport().width, camera.viewport().height);
+        if self.texture.width() != viewport.width || self.texture.height() != viewport.height {
+            self.texture = new_texture(&self.context, viewport);
+            self.depth_texture = new_depth_texture(&self.context, viewport);
+        }
+        let mut background_camera = camera.clone();
+        background_camera.set_viewport(viewport);
+        background_camera.disable_tone_and_color_mapping();
+        // The scissor box is moved into the coordinates of the background textures
+        let scissor_box = ScissorBox {
+            x: scissor_box.x - camera.viewport().x,
+            y: scissor_box.y - camera.viewport().y,
+            ..scissor_box
+        };
+        RenderTarget::new(
+            self.texture.as_color_target(None),
+            self.depth_texture.as_depth_target(),
+        )
+        .clear(ClearState::color_and_depth(0.0, 0.0, 0.0, 1.0, 1.0))
+        .render_partially(scissor_box, &background_camera, objects, lights);
+        self.view_projection = camera.projection() * camera.view();
+    }
+}
+
+fn new_texture(context: &Context, viewport: Viewport) -> Texture2D {
+    // Mip maps are used to blur the background seen through rough materials
+    Texture2D::new_empty::<[f16; 4]>(
+        context,
+        viewport.width,
+        viewport.height,
+        Interpolation::Linear,
+        Interpolation::Linear,
+        Some(Interpolation::Linear),
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    )
+}
+
+fn new_depth_texture(context: &Context, viewport: Viewport) -> DepthTexture2D {
+    DepthTexture2D::new::<f32>(
+        context,
+        viewport.width,
+        viewport.height,
+        Wrapping::ClampToEdge,
+        Wrapping::ClampToEdge,
+    )
+}
+
+impl Light for TransmissionBackground {
+    fn shader_source(&self, i: u32) -> String {
+        format!(
+            "
+                #define USE_TRANSMISSION_BACKGROUND
+                uniform sampler2D transmissionBackground{i};
+                uniform mat4 transmissionViewProjection{i};
+
+                vec3 calculate_lighting{i}(vec3 surface_color, vec3 position, vec3 normal, vec3 view_direction, float metallic, float roughness, float occlusion)
+                {{
+                    return vec3(0.0);
+                }}
+
+                // Returns the light transmitted from the background at the given position, blurred by the given roughness
+                vec3 sample_transmission_background(vec3 position, float roughness)
+                {{
+                    vec4 p = transmissionViewProjection{i} * vec4(position, 1.0);
+                    vec2 uv = clamp(0.5 * p.xy / p.w + 0.5, 0.0, 1.0);
+                    float lod = log2(float(textureSize(transmissionBackground{i}, 0).x)) * roughness;
+                    return textureLod(transmissionBackground{i}, uv, lod).rgb;
+                }}
+
+            "
+        )
+    }
+
+    fn use_uniforms(&self, program: &Program, i: u32) {
+        if program.requires_uniform(&format!("transmissionBackground{}", i)) {
+            program.use_texture(&format!("transmissionBackground{}", i), &self.texture);
+            program.use_uniform(
+                &format!("transmissionViewProjection{}", i),
+                self.view_projection,
+            );
+        }
+    }
+
+    fn id(&self) -> LightId {
+        LightId::TransmissionBackground
+    }
+}
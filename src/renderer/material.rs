@@ -25,6 +25,18 @@ macro_rules! impl_material_body {
         fn id(&self) -> EffectMaterialId {
             self.$inner().id()
         }
+        fn source_id(&self) -> Option<u64> {
+            self.$inner().source_id()
+        }
+        fn uses_material_extensions(&self) -> bool {
+            self.$inner().uses_material_extensions()
+        }
+        fn alpha_mask(&self) -> Option<AlphaMask> {
+            self.$inner().alpha_mask()
+        }
+        fn vertex_deformation(&self) -> Option<VertexDeformation> {
+            self.$inner().vertex_deformation()
+        }
     };
 }
 
@@ -66,6 +78,26 @@ mod physical_material;
 #[doc(inline)]
 pub use physical_material::*;
 
+mod shadow_catcher_material;
+#[doc(inline)]
+pub use shadow_catcher_material::*;
+
+mod toon_material;
+#[doc(inline)]
+pub use toon_material::*;
+
+mod triplanar_material;
+#[doc(inline)]
+pub use triplanar_material::*;
+
+mod terrain_material;
+#[doc(inline)]
+pub use terrain_material::*;
+
+mod snippet_material;
+#[doc(inline)]
+pub use snippet_material::*;
+
 mod deferred_physical_material;
 #[doc(inline)]
 pub use deferred_physical_material::*;
@@ -132,6 +164,140 @@ impl std::convert::From<Arc<Texture2D>> for Texture2DRef {
     }
 }
 
+///
+/// Describes how the vertices of a [Geometry] are deformed, for example to make foliage sway in the wind, to displace the vertices using a height texture or to make a flag wave.
+/// A [Material] deforms the geometry it is applied to by returning a deformation from [Material::vertex_deformation].
+/// The deformation is applied by [Mesh], [InstancedMesh], [ParticleSystem], [Sprites], [Terrain] and [Water], also when generating shadow maps and when picking.
+/// Rendering any other geometry, for example a [Skybox] or a custom [Geometry], with a deformation panics.
+/// A custom geometry supports deformation if its vertex shader calls `deform_vertex` when `USE_VERTEX_DEFORMATION` is defined, in which case the function is declared by the geometry and defined by the deformation.
+///
+/// The deformation is defined by GLSL source code which defines the function
+/// ```no_rust
+/// void deform_vertex(vec3 local_position, vec2 uv, inout vec3 world_position, inout vec3 world_normal)
+/// ```
+/// which is called for each vertex with the position before any transformation, the uv coordinates, the position in world space and the normal in world space.
+/// The uv coordinates and the normal are only available if [VertexDeformation::uv] and [VertexDeformation::normal] are true respectively, otherwise they can be zero. Any changes to the normal are applied to the normal used for lighting.
+/// The uniforms and textures are declared automatically and must not have the same names as the uniforms of the vertex shader of the geometry, for example `time` is used by [ParticleSystem] and [Water].
+///
+/// Note that the bounding box of the geometry is not changed by the deformation, so large deformations can cause the geometry to be culled while still visible.
+/// Cloning a deformation is cheap, since the source, uniforms and textures are shared between the clones until they are changed.
+///
+#[derive(Clone)]
+pub struct VertexDeformation {
+    /// Whether or not the deformation uses the normal, which requires that the [Geometry] supports normals. [Sprites] does not support normals.
+    pub normal: bool,
+    /// Whether or not the deformation uses the uv coordinates, which requires that the [Geometry] supports uv coordinates.
+    pub uv: bool,
+    id: u64,
+    source: Arc<str>,
+    uniforms: Arc<Vec<(String, SnippetUniform)>>,
+    textures: Arc<Vec<(String, Texture2DRef)>>,
+}
+
+impl VertexDeformation {
+    ///
+    /// Creates a new deformation from the given GLSL source code defining the `deform_vertex` function, which does not use any uniforms, textures, normals or uv coordinates.
+    ///
+    pub fn new(source: &str) -> Self {
+        let mut vertex_deformation = Self {
+            normal: false,
+            uv: false,
+            id: 0,
+            source: source.into(),
+            uniforms: Arc::new(Vec::new()),
+            textures: Arc::new(Vec::new()),
+        };
+        vertex_deformation.update_id();
+        vertex_deformation
+    }
+
+    ///
+    /// Returns the GLSL source code defining the `deform_vertex` function.
+    ///
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    ///
+    /// Sets the value of the uniform with the given name, which is added if it does not already exist.
+    /// The uniform is declared with the GLSL type given by the value.
+    ///
+    pub fn set_uniform(&mut self, name: &str, value: impl Into<SnippetUniform>) {
+        let value = value.into();
+        let uniforms = Arc::make_mut(&mut self.uniforms);
+        if let Some(uniform) = uniforms.iter_mut().find(|(n, _)| n == name) {
+            let changed_type = uniform.1.glsl_type() != value.glsl_type();
+            uniform.1 = value;
+            if changed_type {
+                self.update_id();
+            }
+        } else {
+            uniforms.push((name.to_string(), value));
+            self.update_id();
+        }
+    }
+
+    ///
+    /// Sets the texture with the given name, which is added if it does not already exist.
+    /// The texture is declared as `uniform sampler2D`.
+    ///
+    pub fn set_texture(&mut self, name: &str, texture: impl Into<Texture2DRef>) {
+        let texture = texture.into();
+        let textures = Arc::make_mut(&mut self.textures);
+        if let Some(t) = textures.iter_mut().find(|(n, _)| n == name) {
+            t.1 = texture;
+        } else {
+            textures.push((name.to_string(), texture));
+            self.update_id();
+        }
+    }
+
+    ///
+    /// Returns an id of the vertex shader source, which is computed when the source changes since it is used every time the deformation is rendered.
+    ///
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn update_id(&mut self) {
+        self.id = fnv1a_hash(self.vertex_shader_source().as_bytes());
+    }
+
+    ///
+    /// Returns the vertex shader source which is appended to the vertex shader of the geometry.
+    ///
+    pub(crate) fn vertex_shader_source(&self) -> String {
+        let mut source = String::new();
+        for (name, value) in self.uniforms.iter() {
+            source.push_str(&format!("uniform {} {};\n", value.glsl_type(), name));
+        }
+        for (name, _) in self.textures.iter() {
+            source.push_str(&format!("uniform sampler2D {};\n", name));
+        }
+        source.push_str(&self.source);
+        source
+    }
+
+    pub(crate) fn fragment_attributes(vertex_deformation: Option<&Self>) -> FragmentAttributes {
+        FragmentAttributes {
+            normal: vertex_deformation.map(|d| d.normal).unwrap_or(false),
+            uv: vertex_deformation.map(|d| d.uv).unwrap_or(false),
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    pub(crate) fn use_uniforms(&self, program: &Program) {
+        for (name, value) in self.uniforms.iter() {
+            value.use_uniform(program, name);
+        }
+        for (name, texture) in self.textures.iter() {
+            if program.requires_uniform(name) {
+                program.use_texture(name, texture);
+            }
+        }
+    }
+}
+
 ///
 /// Defines the material type which is needed to render the objects in the correct order.
 /// For example, transparent objects need to be rendered back to front, whereas opaque objects need to be rendered front to back.
@@ -144,6 +310,59 @@ pub enum MaterialType {
     Transparent,
     /// Deferred opaque
     Deferred,
+    /// Forward transmissive, ie. the light from the opaque objects behind is transmitted through the object, see [TransmissionBackground]
+    Transmissive,
+}
+
+///
+/// Describes which fragments are discarded by a material with an alpha cutout, for example a [PhysicalMaterial] with [PhysicalMaterial::alpha_cutout].
+/// A fragment is discarded if the alpha value, ie. the product of [AlphaMask::alpha], the alpha value sampled from [AlphaMask::texture] and the alpha value of any per vertex colors,
+/// is below [AlphaMask::cutout].
+/// See [Material::alpha_mask] and [Geometry::alpha_mask].
+///
+#[derive(Clone)]
+pub struct AlphaMask {
+    /// Fragments with an alpha value below this threshold are discarded.
+    pub cutout: f32,
+    /// The base alpha value of the material.
+    pub alpha: f32,
+    /// An optional texture where the alpha values are stored in the alpha channel (requires that the [Geometry] supports uv coordinates).
+    pub texture: Option<Texture2DRef>,
+}
+
+impl AlphaMask {
+    ///
+    /// Returns the shader source which defines the `alpha_mask()` function that discards the masked fragments, if an alpha mask is given.
+    ///
+    pub(crate) fn fragment_shader_source(alpha_mask: Option<&Self>) -> String {
+        if let Some(alpha_mask) = alpha_mask {
+            let mut source = "#define USE_ALPHA_MASK\nin vec4 col;\n".to_string();
+            if alpha_mask.texture.is_some() {
+                source.push_str("#define USE_ALPHA_TEXTURE\nin vec2 uvs;\n");
+            }
+            source.push_str(include_str!("material/shaders/alpha_mask.frag"));
+            source
+        } else {
+            "void alpha_mask() {}\n".to_string()
+        }
+    }
+
+    pub(crate) fn fragment_attributes(alpha_mask: Option<&Self>) -> FragmentAttributes {
+        FragmentAttributes {
+            color: alpha_mask.is_some(),
+            uv: alpha_mask.map(|a| a.texture.is_some()).unwrap_or(false),
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    pub(crate) fn use_uniforms(&self, program: &Program) {
+        program.use_uniform("alphaCutout", self.cutout);
+        program.use_uniform("alphaValue", self.alpha);
+        if let Some(ref texture) = self.texture {
+            program.use_texture("alphaTexture", texture);
+            program.use_uniform("alphaTexTransform", texture.transformation);
+        }
+    }
 }
 
 ///
@@ -222,6 +441,43 @@ pub trait Material {
     /// Returns the type of material.
     ///
     fn material_type(&self) -> MaterialType;
+
+    ///
+    /// Returns an id which together with [Material::id] identifies the shader source returned by [Material::fragment_shader_source],
+    /// for materials where the shader source can vary in more ways than can be described by the [EffectMaterialId], for example when the source is provided by the user.
+    /// The id should be computed when the material is created, since it is used every time the material is rendered.
+    /// The default implementation returns `None`, ie. the [EffectMaterialId] identifies the shader source.
+    ///
+    fn source_id(&self) -> Option<u64> {
+        None
+    }
+
+    ///
+    /// Returns whether this [MaterialType::Deferred] material uses the material extensions which are stored in a fourth layer of the geometry pass, see [DeferredPhysicalMaterial].
+    /// The fourth layer is only allocated when rendering deferred objects in for example [RenderTarget::render] if at least one of the materials uses the material extensions.
+    /// The default implementation returns `false`.
+    ///
+    fn uses_material_extensions(&self) -> bool {
+        false
+    }
+
+    ///
+    /// Returns the [AlphaMask] describing which fragments are discarded by this material, if any.
+    /// This is used to discard the same fragments when the geometry is rendered with another material,
+    /// for example when generating shadow maps or when picking.
+    ///
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        None
+    }
+
+    ///
+    /// Returns the [VertexDeformation] which deforms the vertices of the geometry rendered with this material, if any.
+    /// The deformation is also applied when the geometry is rendered with another material,
+    /// for example when generating shadow maps or when picking.
+    ///
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        None
+    }
 }
 
 ///
@@ -287,18 +543,41 @@ impl<T: Material> Material for std::sync::RwLock<T> {
     fn id(&self) -> EffectMaterialId {
         self.read().unwrap().id()
     }
+    fn source_id(&self) -> Option<u64> {
+        self.read().unwrap().source_id()
+    }
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        self.read().unwrap().alpha_mask()
+    }
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        self.read().unwrap().vertex_deformation()
+    }
 }
 
 fn is_transparent(cpu_material: &CpuMaterial) -> bool {
-    cpu_material.albedo.a != 255
-        || cpu_material
-            .albedo_texture
-            .as_ref()
-            .map(|t| match &t.data {
-                TextureData::RgbaU8(data) => data.iter().any(|d| d[3] != 255),
-                TextureData::RgbaF16(data) => data.iter().any(|d| d[3] < f16::from_f32(0.99)),
-                TextureData::RgbaF32(data) => data.iter().any(|d| d[3] < 0.99),
-                _ => false,
-            })
-            .unwrap_or(false)
+    cpu_material.alpha_cutout.is_none()
+        && (cpu_material.albedo.a != 255
+            || cpu_material
+                .albedo_texture
+                .as_ref()
+                .map(|t| match &t.data {
+                    TextureData::RgbaU8(data) => data.iter().any(|d| d[3] != 255),
+                    TextureData::RgbaF16(data) => data.iter().any(|d| d[3] < f16::from_f32(0.99)),
+                    TextureData::RgbaF32(data) => data.iter().any(|d| d[3] < 0.99),
+                    _ => false,
+                })
+                .unwrap_or(false))
+}
+
+///
+/// Returns the reflectance at normal incidence of the non-metallic part of a material with the given index of refraction and specular adjustment.
+///
+fn dielectric_specular(index_of_refraction: f32, specular: Option<&Specular>) -> Vec3 {
+    let reflectance = ((index_of_refraction - 1.0) / (index_of_refraction + 1.0)).powi(2);
+    if let Some(specular) = specular {
+        (specular.color.to_linear_srgb().truncate() * reflectance)
+            .map(|c| c.min(1.0) * specular.factor)
+    } else {
+        vec3(reflectance, reflectance, reflectance)
+    }
 }
@@ -16,12 +16,16 @@ pub struct ColorMaterial {
     pub render_states: RenderStates,
     /// Whether this material should be treated as a transparent material (An object needs to be rendered differently depending on whether it is transparent or opaque).
     pub is_transparent: bool,
+    /// Fragments with an alpha value, ie. the alpha value of the [Self::color] multiplied with the alpha value in the [Self::texture] and any per vertex colors, below this threshold are discarded.
+    /// The same fragments are discarded when casting shadows and when picking, see [Material::alpha_mask].
+    pub alpha_cutout: Option<f32>,
 }
 
 impl ColorMaterial {
     ///
     /// Constructs a new color material from a [CpuMaterial].
     /// Tries to infer whether this material is transparent or opaque from the alpha value of the albedo color and the alpha values in the albedo texture.
+    /// A material with an [CpuMaterial::alpha_cutout] is always opaque.
     /// Since this is not always correct, it is preferred to use [ColorMaterial::new_opaque] or [ColorMaterial::new_transparent].
     ///
     pub fn new(context: &Context, cpu_material: &CpuMaterial) -> Self {
@@ -50,6 +54,7 @@ impl ColorMaterial {
             color: cpu_material.albedo,
             texture,
             is_transparent: false,
+            alpha_cutout: cpu_material.alpha_cutout,
             render_states: RenderStates::default(),
         }
     }
@@ -72,6 +77,7 @@ impl ColorMaterial {
             color: cpu_material.albedo,
             texture,
             is_transparent: true,
+            alpha_cutout: cpu_material.alpha_cutout,
             render_states: RenderStates {
                 write_mask: WriteMask::COLOR,
                 blend: Blend::TRANSPARENCY,
@@ -87,6 +93,7 @@ impl ColorMaterial {
             texture: physical_material.albedo_texture.clone(),
             render_states: physical_material.render_states,
             is_transparent: physical_material.is_transparent,
+            alpha_cutout: physical_material.alpha_cutout,
         }
     }
 }
@@ -99,7 +106,7 @@ impl FromCpuMaterial for ColorMaterial {
 
 impl Material for ColorMaterial {
     fn id(&self) -> EffectMaterialId {
-        EffectMaterialId::ColorMaterial(self.texture.is_some())
+        EffectMaterialId::ColorMaterial(self.texture.is_some(), self.alpha_cutout.is_some())
     }
 
     fn fragment_shader_source(&self, _lights: &[&dyn Light]) -> String {
@@ -107,6 +114,9 @@ impl Material for ColorMaterial {
         if self.texture.is_some() {
             shader.push_str("#define USE_TEXTURE\nin vec2 uvs;\n");
         }
+        if self.alpha_cutout.is_some() {
+            shader.push_str("#define ALPHACUT\nuniform float alphaCutout;\n");
+        }
         shader.push_str(include_str!("../../core/shared.frag"));
         shader.push_str(ColorMapping::fragment_shader_source());
         shader.push_str(include_str!("shaders/color_material.frag"));
@@ -128,6 +138,9 @@ impl Material for ColorMaterial {
             program.use_uniform("textureTransformation", tex.transformation);
             program.use_texture("tex", tex);
         }
+        if let Some(alpha_cutout) = self.alpha_cutout {
+            program.use_uniform("alphaCutout", alpha_cutout);
+        }
     }
     fn render_states(&self) -> RenderStates {
         self.render_states
@@ -139,4 +152,12 @@ impl Material for ColorMaterial {
             MaterialType::Opaque
         }
     }
+
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        self.alpha_cutout.map(|cutout| AlphaMask {
+            cutout,
+            alpha: self.color.to_linear_srgb().w,
+            texture: self.texture.clone(),
+        })
+    }
 }
@@ -10,6 +10,10 @@ use crate::renderer::*;
 /// However, it is not possible to use the [Object::render] method to render a [Geometry] with this material directly to the screen.
 /// Instead render the object into a [RenderTarget] consisting of a [Texture2DArray] with three RGBA u8 layers as color target and a [DepthTexture2D] as depth target.
 /// Then call the [DeferredPhysicalMaterial::lighting_pass] method with these textures to render to the screen.
+/// A fourth RGBA u8 layer is needed to support the [clearcoat](DeferredPhysicalMaterial::clearcoat), [sheen](DeferredPhysicalMaterial::sheen)
+/// and [specular](DeferredPhysicalMaterial::specular) extensions and the [index of refraction](DeferredPhysicalMaterial::index_of_refraction), otherwise they are ignored.
+/// The render methods only allocates this layer if at least one of the materials uses these extensions, see [Material::uses_material_extensions].
+/// Note that these are stored with a reduced precision in the geometry pass.
 ///
 #[derive(Clone)]
 pub struct DeferredPhysicalMaterial {
@@ -46,7 +50,17 @@ pub struct DeferredPhysicalMaterial {
     /// A threshold on the alpha value of the color as a workaround for transparency.
     /// If the alpha value of a pixel touched by an object with this material is less than the threshold, then that object is not contributing to the color of that pixel.
     /// On the other hand, if the alpha value is more than the threshold, then it is contributing fully to that pixel and thereby blocks out everything behind.
+    /// The same fragments are discarded when casting shadows and when picking, see [Material::alpha_mask].
     pub alpha_cutout: Option<f32>,
+    /// The index of refraction of the non-metallic part of the material, which determines how much light is reflected at normal incidence.
+    /// The default value of 1.5 corresponds to a reflectance of 4% which is typical for many materials, for example plastic and glass.
+    pub index_of_refraction: f32,
+    /// An optional clear coating on top of the material, see [Clearcoat].
+    pub clearcoat: Option<Clearcoat>,
+    /// An optional sheen on top of the material, see [Sheen].
+    pub sheen: Option<Sheen>,
+    /// An optional adjustment of the strength and color of the specular reflections, see [Specular].
+    pub specular: Option<Specular>,
 }
 
 impl DeferredPhysicalMaterial {
@@ -54,6 +68,7 @@ impl DeferredPhysicalMaterial {
     /// Constructs a new deferred physical material from a [CpuMaterial].
     /// If the input contains an [CpuMaterial::occlusion_metallic_roughness_texture], this texture is used for both
     /// [DeferredPhysicalMaterial::metallic_roughness_texture] and [DeferredPhysicalMaterial::occlusion_texture] while any [CpuMaterial::metallic_roughness_texture] or [CpuMaterial::occlusion_texture] are ignored.
+    /// The [DeferredPhysicalMaterial::index_of_refraction] is read from the [CpuMaterial], while the [DeferredPhysicalMaterial::clearcoat], [DeferredPhysicalMaterial::sheen] and [DeferredPhysicalMaterial::specular] are not available in a [CpuMaterial] and are therefore not set.
     ///
     pub fn new(context: &Context, cpu_material: &CpuMaterial) -> Self {
         let albedo_texture =
@@ -116,6 +131,10 @@ impl DeferredPhysicalMaterial {
             alpha_cutout: cpu_material.alpha_cutout,
             emissive: cpu_material.emissive,
             emissive_texture,
+            index_of_refraction: cpu_material.index_of_refraction,
+            clearcoat: None,
+            sheen: None,
+            specular: None,
         }
     }
 
@@ -142,12 +161,20 @@ impl DeferredPhysicalMaterial {
             emissive: physical_material.emissive,
             emissive_texture: physical_material.emissive_texture.clone(),
             alpha_cutout: if physical_material.is_transparent {
-                Some(0.5)
+                physical_material.alpha_cutout.or(Some(0.5))
             } else {
-                None
+                physical_material.alpha_cutout
             },
+            index_of_refraction: physical_material.index_of_refraction,
+            clearcoat: physical_material.clearcoat.clone(),
+            sheen: physical_material.sheen,
+            specular: physical_material.specular,
         }
     }
+
+    fn uses_specular(&self) -> bool {
+        self.specular.is_some() || self.index_of_refraction != 1.5
+    }
     ///
     /// The second stage of a deferred render call.
     /// Use the [Object::render] method to render the objects with this material into a [RenderTarget] and then call this method with these textures to render to the screen.
@@ -162,7 +189,33 @@ impl DeferredPhysicalMaterial {
     ) {
         apply_screen_effect(
             context,
-            lighting_pass::LightingPassEffect {},
+            lighting_pass::LightingPassEffect {
+                contact_shadows: None,
+            },
+            camera,
+            lights,
+            Some(geometry_pass_color_texture),
+            Some(geometry_pass_depth_texture),
+        );
+    }
+
+    ///
+    /// The second stage of a deferred render call like [DeferredPhysicalMaterial::lighting_pass],
+    /// where the light from each light which supports it is blocked by the given contact shadows (see [ContactShadowEffect]).
+    ///
+    pub fn lighting_pass_with_contact_shadows(
+        context: &Context,
+        camera: &Camera,
+        geometry_pass_color_texture: ColorTexture,
+        geometry_pass_depth_texture: DepthTexture,
+        lights: &[&dyn Light],
+        contact_shadows: &ContactShadowEffect,
+    ) {
+        apply_screen_effect(
+            context,
+            lighting_pass::LightingPassEffect {
+                contact_shadows: Some(contact_shadows.clone()),
+            },
             camera,
             lights,
             Some(geometry_pass_color_texture),
@@ -186,17 +239,28 @@ impl Material for DeferredPhysicalMaterial {
             self.normal_texture.is_some(),
             self.emissive_texture.is_some(),
             self.alpha_cutout.is_some(),
+            self.clearcoat.is_some(),
+            self.sheen.is_some(),
+            self.uses_specular(),
         )
     }
 
     fn fragment_shader_source(&self, _lights: &[&dyn Light]) -> String {
         let mut output = include_str!("../../core/shared.frag").to_string();
+        if self.clearcoat.is_some() {
+            output.push_str("#define USE_CLEARCOAT\n");
+        }
+        if self.sheen.is_some() {
+            output.push_str("#define USE_SHEEN\n");
+        }
+        if self.uses_specular() {
+            output.push_str("#define USE_SPECULAR\n");
+        }
         if self.albedo_texture.is_some()
             || self.metallic_roughness_texture.is_some()
             || self.normal_texture.is_some()
             || self.occlusion_texture.is_some()
             || self.emissive_texture.is_some()
-            || self.alpha_cutout.is_some()
         {
             output.push_str("in vec2 uvs;\n");
             if self.albedo_texture.is_some() {
@@ -214,15 +278,9 @@ impl Material for DeferredPhysicalMaterial {
             if self.emissive_texture.is_some() {
                 output.push_str("#define USE_EMISSIVE_TEXTURE;\n");
             }
-            if self.alpha_cutout.is_some() {
-                output.push_str(
-                    format!(
-                        "#define ALPHACUT;\nfloat acut = {};",
-                        self.alpha_cutout.unwrap()
-                    )
-                    .as_str(),
-                );
-            }
+        }
+        if self.alpha_cutout.is_some() {
+            output.push_str("#define ALPHACUT;\nuniform float alphaCutout;\n");
         }
         output.push_str(include_str!("shaders/deferred_physical_material.frag"));
         output
@@ -237,8 +295,7 @@ impl Material for DeferredPhysicalMaterial {
                 || self.metallic_roughness_texture.is_some()
                 || self.normal_texture.is_some()
                 || self.occlusion_texture.is_some()
-                || self.emissive_texture.is_some()
-                || self.alpha_cutout.is_some(),
+                || self.emissive_texture.is_some(),
             tangents: self.normal_texture.is_some(),
         }
     }
@@ -252,6 +309,9 @@ impl Material for DeferredPhysicalMaterial {
             program.use_texture("albedoTexture", texture);
             program.use_uniform("albedoTexTransform", texture.transformation);
         }
+        if let Some(alpha_cutout) = self.alpha_cutout {
+            program.use_uniform("alphaCutout", alpha_cutout);
+        }
         if let Some(ref texture) = self.metallic_roughness_texture {
             program.use_texture("metallicRoughnessTexture", texture);
             program.use_uniform("metallicRoughnessTexTransform", texture.transformation);
@@ -272,6 +332,20 @@ impl Material for DeferredPhysicalMaterial {
                 program.use_texture("emissiveTexture", texture);
             }
         }
+        if let Some(ref clearcoat) = self.clearcoat {
+            program.use_uniform("clearcoatFactor", clearcoat.factor);
+            program.use_uniform("clearcoatRoughness", clearcoat.roughness);
+        }
+        if let Some(ref sheen) = self.sheen {
+            program.use_uniform("sheenColor", sheen.color.to_linear_srgb().truncate());
+            program.use_uniform("sheenRoughness", sheen.roughness);
+        }
+        if self.uses_specular() {
+            program.use_uniform(
+                "dielectricSpecular",
+                super::dielectric_specular(self.index_of_refraction, self.specular.as_ref()),
+            );
+        }
     }
 
     fn render_states(&self) -> RenderStates {
@@ -281,6 +355,18 @@ impl Material for DeferredPhysicalMaterial {
     fn material_type(&self) -> MaterialType {
         MaterialType::Deferred
     }
+
+    fn uses_material_extensions(&self) -> bool {
+        self.clearcoat.is_some() || self.sheen.is_some() || self.uses_specular()
+    }
+
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        self.alpha_cutout.map(|cutout| AlphaMask {
+            cutout,
+            alpha: self.albedo.to_linear_srgb().w,
+            texture: self.albedo_texture.clone(),
+        })
+    }
 }
 
 impl Default for DeferredPhysicalMaterial {
@@ -300,6 +386,10 @@ impl Default for DeferredPhysicalMaterial {
             alpha_cutout: None,
             emissive: Srgba::BLACK,
             emissive_texture: None,
+            index_of_refraction: 1.5,
+            clearcoat: None,
+            sheen: None,
+            specular: None,
         }
     }
 }
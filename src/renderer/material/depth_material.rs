@@ -13,6 +13,10 @@ pub struct DepthMaterial {
     pub max_distance: Option<f32>,
     /// Render states.
     pub render_states: RenderStates,
+    /// An optional alpha mask which discards the same fragments as the material the geometry is otherwise rendered with, see [Geometry::alpha_mask].
+    pub alpha_mask: Option<AlphaMask>,
+    /// An optional vertex deformation which deforms the geometry in the same way as the material the geometry is otherwise rendered with, see [Geometry::vertex_deformation].
+    pub vertex_deformation: Option<VertexDeformation>,
 }
 
 impl FromCpuMaterial for DepthMaterial {
@@ -23,17 +27,25 @@ impl FromCpuMaterial for DepthMaterial {
 
 impl Material for DepthMaterial {
     fn id(&self) -> EffectMaterialId {
-        EffectMaterialId::DepthMaterial
+        EffectMaterialId::DepthMaterial(
+            self.alpha_mask.is_some(),
+            self.alpha_mask
+                .as_ref()
+                .map(|a| a.texture.is_some())
+                .unwrap_or(false),
+        )
     }
 
     fn fragment_shader_source(&self, _lights: &[&dyn Light]) -> String {
-        include_str!("shaders/depth_material.frag").to_string()
+        let mut source = AlphaMask::fragment_shader_source(self.alpha_mask.as_ref());
+        source.push_str(include_str!("shaders/depth_material.frag"));
+        source
     }
 
     fn fragment_attributes(&self) -> FragmentAttributes {
         FragmentAttributes {
             position: true,
-            ..FragmentAttributes::NONE
+            ..AlphaMask::fragment_attributes(self.alpha_mask.as_ref())
         }
     }
 
@@ -47,6 +59,9 @@ impl Material for DepthMaterial {
             self.max_distance.unwrap_or_else(|| camera.z_far()),
         );
         program.use_uniform("eye", camera.position());
+        if let Some(ref alpha_mask) = self.alpha_mask {
+            alpha_mask.use_uniforms(program);
+        }
     }
 
     fn render_states(&self) -> RenderStates {
@@ -56,4 +71,8 @@ impl Material for DepthMaterial {
     fn material_type(&self) -> MaterialType {
         MaterialType::Opaque
     }
+
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        self.vertex_deformation.clone()
+    }
 }
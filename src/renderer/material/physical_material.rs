@@ -30,10 +30,16 @@ pub struct PhysicalMaterial {
     pub normal_scale: f32,
     /// A tangent space normal map, also known as bump map.
     pub normal_texture: Option<Texture2DRef>,
+    /// An optional height map which makes the surface appear to have depth, see [Parallax].
+    pub parallax: Option<Parallax>,
     /// Render states.
     pub render_states: RenderStates,
     /// Whether this material should be treated as a transparent material (An object needs to be rendered differently depending on whether it is transparent or opaque).
     pub is_transparent: bool,
+    /// Fragments with an alpha value, ie. the alpha value of the [Self::albedo] multiplied with the alpha value in the [Self::albedo_texture] and any per vertex colors, below this threshold are discarded.
+    /// This is used for cutting out parts of an opaque material, for example leaves or a fence, instead of rendering it as a transparent material.
+    /// The same fragments are discarded when casting shadows and when picking, see [Material::alpha_mask].
+    pub alpha_cutout: Option<f32>,
     /// Color of light shining from an object.
     pub emissive: Srgba,
     /// Texture with color of light shining from an object.
@@ -41,6 +47,171 @@ pub struct PhysicalMaterial {
     pub emissive_texture: Option<Texture2DRef>,
     /// The lighting model used when rendering this material
     pub lighting_model: LightingModel,
+    /// The index of refraction of the non-metallic part of the material, which determines how much light is reflected at normal incidence.
+    /// The default value of 1.5 corresponds to a reflectance of 4% which is typical for many materials, for example plastic and glass.
+    pub index_of_refraction: f32,
+    /// An optional clear coating on top of the material, see [Clearcoat].
+    pub clearcoat: Option<Clearcoat>,
+    /// An optional sheen on top of the material, see [Sheen].
+    pub sheen: Option<Sheen>,
+    /// An optional adjustment of the strength and color of the specular reflections, see [Specular].
+    pub specular: Option<Specular>,
+    /// An optional transmission of the light through the material, for example for glass and liquids, see [Transmission].
+    /// If specified, the material is rendered as a [MaterialType::Transmissive] material.
+    pub transmission: Option<Transmission>,
+}
+
+///
+/// The light transmitted through the non-metallic part of a [PhysicalMaterial] instead of being diffusely reflected, for example for glass and liquids.
+/// The transmitted light is the light from the opaque objects behind, see [TransmissionBackground], which is tinted by the albedo and blurred by the roughness of the material.
+///
+#[derive(Clone)]
+pub struct Transmission {
+    /// A value in the range `[0..1]` specifying the fraction of the light which is transmitted.
+    pub factor: f32,
+    /// Texture containing the transmission which is multiplied with the [Self::factor] value in the shader.
+    /// The transmission values are sampled from the red channel.
+    pub texture: Option<Texture2DRef>,
+    /// An optional volume inside the material which refracts and absorbs the transmitted light, see [Volume].
+    /// If not specified, the material is assumed to be infinitely thin, ie. the transmitted light is neither refracted nor absorbed.
+    pub volume: Option<Volume>,
+}
+
+impl Default for Transmission {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            texture: None,
+            volume: None,
+        }
+    }
+}
+
+///
+/// Parallax occlusion mapping of a [PhysicalMaterial], which makes the surface appear to have depth by offsetting the texture coordinates according to the view direction.
+/// Requires that the [Geometry] supports uv coordinates and tangents.
+///
+#[derive(Clone)]
+pub struct Parallax {
+    /// The height map. The heights are sampled from the red channel, where 1 is the surface of the geometry and 0 is the deepest point, see [Self::height_scale].
+    pub height_texture: Texture2DRef,
+    /// The depth of the deepest point in the [Self::height_texture] relative to the size of the texture.
+    pub height_scale: f32,
+    /// The number of steps used when searching for the intersection with the height field of the [Self::height_texture].
+    /// More steps gives more accurate results, especially at grazing angles, but is more expensive.
+    pub step_count: u32,
+    /// Whether or not the height field of the [Self::height_texture] casts shadows onto itself, ie. whether the light from the lights is blocked by the height field.
+    pub self_shadowing: bool,
+}
+
+impl Parallax {
+    ///
+    /// Constructs a new parallax occlusion mapping from the given height map with default settings.
+    ///
+    pub fn new(height_texture: Texture2DRef) -> Self {
+        Self {
+            height_texture,
+            height_scale: 0.05,
+            step_count: 32,
+            self_shadowing: true,
+        }
+    }
+}
+
+///
+/// The volume inside a [Transmission] of a [PhysicalMaterial], for example a glass of colored liquid.
+/// The light transmitted through the volume is refracted at the surface according to the [PhysicalMaterial::index_of_refraction] and absorbed on the way through the volume.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Volume {
+    /// The thickness of the volume, ie. the distance the transmitted light travels through the volume.
+    pub thickness: f32,
+    /// The color that white light turns into when travelling the [Self::attenuation_distance] through the volume.
+    pub attenuation_color: Srgba,
+    /// The distance the light travels through the volume before it has the [Self::attenuation_color].
+    /// The default value of infinity means that the light is not absorbed.
+    pub attenuation_distance: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            thickness: 1.0,
+            attenuation_color: Srgba::WHITE,
+            attenuation_distance: f32::INFINITY,
+        }
+    }
+}
+
+///
+/// A clear coating on top of a [PhysicalMaterial] or [DeferredPhysicalMaterial], for example a layer of clear lacquer on car paint or varnished wood.
+/// The coating is a thin dielectric layer with an index of refraction of 1.5 which reflects light at the top and attenuates the light reflected by the material below.
+///
+#[derive(Clone)]
+pub struct Clearcoat {
+    /// A value in the range `[0..1]` specifying the intensity of the clear coating.
+    pub factor: f32,
+    /// A value in the range `[0..1]` specifying how rough the clear coating is.
+    pub roughness: f32,
+    /// A scalar multiplier applied to each normal vector of the [Self::normal_texture].
+    pub normal_scale: f32,
+    /// A tangent space normal map of the clear coating.
+    /// If not specified, the coating follows the normal of the geometry without the normal map of the material applied, ie. the coating is smooth on top of a bumpy surface.
+    /// This is not supported by [DeferredPhysicalMaterial] where the coating always follows the normal of the material.
+    pub normal_texture: Option<Texture2DRef>,
+}
+
+impl Default for Clearcoat {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            roughness: 0.0,
+            normal_scale: 1.0,
+            normal_texture: None,
+        }
+    }
+}
+
+///
+/// A sheen on top of a [PhysicalMaterial] or [DeferredPhysicalMaterial], which is the soft light reflected at grazing angles by cloth like velvet and satin.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Sheen {
+    /// The color of the sheen.
+    pub color: Srgba,
+    /// A value in the range `[0..1]` specifying how rough the sheen is, ie. how far the sheen spreads from the grazing angles.
+    pub roughness: f32,
+}
+
+impl Default for Sheen {
+    fn default() -> Self {
+        Self {
+            color: Srgba::WHITE,
+            roughness: 0.5,
+        }
+    }
+}
+
+///
+/// Adjusts the strength and color of the specular reflections of the non-metallic part of a [PhysicalMaterial] or [DeferredPhysicalMaterial].
+/// The reflectance at normal incidence is given by the index of refraction of the material multiplied by the color and the factor.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Specular {
+    /// A value in the range `[0..1]` specifying the strength of the specular reflections.
+    pub factor: f32,
+    /// The color of the specular reflections.
+    /// This is approximated by its average when using [DeferredPhysicalMaterial].
+    pub color: Srgba,
+}
+
+impl Default for Specular {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            color: Srgba::WHITE,
+        }
+    }
 }
 
 impl PhysicalMaterial {
@@ -48,7 +219,10 @@ impl PhysicalMaterial {
     /// Constructs a new physical material from a [CpuMaterial].
     /// If the input contains an [CpuMaterial::occlusion_metallic_roughness_texture], this texture is used for both
     /// [PhysicalMaterial::metallic_roughness_texture] and [PhysicalMaterial::occlusion_texture] while any [CpuMaterial::metallic_roughness_texture] or [CpuMaterial::occlusion_texture] are ignored.
+    /// The [PhysicalMaterial::index_of_refraction] and [PhysicalMaterial::transmission] are read from the [CpuMaterial], except for the [Transmission::volume],
+    /// while the [PhysicalMaterial::clearcoat], [PhysicalMaterial::sheen], [PhysicalMaterial::specular] and [PhysicalMaterial::parallax] are not available in a [CpuMaterial] and are therefore not set.
     /// Tries to infer whether this material is transparent or opaque from the alpha value of the albedo color and the alpha values in the albedo texture.
+    /// A material with an [CpuMaterial::alpha_cutout] is always opaque.
     /// Since this is not always correct, it is preferred to use [PhysicalMaterial::new_opaque] or [PhysicalMaterial::new_transparent].
     ///
     pub fn new(context: &Context, cpu_material: &CpuMaterial) -> Self {
@@ -103,6 +277,10 @@ impl PhysicalMaterial {
             .normal_texture
             .as_ref()
             .map(|cpu_texture| Texture2DRef::from_cpu_texture(context, cpu_texture));
+        let transmission_texture = cpu_material
+            .transmission_texture
+            .as_ref()
+            .map(|cpu_texture| Texture2DRef::from_cpu_texture(context, cpu_texture));
         let emissive_texture =
             cpu_material
                 .emissive_texture
@@ -124,6 +302,7 @@ impl PhysicalMaterial {
             metallic_roughness_texture,
             normal_texture,
             normal_scale: cpu_material.normal_scale,
+            parallax: None,
             occlusion_texture,
             occlusion_strength: cpu_material.occlusion_strength,
             render_states: if is_transparent {
@@ -136,11 +315,48 @@ impl PhysicalMaterial {
                 RenderStates::default()
             },
             is_transparent,
+            alpha_cutout: cpu_material.alpha_cutout,
             emissive: cpu_material.emissive,
             emissive_texture,
             lighting_model: cpu_material.lighting_model,
+            index_of_refraction: cpu_material.index_of_refraction,
+            clearcoat: None,
+            sheen: None,
+            specular: None,
+            transmission: (cpu_material.transmission > 0.0).then(|| Transmission {
+                factor: cpu_material.transmission,
+                texture: transmission_texture,
+                volume: None,
+            }),
         }
     }
+
+    fn uses_specular(&self) -> bool {
+        self.specular.is_some() || self.index_of_refraction != 1.5
+    }
+
+    fn is_transmissive(&self) -> bool {
+        self.transmission.is_some()
+    }
+
+    fn uses_transmission_texture(&self) -> bool {
+        self.transmission
+            .as_ref()
+            .is_some_and(|transmission| transmission.texture.is_some())
+    }
+
+    fn uses_volume(&self) -> bool {
+        self.transmission
+            .as_ref()
+            .is_some_and(|transmission| transmission.volume.is_some())
+    }
+
+    fn uses_clearcoat_normal_texture(&self) -> bool {
+        self.clearcoat
+            .as_ref()
+            .map(|clearcoat| clearcoat.normal_texture.is_some())
+            .unwrap_or(false)
+    }
 }
 
 impl FromCpuMaterial for PhysicalMaterial {
@@ -157,18 +373,56 @@ impl Material for PhysicalMaterial {
             self.occlusion_texture.is_some(),
             self.normal_texture.is_some(),
             self.emissive_texture.is_some(),
+            self.clearcoat.is_some(),
+            self.uses_clearcoat_normal_texture(),
+            self.sheen.is_some(),
+            self.uses_specular(),
+            self.is_transmissive(),
+            self.uses_transmission_texture(),
+            self.uses_volume(),
+            self.alpha_cutout.is_some(),
+            self.parallax.is_some(),
         )
     }
 
     fn fragment_shader_source(&self, lights: &[&dyn Light]) -> String {
-        let mut output = lights_shader_source(lights, self.lighting_model);
+        // The material extensions must be defined before the lights since they change the lighting calculations
+        let mut output = String::new();
+        if self.clearcoat.is_some() {
+            output.push_str("#define USE_CLEARCOAT\n");
+        }
+        if self.sheen.is_some() {
+            output.push_str("#define USE_SHEEN\n");
+        }
+        if self.uses_specular() {
+            output.push_str("#define USE_SPECULAR\n");
+        }
+        if self.parallax.is_some() {
+            output.push_str("#define USE_PARALLAX_SHADOW\n");
+        }
+        if self.is_transmissive() {
+            output.push_str("#define USE_TRANSMISSION\n");
+            if self.uses_volume() {
+                output.push_str("#define USE_VOLUME\n");
+            }
+        }
+        output.push_str(&lights_shader_source(lights, self.lighting_model));
+        if self.normal_texture.is_some()
+            || self.uses_clearcoat_normal_texture()
+            || self.parallax.is_some()
+        {
+            output.push_str("in vec3 tang;\nin vec3 bitang;\n");
+        }
         if self.albedo_texture.is_some()
             || self.metallic_roughness_texture.is_some()
             || self.normal_texture.is_some()
             || self.occlusion_texture.is_some()
             || self.emissive_texture.is_some()
+            || self.uses_clearcoat_normal_texture()
+            || self.uses_transmission_texture()
+            || self.parallax.is_some()
         {
-            output.push_str("in vec2 uvs;\n");
+            output.push_str("in vec2 uvs;\n#define USE_UVS;\n");
             if self.albedo_texture.is_some() {
                 output.push_str("#define USE_ALBEDO_TEXTURE;\n");
             }
@@ -179,11 +433,23 @@ impl Material for PhysicalMaterial {
                 output.push_str("#define USE_OCCLUSION_TEXTURE;\n");
             }
             if self.normal_texture.is_some() {
-                output.push_str("#define USE_NORMAL_TEXTURE;\nin vec3 tang;\nin vec3 bitang;\n");
+                output.push_str("#define USE_NORMAL_TEXTURE;\n");
             }
             if self.emissive_texture.is_some() {
                 output.push_str("#define USE_EMISSIVE_TEXTURE;\n");
             }
+            if self.uses_clearcoat_normal_texture() {
+                output.push_str("#define USE_CLEARCOAT_NORMAL_TEXTURE;\n");
+            }
+            if self.uses_transmission_texture() {
+                output.push_str("#define USE_TRANSMISSION_TEXTURE;\n");
+            }
+            if self.parallax.is_some() {
+                output.push_str("#define USE_HEIGHT_TEXTURE;\n");
+            }
+        }
+        if self.alpha_cutout.is_some() {
+            output.push_str("#define ALPHACUT;\nuniform float alphaCutout;\n");
         }
         output.push_str(ToneMapping::fragment_shader_source());
         output.push_str(ColorMapping::fragment_shader_source());
@@ -200,8 +466,13 @@ impl Material for PhysicalMaterial {
                 || self.metallic_roughness_texture.is_some()
                 || self.normal_texture.is_some()
                 || self.occlusion_texture.is_some()
-                || self.emissive_texture.is_some(),
-            tangents: self.normal_texture.is_some(),
+                || self.emissive_texture.is_some()
+                || self.uses_clearcoat_normal_texture()
+                || self.uses_transmission_texture()
+                || self.parallax.is_some(),
+            tangents: self.normal_texture.is_some()
+                || self.uses_clearcoat_normal_texture()
+                || self.parallax.is_some(),
         }
     }
 
@@ -215,12 +486,6 @@ impl Material for PhysicalMaterial {
             }
             program.use_uniform("metallic", self.metallic);
             program.use_uniform_if_required("roughness", self.roughness);
-            if program.requires_uniform("albedoTexture") {
-                if let Some(ref texture) = self.albedo_texture {
-                    program.use_uniform("albedoTexTransform", texture.transformation);
-                    program.use_texture("albedoTexture", texture);
-                }
-            }
             if program.requires_uniform("metallicRoughnessTexture") {
                 if let Some(ref texture) = self.metallic_roughness_texture {
                     program.use_uniform("metallicRoughnessTexTransform", texture.transformation);
@@ -241,8 +506,74 @@ impl Material for PhysicalMaterial {
                     program.use_texture("normalTexture", texture);
                 }
             }
+            if let Some(ref clearcoat) = self.clearcoat {
+                program.use_uniform_if_required("clearcoatFactor", clearcoat.factor);
+                program.use_uniform_if_required("clearcoatRoughness", clearcoat.roughness);
+                if program.requires_uniform("clearcoatNormalTexture") {
+                    if let Some(ref texture) = clearcoat.normal_texture {
+                        program.use_uniform("clearcoatNormalTexTransform", texture.transformation);
+                        program.use_uniform("clearcoatNormalScale", clearcoat.normal_scale);
+                        program.use_texture("clearcoatNormalTexture", texture);
+                    }
+                }
+            }
+            if let Some(ref sheen) = self.sheen {
+                program
+                    .use_uniform_if_required("sheenColor", sheen.color.to_linear_srgb().truncate());
+                program.use_uniform_if_required("sheenRoughness", sheen.roughness);
+            }
+            if self.uses_specular() {
+                program.use_uniform_if_required(
+                    "dielectricSpecular",
+                    super::dielectric_specular(self.index_of_refraction, self.specular.as_ref()),
+                );
+            }
+        }
+        if let Some(ref transmission) = self.transmission {
+            program.use_uniform_if_required("cameraPosition", camera.position());
+            program.use_uniform_if_required("transmission", transmission.factor);
+            program.use_uniform_if_required("indexOfRefraction", self.index_of_refraction);
+            program.use_uniform_if_required("metallic", self.metallic);
+            program.use_uniform_if_required("roughness", self.roughness);
+            if program.requires_uniform("transmissionTexture") {
+                if let Some(ref texture) = transmission.texture {
+                    program.use_uniform("transmissionTexTransform", texture.transformation);
+                    program.use_texture("transmissionTexture", texture);
+                }
+            }
+            if let Some(ref volume) = transmission.volume {
+                program.use_uniform_if_required("volumeThickness", volume.thickness);
+                program.use_uniform_if_required(
+                    "attenuationColor",
+                    volume.attenuation_color.to_linear_srgb().truncate(),
+                );
+                program.use_uniform_if_required("attenuationDistance", volume.attenuation_distance);
+            }
+        }
+        if program.requires_uniform("heightTexture") {
+            if let Some(ref parallax) = self.parallax {
+                let texture = &parallax.height_texture;
+                program.use_uniform("heightTexTransform", texture.transformation);
+                program.use_uniform("heightScale", parallax.height_scale);
+                program.use_uniform("parallaxStepCount", parallax.step_count.max(1) as i32);
+                program.use_uniform_if_required(
+                    "parallaxSelfShadowing",
+                    parallax.self_shadowing as i32,
+                );
+                program.use_uniform_if_required("cameraPosition", camera.position());
+                program.use_texture("heightTexture", texture);
+            }
         }
         program.use_uniform("albedo", self.albedo.to_linear_srgb());
+        if program.requires_uniform("albedoTexture") {
+            if let Some(ref texture) = self.albedo_texture {
+                program.use_uniform("albedoTexTransform", texture.transformation);
+                program.use_texture("albedoTexture", texture);
+            }
+        }
+        if let Some(alpha_cutout) = self.alpha_cutout {
+            program.use_uniform("alphaCutout", alpha_cutout);
+        }
         program.use_uniform("emissive", self.emissive.to_linear_srgb());
         if program.requires_uniform("emissiveTexture") {
             if let Some(ref texture) = self.emissive_texture {
@@ -256,12 +587,22 @@ impl Material for PhysicalMaterial {
         self.render_states
     }
     fn material_type(&self) -> MaterialType {
-        if self.is_transparent {
+        if self.is_transmissive() {
+            MaterialType::Transmissive
+        } else if self.is_transparent {
             MaterialType::Transparent
         } else {
             MaterialType::Opaque
         }
     }
+
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        self.alpha_cutout.map(|cutout| AlphaMask {
+            cutout,
+            alpha: self.albedo.to_linear_srgb().w,
+            texture: self.albedo_texture.clone(),
+        })
+    }
 }
 
 impl Default for PhysicalMaterial {
@@ -275,13 +616,20 @@ impl Default for PhysicalMaterial {
             metallic_roughness_texture: None,
             normal_texture: None,
             normal_scale: 1.0,
+            parallax: None,
             occlusion_texture: None,
             occlusion_strength: 1.0,
             render_states: RenderStates::default(),
             is_transparent: false,
+            alpha_cutout: None,
             emissive: Srgba::BLACK,
             emissive_texture: None,
             lighting_model: LightingModel::Blinn,
+            index_of_refraction: 1.5,
+            clearcoat: None,
+            sheen: None,
+            specular: None,
+            transmission: None,
         }
     }
 }
@@ -0,0 +1,16 @@
+
+uniform float alphaCutout;
+uniform float alphaValue;
+#ifdef USE_ALPHA_TEXTURE
+uniform sampler2D alphaTexture;
+uniform mat3 alphaTexTransform;
+#endif
+
+void alpha_mask()
+{
+    float alpha = alphaValue * col.a;
+#ifdef USE_ALPHA_TEXTURE
+    alpha *= texture(alphaTexture, (alphaTexTransform * vec3(uvs, 1.0)).xy).a;
+#endif
+    if (alpha < alphaCutout) discard;
+}
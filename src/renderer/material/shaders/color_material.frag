@@ -17,5 +17,9 @@ void main()
     outColor *= texture(tex, (textureTransformation * vec3(uvs, 1.0)).xy);
     #endif
 
+    #ifdef ALPHACUT
+    if (outColor.a < alphaCutout) discard;
+    #endif
+
     outColor.rgb = color_mapping(outColor.rgb);
 }
\ No newline at end of file
@@ -31,6 +31,20 @@ uniform mat3 normalTexTransform;
 uniform float normalScale;
 #endif
 
+#ifdef USE_CLEARCOAT
+uniform float clearcoatFactor;
+uniform float clearcoatRoughness;
+#endif
+
+#ifdef USE_SHEEN
+uniform vec3 sheenColor;
+uniform float sheenRoughness;
+#endif
+
+#ifdef USE_SPECULAR
+uniform vec3 dielectricSpecular;
+#endif
+
 in vec3 pos;
 in vec3 nor;
 in vec4 col;
@@ -38,17 +52,26 @@ in vec4 col;
 layout (location = 0) out vec4 outColor;
 layout (location = 1) out vec4 outNormal;
 layout (location = 2) out vec4 outEmissive;
+layout (location = 3) out vec4 outExtensions;
+
+// Packs two values in the range [0..1] into the high and low 4 bits of a byte
+float pack_nibbles(float high, float low)
+{
+    int h = int(round(clamp(high, 0.0, 1.0) * 15.0));
+    int l = int(round(clamp(low, 0.0, 1.0) * 15.0));
+    return float(h << 4 | l) / 255.0;
+}
 
 void main()
 {
     vec4 surface_color = albedo * col;
 #ifdef USE_ALBEDO_TEXTURE
     vec4 c = texture(albedoTexture, (albedoTexTransform * vec3(uvs, 1.0)).xy);
-    #ifdef ALPHACUT
-        if (c.a < acut) discard;
-    #endif
     surface_color *= c;
 #endif
+#ifdef ALPHACUT
+    if (surface_color.a < alphaCutout) discard;
+#endif
 
     float metallic_factor = metallic;
     float roughness_factor = roughness;
@@ -83,5 +106,25 @@ void main()
         nz = 0;
     }
     outNormal = vec4(0.5 * normal.xy + 0.5, float(o | nz << 7)/255.0, roughness_factor);
-    outEmissive = vec4(total_emissive, 0.0);
+
+    // The material extensions are stored in the alpha channel of the emissive layer and in a fourth layer, which is ignored if not present
+    float specular = 0.04;
+#ifdef USE_SPECULAR
+    specular = dot(dielectricSpecular, vec3(1.0 / 3.0));
+#endif
+    vec3 sheen_color = vec3(0.0);
+    float sheen_roughness = 0.0;
+#ifdef USE_SHEEN
+    sheen_color = sheenColor;
+    sheen_roughness = sheenRoughness;
+#endif
+    float clearcoat_factor = 0.0;
+    float clearcoat_roughness = 0.0;
+#ifdef USE_CLEARCOAT
+    clearcoat_factor = clearcoatFactor;
+    clearcoat_roughness = clearcoatRoughness;
+#endif
+    // The specular reflectance is encoded such that the default of 0.04 is represented exactly
+    outEmissive = vec4(total_emissive, pack_nibbles(sqrt(specular / 0.36), sheen_roughness));
+    outExtensions = vec4(sheen_color, pack_nibbles(clearcoat_factor, clearcoat_roughness));
 }
\ No newline at end of file
@@ -9,6 +9,7 @@ layout (location = 0) out vec4 outColor;
 
 void main()
 {
+    alpha_mask();
     float dist = (distance(pos, eye) - minDistance) / (maxDistance - minDistance);
     outColor = vec4(dist, dist, dist, 1.0);
 }
\ No newline at end of file
@@ -11,6 +11,7 @@ layout (location = 0) out vec4 outColor;
 
 void main()
 {
+    alpha_mask();
     float dist = (distance(pos, eye) - minDistance) / (maxDistance - minDistance);
     outColor = vec4(dist, uintBitsToFloat(geometryId), intBitsToFloat(instance_id), intBitsToFloat(gl_PrimitiveID));
 }
\ No newline at end of file
@@ -32,50 +32,215 @@ uniform mat3 normalTexTransform;
 uniform float normalScale;
 #endif
 
+#ifdef USE_CLEARCOAT
+uniform float clearcoatFactor;
+uniform float clearcoatRoughness;
+#endif
+
+#ifdef USE_CLEARCOAT_NORMAL_TEXTURE
+uniform sampler2D clearcoatNormalTexture;
+uniform mat3 clearcoatNormalTexTransform;
+uniform float clearcoatNormalScale;
+#endif
+
+#ifdef USE_SHEEN
+uniform vec3 sheenColor;
+uniform float sheenRoughness;
+#endif
+
+#ifdef USE_SPECULAR
+uniform vec3 dielectricSpecular;
+#endif
+
+#ifdef USE_TRANSMISSION
+uniform float transmission;
+uniform float indexOfRefraction;
+#endif
+
+#ifdef USE_TRANSMISSION_TEXTURE
+uniform sampler2D transmissionTexture;
+uniform mat3 transmissionTexTransform;
+#endif
+
+#ifdef USE_VOLUME
+uniform float volumeThickness;
+uniform vec3 attenuationColor;
+uniform float attenuationDistance;
+#endif
+
+#ifdef USE_HEIGHT_TEXTURE
+uniform sampler2D heightTexture;
+uniform mat3 heightTexTransform;
+uniform float heightScale;
+uniform int parallaxStepCount;
+uniform int parallaxSelfShadowing;
+#endif
+
 in vec3 pos;
 in vec3 nor;
 in vec4 col;
 
 layout (location = 0) out vec4 outColor;
 
+#ifdef USE_HEIGHT_TEXTURE
+// The uv coordinates, depth and tangent space found by the parallax occlusion mapping, which are used for the self-shadowing
+vec2 parallax_uv;
+float parallax_depth;
+mat3 parallax_tbn;
+// The derivatives of the uv coordinates, since the height texture is sampled in non-uniform control flow
+vec2 parallax_uv_dx;
+vec2 parallax_uv_dy;
+
+// Returns the depth below the surface, in the range [0..1], of the height field at the given uv coordinates
+float sample_parallax_depth(vec2 uv)
+{
+    mat2 transformation = mat2(heightTexTransform);
+    return 1.0 - textureGrad(heightTexture, (heightTexTransform * vec3(uv, 1.0)).xy, transformation * parallax_uv_dx, transformation * parallax_uv_dy).r;
+}
+
+// Converts a tangent space direction to the direction in uv space scaled by the height scale per unit of depth
+vec2 parallax_direction(vec3 tangent_direction)
+{
+    return tangent_direction.xy / max(tangent_direction.z, 0.05) * heightScale;
+}
+
+// Marches along the view direction through the height field and returns the uv coordinates where the view ray hits the height field
+vec2 parallax_occlusion_mapping(vec2 uv, vec3 view_direction)
+{
+    float layer_depth = 1.0 / float(parallaxStepCount);
+    vec2 delta = parallax_direction(transpose(parallax_tbn) * view_direction) * layer_depth;
+    float current_layer = 0.0;
+    float current_depth = sample_parallax_depth(uv);
+    for (int i = 0; i < parallaxStepCount && current_layer < current_depth; i++) {
+        uv -= delta;
+        current_layer += layer_depth;
+        current_depth = sample_parallax_depth(uv);
+    }
+    // Interpolate between the last two steps to find the intersection
+    float after = current_depth - current_layer;
+    float before = sample_parallax_depth(uv + delta) - current_layer + layer_depth;
+    float weight = after / min(after - before, -0.0001);
+    parallax_depth = current_layer - weight * layer_depth;
+    return uv + weight * delta;
+}
+
+// Marches from the point found by the parallax occlusion mapping towards the light and returns the fraction of the light which is not blocked by the height field
+float parallax_shadow(vec3 L)
+{
+    vec3 tangent_light_direction = transpose(parallax_tbn) * L;
+    if (parallaxSelfShadowing == 0 || tangent_light_direction.z <= 0.0 || parallax_depth <= 0.0) {
+        return 1.0;
+    }
+    float layer_depth = parallax_depth / float(parallaxStepCount);
+    vec2 delta = parallax_direction(tangent_light_direction) * layer_depth;
+    vec2 uv = parallax_uv;
+    float current_layer = parallax_depth;
+    float occlusion = 0.0;
+    for (int i = 0; i < parallaxStepCount && current_layer > 0.0; i++) {
+        uv += delta;
+        current_layer -= layer_depth;
+        occlusion = max(occlusion, current_layer - sample_parallax_depth(uv));
+    }
+    return 1.0 - saturate(occlusion * float(parallaxStepCount));
+}
+#endif
+
 void main()
 {
+#ifdef USE_UVS
+    vec2 uv = uvs;
+#endif
+    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
+#if defined(USE_NORMAL_TEXTURE) || defined(USE_CLEARCOAT_NORMAL_TEXTURE) || defined(USE_HEIGHT_TEXTURE)
+    vec3 tangent = normalize(gl_FrontFacing ? tang : -tang);
+    vec3 bitangent = normalize(gl_FrontFacing ? bitang : -bitang);
+    mat3 tbn = mat3(tangent, bitangent, normal);
+#endif
+
+#ifdef USE_HEIGHT_TEXTURE
+    parallax_tbn = tbn;
+    parallax_uv_dx = dFdx(uv);
+    parallax_uv_dy = dFdy(uv);
+    parallax_uv = parallax_occlusion_mapping(uv, normalize(cameraPosition - pos));
+    uv = parallax_uv;
+#endif
+
     vec4 surface_color = albedo * col;
 #ifdef USE_ALBEDO_TEXTURE
-    vec4 c = texture(albedoTexture, (albedoTexTransform * vec3(uvs, 1.0)).xy);
-    #ifdef ALPHACUT
-        if (c.a < acut) discard;
-    #endif
+    vec4 c = texture(albedoTexture, (albedoTexTransform * vec3(uv, 1.0)).xy);
     surface_color *= c;
 #endif
+#ifdef ALPHACUT
+    if (surface_color.a < alphaCutout) discard;
+#endif
 
     float metallic_factor = metallic;
     float roughness_factor = roughness;
 #ifdef USE_METALLIC_ROUGHNESS_TEXTURE
-    vec2 t = texture(metallicRoughnessTexture, (metallicRoughnessTexTransform * vec3(uvs, 1.0)).xy).gb;
+    vec2 t = texture(metallicRoughnessTexture, (metallicRoughnessTexTransform * vec3(uv, 1.0)).xy).gb;
     roughness_factor *= t.x;
     metallic_factor *= t.y;
 #endif
 
     float occlusion = 1.0;
 #ifdef USE_OCCLUSION_TEXTURE
-    occlusion = mix(1.0, texture(occlusionTexture, (occlusionTexTransform * vec3(uvs, 1.0)).xy).r, occlusionStrength);
+    occlusion = mix(1.0, texture(occlusionTexture, (occlusionTexTransform * vec3(uv, 1.0)).xy).r, occlusionStrength);
+#endif
+
+#ifdef USE_CLEARCOAT
+    clearcoat_factor = clearcoatFactor;
+    clearcoat_roughness = clearcoatRoughness;
+    clearcoat_normal = normal;
+#ifdef USE_CLEARCOAT_NORMAL_TEXTURE
+    clearcoat_normal = normalize(tbn * ((2.0 * texture(clearcoatNormalTexture, (clearcoatNormalTexTransform * vec3(uv, 1.0)).xy).xyz - 1.0) * vec3(clearcoatNormalScale, clearcoatNormalScale, 1.0)));
+#endif
 #endif
 
-    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
 #ifdef USE_NORMAL_TEXTURE
-    vec3 tangent = normalize(gl_FrontFacing ? tang : -tang);
-    vec3 bitangent = normalize(gl_FrontFacing ? bitang : -bitang);
-    mat3 tbn = mat3(tangent, bitangent, normal);
-    normal = tbn * ((2.0 * texture(normalTexture, (normalTexTransform * vec3(uvs, 1.0)).xy).xyz - 1.0) * vec3(normalScale, normalScale, 1.0));
+    normal = tbn * ((2.0 * texture(normalTexture, (normalTexTransform * vec3(uv, 1.0)).xy).xyz - 1.0) * vec3(normalScale, normalScale, 1.0));
+#endif
+
+#ifdef USE_SHEEN
+    sheen_color = sheenColor;
+    sheen_roughness = sheenRoughness;
+#endif
+
+#ifdef USE_SPECULAR
+    dielectric_specular = dielectricSpecular;
 #endif
 
     vec3 total_emissive = emissive.rgb;
 #ifdef USE_EMISSIVE_TEXTURE
-    total_emissive *= texture(emissiveTexture, (emissiveTexTransform * vec3(uvs, 1.0)).xy).rgb;
+    total_emissive *= texture(emissiveTexture, (emissiveTexTransform * vec3(uv, 1.0)).xy).rgb;
+#endif
+
+#ifdef USE_TRANSMISSION
+    transmission_factor = transmission;
+#ifdef USE_TRANSMISSION_TEXTURE
+    transmission_factor *= texture(transmissionTexture, (transmissionTexTransform * vec3(uv, 1.0)).xy).r;
+#endif
 #endif
 
     outColor.rgb = total_emissive + calculate_lighting(cameraPosition, surface_color.rgb, pos, normal, metallic_factor, roughness_factor, occlusion);
+
+#ifdef USE_TRANSMISSION
+    vec3 view_direction = normalize(cameraPosition - pos);
+    vec3 transmitted_position = pos;
+    vec3 attenuation = vec3(1.0);
+#ifdef USE_VOLUME
+    // The light is refracted when entering the volume and absorbed on the way through the volume
+    vec3 refracted_direction = refract(-view_direction, normalize(normal), 1.0 / indexOfRefraction);
+    transmitted_position += refracted_direction * volumeThickness;
+    attenuation = pow(attenuationColor, vec3(volumeThickness / attenuationDistance));
+#endif
+    vec3 transmitted = vec3(0.0);
+#ifdef USE_TRANSMISSION_BACKGROUND
+    // The background is more blurred the more the light is refracted
+    transmitted = sample_transmission_background(transmitted_position, roughness_factor * clamp(2.0 * indexOfRefraction - 2.0, 0.0, 1.0));
+#endif
+    vec3 specular_fresnel = fresnel_schlick_roughness(specular_reflectance(surface_color.rgb, metallic_factor), max(0.001, dot(normalize(normal), view_direction)), roughness_factor);
+    outColor.rgb += transmission_factor * (1.0 - metallic_factor) * (1.0 - specular_fresnel) * surface_color.rgb * attenuation * transmitted;
+#endif
     outColor.rgb = tone_mapping(outColor.rgb);
     outColor.rgb = color_mapping(outColor.rgb);
     outColor.a = surface_color.a;
@@ -0,0 +1,15 @@
+
+uniform vec3 shadowColor;
+uniform float opacity;
+
+in vec3 pos;
+in vec3 nor;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
+    outColor = vec4(shadowColor, opacity * calculate_shadow_opacity(pos, normal));
+    outColor.rgb = color_mapping(outColor.rgb);
+}
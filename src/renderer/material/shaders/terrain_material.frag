@@ -0,0 +1,136 @@
+uniform vec3 cameraPosition;
+
+uniform int layerCount;
+uniform vec4 layerAlbedo[MAX_LAYERS];
+// The metallic, roughness, normal scale and tiling of each layer
+uniform vec4 layerParameters[MAX_LAYERS];
+// The min, max and blend of the height and slope ranges of each layer and whether or not the range is used
+uniform vec4 layerHeightRange[MAX_LAYERS];
+uniform vec4 layerSlopeRange[MAX_LAYERS];
+
+uniform float detailFadeStart;
+uniform float detailFadeEnd;
+uniform float distantTilingFactor;
+
+#ifdef USE_ALBEDO_TEXTURES
+uniform sampler2DArray albedoTextures;
+#endif
+
+#ifdef USE_NORMAL_TEXTURES
+uniform sampler2DArray normalTextures;
+#endif
+
+#ifdef USE_ROUGHNESS_TEXTURES
+uniform sampler2DArray roughnessTextures;
+#endif
+
+#ifdef USE_SPLAT_MAPS
+uniform sampler2DArray splatMaps;
+uniform int splatMapCount;
+uniform vec2 splatMapOffset;
+uniform vec2 splatMapSize;
+#endif
+
+in vec3 pos;
+in vec3 nor;
+
+layout (location = 0) out vec4 outColor;
+
+// The world space texture coordinates and their derivatives, which are used for sampling inside the non-uniform control flow of the layer loop
+vec2 uvs;
+vec2 uvs_dx;
+vec2 uvs_dy;
+// How much the distant textures are used instead of the detailed textures
+float detail_fade;
+
+float range_weight(float value, vec4 range)
+{
+    if (range.w < 0.5) {
+        return 1.0;
+    }
+    float blend = max(range.z, 0.0001);
+    return saturate((value - range.x) / blend + 0.5) * saturate((range.y - value) / blend + 0.5);
+}
+
+vec4 sample_layer(sampler2DArray textures, int layer, float tiling)
+{
+    vec4 detail = textureGrad(textures, vec3(uvs * tiling, float(layer)), uvs_dx * tiling, uvs_dy * tiling);
+    float distant_tiling = tiling * distantTilingFactor;
+    vec4 distant = textureGrad(textures, vec3(uvs * distant_tiling, float(layer)), uvs_dx * distant_tiling, uvs_dy * distant_tiling);
+    return mix(detail, distant, detail_fade);
+}
+
+void main()
+{
+    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
+    uvs = pos.xz;
+    uvs_dx = dFdx(uvs);
+    uvs_dy = dFdy(uvs);
+    detail_fade = smoothstep(detailFadeStart, detailFadeEnd, distance(cameraPosition, pos));
+
+    // The weights of the layers from the splat maps and the height and slope rules, where the layers with a rule cover the layers before them
+    float height = pos.y;
+    float slope = acos(clamp(normal.y, -1.0, 1.0));
+    float weights[MAX_LAYERS];
+    float total_weight = 0.0;
+    float coverage = 1.0;
+    for (int i = layerCount - 1; i >= 0; i--) {
+        float weight = range_weight(height, layerHeightRange[i]) * range_weight(slope, layerSlopeRange[i]);
+#ifdef USE_SPLAT_MAPS
+        if (i / 4 < splatMapCount) {
+            vec2 splat_uvs = (uvs - splatMapOffset) / splatMapSize;
+            weight *= texture(splatMaps, vec3(splat_uvs, float(i / 4)))[i % 4];
+        }
+#endif
+        weights[i] = weight * coverage;
+        total_weight += weights[i];
+        if (layerHeightRange[i].w > 0.5 || layerSlopeRange[i].w > 0.5) {
+            coverage *= 1.0 - weight;
+        }
+    }
+    if (total_weight <= 0.0) {
+        weights[0] = 1.0;
+        total_weight = 1.0;
+    }
+
+    // The tangent frame of the projection from above, where the tangent and bitangent are the directions in which u and v increases, ie. along x and z
+    vec3 tangent = normalize(vec3(1.0, 0.0, 0.0) - normal * normal.x);
+    vec3 bitangent = cross(tangent, normal);
+
+    vec4 surface_color = vec4(0.0);
+    float metallic_factor = 0.0;
+    float roughness_factor = 0.0;
+    vec3 tangent_normal = vec3(0.0);
+    for (int i = 0; i < layerCount; i++) {
+        float weight = weights[i] / total_weight;
+        if (weight <= 0.0) {
+            continue;
+        }
+        vec4 parameters = layerParameters[i];
+        vec4 color = layerAlbedo[i];
+#ifdef USE_ALBEDO_TEXTURES
+        color *= sample_layer(albedoTextures, i, parameters.w);
+#endif
+        float roughness = parameters.y;
+#ifdef USE_ROUGHNESS_TEXTURES
+        roughness *= sample_layer(roughnessTextures, i, parameters.w).g;
+#endif
+#ifdef USE_NORMAL_TEXTURES
+        vec2 n = textureGrad(normalTextures, vec3(uvs * parameters.w, float(i)), uvs_dx * parameters.w, uvs_dy * parameters.w).xy;
+        tangent_normal.xy += weight * parameters.z * (2.0 * n - 1.0);
+#endif
+        surface_color += weight * color;
+        metallic_factor += weight * parameters.x;
+        roughness_factor += weight * roughness;
+    }
+
+#ifdef USE_NORMAL_TEXTURES
+    tangent_normal.xy *= 1.0 - detail_fade;
+    normal = normalize(normal + tangent_normal.x * tangent + tangent_normal.y * bitangent);
+#endif
+
+    outColor.rgb = calculate_lighting(cameraPosition, surface_color.rgb, pos, normal, metallic_factor, roughness_factor, 1.0);
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    outColor.a = surface_color.a;
+}
@@ -0,0 +1,39 @@
+uniform vec3 cameraPosition;
+uniform float bands;
+uniform vec3 specularColor;
+uniform float glossiness;
+uniform vec3 rimColor;
+uniform float rimAmount;
+
+uniform vec4 albedo;
+#ifdef USE_ALBEDO_TEXTURE
+uniform sampler2D albedoTexture;
+uniform mat3 albedoTexTransform;
+#endif
+
+in vec3 pos;
+in vec3 nor;
+in vec4 col;
+
+layout (location = 0) out vec4 outColor;
+
+void main()
+{
+    vec4 surface_color = albedo * col;
+#ifdef USE_ALBEDO_TEXTURE
+    surface_color *= texture(albedoTexture, (albedoTexTransform * vec3(uvs, 1.0)).xy);
+#endif
+
+    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
+
+    toon_bands = bands;
+    toon_specular_color = specularColor;
+    toon_glossiness = glossiness;
+    toon_rim_color = rimColor;
+    toon_rim_amount = rimAmount;
+
+    outColor.rgb = calculate_lighting(cameraPosition, surface_color.rgb, pos, normal, 0.0, 1.0, 1.0);
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    outColor.a = surface_color.a;
+}
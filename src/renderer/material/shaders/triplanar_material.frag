@@ -0,0 +1,98 @@
+uniform float metallic;
+uniform float roughness;
+uniform vec3 cameraPosition;
+
+uniform mat4 triplanarTransformation;
+uniform float textureScale;
+uniform float blendSharpness;
+
+uniform vec4 albedo;
+#ifdef USE_ALBEDO_TEXTURE
+uniform sampler2D albedoTexture;
+uniform mat3 albedoTexTransform;
+#endif
+
+#ifdef USE_METALLIC_ROUGHNESS_TEXTURE
+uniform sampler2D metallicRoughnessTexture;
+uniform mat3 metallicRoughnessTexTransform;
+#endif
+
+#ifdef USE_NORMAL_TEXTURE
+uniform sampler2D normalTexture;
+uniform mat3 normalTexTransform;
+uniform float normalScale;
+#endif
+
+in vec3 pos;
+in vec3 nor;
+
+layout (location = 0) out vec4 outColor;
+
+// The texture coordinates of the projections along the x, y and z axes
+vec2 uvs_x;
+vec2 uvs_y;
+vec2 uvs_z;
+// The blend weights of the projections along the x, y and z axes
+vec3 weights;
+
+vec4 sample_triplanar(sampler2D tex, mat3 transformation)
+{
+    return texture(tex, (transformation * vec3(uvs_x, 1.0)).xy) * weights.x
+        + texture(tex, (transformation * vec3(uvs_y, 1.0)).xy) * weights.y
+        + texture(tex, (transformation * vec3(uvs_z, 1.0)).xy) * weights.z;
+}
+
+void main()
+{
+    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);
+
+    // Project the position and normal into the space in which the textures are projected along the axes
+    mat3 normal_transformation = transpose(inverse(mat3(triplanarTransformation)));
+    vec3 position = (triplanarTransformation * vec4(pos, 1.0)).xyz * textureScale;
+    vec3 projection_normal = normalize(normal_transformation * normal);
+    weights = pow(abs(projection_normal), vec3(blendSharpness));
+    weights /= weights.x + weights.y + weights.z;
+
+    // The tangents and bitangents of each projection are the directions in which u and v increases,
+    // and are chosen such that the textures are upright and not mirrored when seen from outside
+    vec3 axis_sign = sign(projection_normal + 0.0001);
+    vec3 tangent_x = vec3(0.0, 0.0, -axis_sign.x);
+    vec3 tangent_y = vec3(axis_sign.y, 0.0, 0.0);
+    vec3 tangent_z = vec3(axis_sign.z, 0.0, 0.0);
+    vec3 bitangent_x = vec3(0.0, -1.0, 0.0);
+    vec3 bitangent_y = vec3(0.0, 0.0, 1.0);
+    vec3 bitangent_z = vec3(0.0, -1.0, 0.0);
+    uvs_x = vec2(dot(position, tangent_x), dot(position, bitangent_x));
+    uvs_y = vec2(dot(position, tangent_y), dot(position, bitangent_y));
+    uvs_z = vec2(dot(position, tangent_z), dot(position, bitangent_z));
+
+    vec4 surface_color = albedo;
+#ifdef USE_ALBEDO_TEXTURE
+    surface_color *= sample_triplanar(albedoTexture, albedoTexTransform);
+#endif
+
+    float metallic_factor = metallic;
+    float roughness_factor = roughness;
+#ifdef USE_METALLIC_ROUGHNESS_TEXTURE
+    vec2 t = sample_triplanar(metallicRoughnessTexture, metallicRoughnessTexTransform).gb;
+    roughness_factor *= t.x;
+    metallic_factor *= t.y;
+#endif
+
+#ifdef USE_NORMAL_TEXTURE
+    // Perturb the surface normal by the blended tangential parts of the normals from each projection
+    vec2 normal_x = normalScale * (2.0 * texture(normalTexture, (normalTexTransform * vec3(uvs_x, 1.0)).xy).xy - 1.0);
+    vec2 normal_y = normalScale * (2.0 * texture(normalTexture, (normalTexTransform * vec3(uvs_y, 1.0)).xy).xy - 1.0);
+    vec2 normal_z = normalScale * (2.0 * texture(normalTexture, (normalTexTransform * vec3(uvs_z, 1.0)).xy).xy - 1.0);
+    projection_normal = normalize(projection_normal
+        + weights.x * (normal_x.x * tangent_x + normal_x.y * bitangent_x)
+        + weights.y * (normal_y.x * tangent_y + normal_y.y * bitangent_y)
+        + weights.z * (normal_z.x * tangent_z + normal_z.y * bitangent_z));
+    normal = normalize(transpose(mat3(triplanarTransformation)) * projection_normal);
+#endif
+
+    outColor.rgb = calculate_lighting(cameraPosition, surface_color.rgb, pos, normal, metallic_factor, roughness_factor, 1.0);
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    outColor.a = surface_color.a;
+}
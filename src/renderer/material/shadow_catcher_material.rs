@@ -0,0 +1,106 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// A material that is invisible except for the shadows it receives, which is for example used for a ground plane when compositing objects onto a photo.
+/// The output is a transparent color where the opacity is the fraction of the light which is blocked by shadow casting objects,
+/// so it composites correctly both onto a background rendered before this material and onto a render target cleared to a transparent color.
+///
+/// Only the lights which support it contributes to the shadows, see [Light::incoming_light_shader_source],
+/// and only lights with a shadow map casts shadows, see for example [DirectionalLight::generate_shadow_map].
+/// Lights without a shadow map makes the shadows of the other lights lighter, since they also light the shadowed areas.
+///
+#[derive(Clone, Debug)]
+pub struct ShadowCatcherMaterial {
+    /// The color of the shadows.
+    pub color: Srgba,
+    /// The opacity of an area where all of the light is blocked.
+    pub opacity: f32,
+    /// Render states.
+    pub render_states: RenderStates,
+}
+
+impl Default for ShadowCatcherMaterial {
+    fn default() -> Self {
+        Self {
+            color: Srgba::BLACK,
+            opacity: 1.0,
+            render_states: RenderStates {
+                write_mask: WriteMask::COLOR,
+                // The alpha channel is blended such that the shadows are also visible on a transparent background
+                blend: Blend::Enabled {
+                    source_rgb_multiplier: BlendMultiplierType::SrcAlpha,
+                    source_alpha_multiplier: BlendMultiplierType::One,
+                    destination_rgb_multiplier: BlendMultiplierType::OneMinusSrcAlpha,
+                    destination_alpha_multiplier: BlendMultiplierType::OneMinusSrcAlpha,
+                    rgb_equation: BlendEquationType::Add,
+                    alpha_equation: BlendEquationType::Add,
+                },
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl Material for ShadowCatcherMaterial {
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::ShadowCatcherMaterial
+    }
+
+    fn fragment_shader_source(&self, lights: &[&dyn Light]) -> String {
+        let (mut source, incoming_lights) = incoming_lights_shader_source(lights);
+        source.push_str(ColorMapping::fragment_shader_source());
+        let light_sum = (0..incoming_lights.len())
+            .map(|i| format!(
+                "
+                    irradiance = dot(calculate_incoming_light{i}(position, normal, light_direction, visibility), vec3(0.2126, 0.7152, 0.0722))
+                        * max(dot(normal, light_direction), 0.0);
+                    total += irradiance;
+                    visible += irradiance * visibility;
+                "
+            ))
+            .collect::<String>();
+        source.push_str(&format!(
+            "
+                float calculate_shadow_opacity(vec3 position, vec3 normal)
+                {{
+                    float total = 0.0;
+                    float visible = 0.0;
+                    float irradiance;
+                    vec3 light_direction;
+                    float visibility;
+                    {light_sum}
+                    return total > 0.0 ? 1.0 - visible / total : 0.0;
+                }}
+            "
+        ));
+        source.push_str(include_str!("shaders/shadow_catcher_material.frag"));
+        source
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            position: true,
+            normal: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        camera.color_mapping.use_uniforms(program);
+        program.use_uniform("shadowColor", self.color.to_linear_srgb().truncate());
+        program.use_uniform("opacity", self.opacity);
+        let (_, incoming_lights) = incoming_lights_shader_source(lights);
+        for (i, light) in incoming_lights.iter().enumerate() {
+            light.use_uniforms(program, i as u32);
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        self.render_states
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Transparent
+    }
+}
@@ -0,0 +1,518 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// The value of a uniform variable of a [SnippetMaterial] or a [VertexDeformation], which determines the GLSL type of the uniform.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnippetUniform {
+    /// `uniform float`
+    Float(f32),
+    /// `uniform int`
+    Int(i32),
+    /// `uniform vec2`
+    Vec2(Vec2),
+    /// `uniform vec3`
+    Vec3(Vec3),
+    /// `uniform vec4`
+    Vec4(Vec4),
+    /// `uniform mat3`
+    Mat3(Mat3),
+    /// `uniform mat4`
+    Mat4(Mat4),
+}
+
+impl SnippetUniform {
+    pub(crate) fn glsl_type(&self) -> &'static str {
+        match self {
+            Self::Float(_) => "float",
+            Self::Int(_) => "int",
+            Self::Vec2(_) => "vec2",
+            Self::Vec3(_) => "vec3",
+            Self::Vec4(_) => "vec4",
+            Self::Mat3(_) => "mat3",
+            Self::Mat4(_) => "mat4",
+        }
+    }
+
+    pub(crate) fn use_uniform(&self, program: &Program, name: &str) {
+        match *self {
+            Self::Float(value) => program.use_uniform_if_required(name, value),
+            Self::Int(value) => program.use_uniform_if_required(name, value),
+            Self::Vec2(value) => program.use_uniform_if_required(name, value),
+            Self::Vec3(value) => program.use_uniform_if_required(name, value),
+            Self::Vec4(value) => program.use_uniform_if_required(name, value),
+            Self::Mat3(value) => program.use_uniform_if_required(name, value),
+            Self::Mat4(value) => program.use_uniform_if_required(name, value),
+        }
+    }
+}
+
+impl From<f32> for SnippetUniform {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<i32> for SnippetUniform {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<Vec2> for SnippetUniform {
+    fn from(value: Vec2) -> Self {
+        Self::Vec2(value)
+    }
+}
+
+impl From<Vec3> for SnippetUniform {
+    fn from(value: Vec3) -> Self {
+        Self::Vec3(value)
+    }
+}
+
+impl From<Vec4> for SnippetUniform {
+    fn from(value: Vec4) -> Self {
+        Self::Vec4(value)
+    }
+}
+
+impl From<Mat3> for SnippetUniform {
+    fn from(value: Mat3) -> Self {
+        Self::Mat3(value)
+    }
+}
+
+impl From<Mat4> for SnippetUniform {
+    fn from(value: Mat4) -> Self {
+        Self::Mat4(value)
+    }
+}
+
+/// A color is converted to a `vec4` in linear sRGB color space.
+impl From<Srgba> for SnippetUniform {
+    fn from(value: Srgba) -> Self {
+        Self::Vec4(value.to_linear_srgb())
+    }
+}
+
+///
+/// A builder for a [SnippetMaterial], which composes GLSL snippets into a material that uses the built-in lighting, tone mapping and color mapping.
+///
+/// The surface inputs, ie. [albedo](SnippetMaterialBuilder::albedo), [metallic](SnippetMaterialBuilder::metallic), [roughness](SnippetMaterialBuilder::roughness),
+/// [occlusion](SnippetMaterialBuilder::occlusion), [normal](SnippetMaterialBuilder::normal) and [emissive](SnippetMaterialBuilder::emissive), are GLSL expressions which can use
+/// - the world space position `pos` and the normal `normal`, which is the normalized geometry normal facing the camera,
+/// - the vertex attributes `uvs` (`vec2`), `col` (`vec4`), `tang` and `bitang` (`vec3`), which are automatically required from the [Geometry] when used,
+/// - the camera position `cameraPosition`,
+/// - the [uniforms](SnippetMaterialBuilder::uniform) and [textures](SnippetMaterialBuilder::texture) added to the builder,
+/// - the [functions](SnippetMaterialBuilder::function) and [variables](SnippetMaterialBuilder::variable) added to the builder, where a variable can use the variables added before it.
+///
+/// ```no_run
+/// # use three_d::*;
+/// # let texture: Texture2DRef = unimplemented!();
+/// let material = SnippetMaterialBuilder::new()
+///     .uniform("tint", Srgba::new(255, 200, 150, 255))
+///     .texture("stripeTexture", texture)
+///     .function("float stripes(vec2 uv) { return step(0.5, fract(uv.x * 10.0)); }")
+///     .variable("float", "stripe", "stripes(uvs)")
+///     .albedo("tint * texture(stripeTexture, uvs) * (0.5 + 0.5 * stripe)")
+///     .roughness("mix(0.2, 0.8, stripe)")
+///     .build();
+/// ```
+///
+#[derive(Clone)]
+pub struct SnippetMaterialBuilder {
+    material: SnippetMaterial,
+}
+
+impl SnippetMaterialBuilder {
+    ///
+    /// Creates a new builder for a white, non-metallic and rough material.
+    ///
+    pub fn new() -> Self {
+        Self {
+            material: SnippetMaterial {
+                source_id: 0,
+                uniforms: Vec::new(),
+                textures: Vec::new(),
+                functions: Vec::new(),
+                variables: Vec::new(),
+                albedo: "vec4(1.0)".to_string(),
+                metallic: "0.0".to_string(),
+                roughness: "1.0".to_string(),
+                occlusion: "1.0".to_string(),
+                normal: "normal".to_string(),
+                emissive: "vec3(0.0)".to_string(),
+                vertex_deformation: None,
+                lighting_model: LightingModel::Blinn,
+                render_states: RenderStates::default(),
+                material_type: MaterialType::Opaque,
+            },
+        }
+    }
+
+    ///
+    /// Adds a uniform variable with the given name, which is available in the snippets with the GLSL type given by the value.
+    /// The value can be changed after the material is built using [SnippetMaterial::set_uniform].
+    ///
+    pub fn uniform(mut self, name: &str, value: impl Into<SnippetUniform>) -> Self {
+        self.material
+            .uniforms
+            .push((name.to_string(), value.into()));
+        self
+    }
+
+    ///
+    /// Adds a texture with the given name, which is available in the snippets as a `sampler2D` with that name.
+    /// The [Texture2DRef::transformation] is available as a `mat3` with the name followed by `Transform`, for example `uniform mat3 detailTransform;` for a texture named `detail`.
+    /// The texture can be changed after the material is built using [SnippetMaterial::set_texture].
+    ///
+    pub fn texture(mut self, name: &str, texture: impl Into<Texture2DRef>) -> Self {
+        self.material
+            .textures
+            .push((name.to_string(), texture.into()));
+        self
+    }
+
+    ///
+    /// Adds GLSL source code defining one or more functions which can be called from the snippets.
+    ///
+    pub fn function(mut self, source: &str) -> Self {
+        self.material.functions.push(source.to_string());
+        self
+    }
+
+    ///
+    /// Adds a variable with the given GLSL type and name which is assigned the given GLSL expression.
+    /// The variable can be used by the variables added after it and by the surface inputs.
+    ///
+    pub fn variable(mut self, glsl_type: &str, name: &str, expression: &str) -> Self {
+        self.material.variables.push((
+            glsl_type.to_string(),
+            name.to_string(),
+            expression.to_string(),
+        ));
+        self
+    }
+
+    ///
+    /// Sets the GLSL expression of type `vec4` for the albedo base color, also called diffuse color, in linear color space including the alpha value. The default is `vec4(1.0)`.
+    ///
+    pub fn albedo(mut self, expression: &str) -> Self {
+        self.material.albedo = expression.to_string();
+        self
+    }
+
+    ///
+    /// Sets the GLSL expression of type `float` for how metallic the surface is, in the range `[0..1]`. The default is `0.0`.
+    ///
+    pub fn metallic(mut self, expression: &str) -> Self {
+        self.material.metallic = expression.to_string();
+        self
+    }
+
+    ///
+    /// Sets the GLSL expression of type `float` for how rough the surface is, in the range `[0..1]`. The default is `1.0`.
+    ///
+    pub fn roughness(mut self, expression: &str) -> Self {
+        self.material.roughness = expression.to_string();
+        self
+    }
+
+    ///
+    /// Sets the GLSL expression of type `float` for the ambient occlusion, in the range `[0..1]` where 0 is fully occluded. The default is `1.0`.
+    ///
+    pub fn occlusion(mut self, expression: &str) -> Self {
+        self.material.occlusion = expression.to_string();
+        self
+    }
+
+    ///
+    /// Sets the GLSL expression of type `vec3` for the surface normal in world space, which is normalized before use. The default is `normal`, ie. the geometry normal.
+    ///
+    pub fn normal(mut self, expression: &str) -> Self {
+        self.material.normal = expression.to_string();
+        self
+    }
+
+    ///
+    /// Sets the GLSL expression of type `vec3` for the light emitted from the surface in linear color space, which is added to the reflected light. The default is `vec3(0.0)`.
+    ///
+    pub fn emissive(mut self, expression: &str) -> Self {
+        self.material.emissive = expression.to_string();
+        self
+    }
+
+    ///
+    /// Sets the [VertexDeformation] which deforms the vertices of the geometry rendered with the material. The default is no deformation.
+    ///
+    pub fn vertex_deformation(mut self, vertex_deformation: VertexDeformation) -> Self {
+        self.material.vertex_deformation = Some(vertex_deformation);
+        self
+    }
+
+    ///
+    /// Sets the lighting model used when rendering the material. The default is [LightingModel::Blinn].
+    ///
+    pub fn lighting_model(mut self, lighting_model: LightingModel) -> Self {
+        self.material.lighting_model = lighting_model;
+        self
+    }
+
+    ///
+    /// Sets the render states of the material. The default is [RenderStates::default].
+    ///
+    pub fn render_states(mut self, render_states: RenderStates) -> Self {
+        self.material.render_states = render_states;
+        self
+    }
+
+    ///
+    /// Sets the material type, which should be [MaterialType::Transparent] if the albedo alpha value is less than one. The default is [MaterialType::Opaque].
+    ///
+    /// # Panic
+    /// Will panic if the material type is [MaterialType::Deferred] or [MaterialType::Transmissive], since the material is always rendered in a forward pass.
+    ///
+    pub fn material_type(mut self, material_type: MaterialType) -> Self {
+        if material_type == MaterialType::Deferred || material_type == MaterialType::Transmissive {
+            panic!(
+                "a snippet material can only be opaque or transparent, not {:?}",
+                material_type
+            );
+        }
+        self.material.material_type = material_type;
+        self
+    }
+
+    ///
+    /// Builds the material and derives its [Material::source_id] from the snippets, uniform and texture declarations and lighting model,
+    /// such that materials built from the same snippets share the same compiled shader program.
+    ///
+    pub fn build(self) -> SnippetMaterial {
+        let mut material = self.material;
+        material.source_id = fnv1a_hash(material.fragment_shader_source(&[]).as_bytes());
+        material
+    }
+}
+
+impl Default for SnippetMaterialBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// A material composed of GLSL snippets using a [SnippetMaterialBuilder], which makes it possible to create a custom material without writing a full fragment shader.
+/// The material is affected by lights in the same way as a [PhysicalMaterial].
+///
+#[derive(Clone)]
+pub struct SnippetMaterial {
+    source_id: u64,
+    uniforms: Vec<(String, SnippetUniform)>,
+    textures: Vec<(String, Texture2DRef)>,
+    functions: Vec<String>,
+    variables: Vec<(String, String, String)>,
+    albedo: String,
+    metallic: String,
+    roughness: String,
+    occlusion: String,
+    normal: String,
+    emissive: String,
+    vertex_deformation: Option<VertexDeformation>,
+    lighting_model: LightingModel,
+    render_states: RenderStates,
+    material_type: MaterialType,
+}
+
+impl SnippetMaterial {
+    ///
+    /// Sets the value of the uniform with the given name which was added using [SnippetMaterialBuilder::uniform].
+    ///
+    /// # Panic
+    /// Will panic if no uniform with the given name was added or if the value is not of the same type as the value given to the builder.
+    ///
+    pub fn set_uniform(&mut self, name: &str, value: impl Into<SnippetUniform>) {
+        let value = value.into();
+        let uniform = self
+            .uniforms
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .unwrap_or_else(|| panic!("the snippet material has no uniform named {}", name));
+        if uniform.1.glsl_type() != value.glsl_type() {
+            panic!(
+                "the uniform {} is of type {} and cannot be set to a value of type {}",
+                name,
+                uniform.1.glsl_type(),
+                value.glsl_type()
+            );
+        }
+        uniform.1 = value;
+    }
+
+    ///
+    /// Sets the texture with the given name which was added using [SnippetMaterialBuilder::texture].
+    ///
+    /// # Panic
+    /// Will panic if no texture with the given name was added.
+    ///
+    pub fn set_texture(&mut self, name: &str, texture: impl Into<Texture2DRef>) {
+        self.textures
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .unwrap_or_else(|| panic!("the snippet material has no texture named {}", name))
+            .1 = texture.into();
+    }
+
+    ///
+    /// Returns a mutable reference to the [VertexDeformation] given to [SnippetMaterialBuilder::vertex_deformation], for example to update its uniforms.
+    ///
+    pub fn vertex_deformation_mut(&mut self) -> Option<&mut VertexDeformation> {
+        self.vertex_deformation.as_mut()
+    }
+
+    ///
+    /// Sets the render states.
+    ///
+    pub fn set_render_states(&mut self, render_states: RenderStates) {
+        self.render_states = render_states;
+    }
+
+    fn snippets(&self) -> impl Iterator<Item = &str> {
+        self.functions
+            .iter()
+            .map(|f| f.as_str())
+            .chain(self.variables.iter().map(|(_, _, e)| e.as_str()))
+            .chain([
+                self.albedo.as_str(),
+                self.metallic.as_str(),
+                self.roughness.as_str(),
+                self.occlusion.as_str(),
+                self.normal.as_str(),
+                self.emissive.as_str(),
+            ])
+    }
+
+    fn uses_identifier(&self, identifier: &str) -> bool {
+        self.snippets().any(|snippet| {
+            snippet
+                .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .any(|token| token == identifier)
+        })
+    }
+}
+
+impl Material for SnippetMaterial {
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::SnippetMaterial
+    }
+
+    fn source_id(&self) -> Option<u64> {
+        Some(self.source_id)
+    }
+
+    fn fragment_shader_source(&self, lights: &[&dyn Light]) -> String {
+        let attributes = self.fragment_attributes();
+        let mut output = lights_shader_source(lights, self.lighting_model);
+        output.push_str(ToneMapping::fragment_shader_source());
+        output.push_str(ColorMapping::fragment_shader_source());
+        output.push_str("uniform vec3 cameraPosition;\n");
+        for (name, value) in self.uniforms.iter() {
+            output.push_str(&format!("uniform {} {};\n", value.glsl_type(), name));
+        }
+        for (name, _) in self.textures.iter() {
+            output.push_str(&format!(
+                "uniform sampler2D {name};\nuniform mat3 {name}Transform;\n"
+            ));
+        }
+        output.push_str("in vec3 pos;\nin vec3 nor;\n");
+        if attributes.uv {
+            output.push_str("in vec2 uvs;\n");
+        }
+        if attributes.color {
+            output.push_str("in vec4 col;\n");
+        }
+        if attributes.tangents {
+            output.push_str("in vec3 tang;\nin vec3 bitang;\n");
+        }
+        output.push_str("layout (location = 0) out vec4 outColor;\n");
+        for function in self.functions.iter() {
+            output.push_str(function);
+            output.push('\n');
+        }
+        output.push_str("void main()\n{\n");
+        output.push_str("    vec3 normal = normalize(gl_FrontFacing ? nor : -nor);\n");
+        for (glsl_type, name, expression) in self.variables.iter() {
+            output.push_str(&format!("    {} {} = {};\n", glsl_type, name, expression));
+        }
+        output.push_str(&format!(
+            "    vec4 surface_color = {};
+    float metallic_factor = {};
+    float roughness_factor = {};
+    float occlusion_factor = {};
+    vec3 emissive_color = {};
+    normal = normalize({});
+    outColor.rgb = calculate_lighting(cameraPosition, surface_color.rgb, pos, normal, metallic_factor, roughness_factor, occlusion_factor) + emissive_color;
+    outColor.rgb = tone_mapping(outColor.rgb);
+    outColor.rgb = color_mapping(outColor.rgb);
+    outColor.a = surface_color.a;
+}}
+",
+            self.albedo,
+            self.metallic,
+            self.roughness,
+            self.occlusion,
+            self.emissive,
+            self.normal
+        ));
+        output
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            position: true,
+            normal: true,
+            uv: self.uses_identifier("uvs"),
+            color: self.uses_identifier("col"),
+            tangents: self.uses_identifier("tang") || self.uses_identifier("bitang"),
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        program.use_uniform_if_required("cameraPosition", camera.position());
+        for (i, light) in lights.iter().enumerate() {
+            light.use_uniforms(program, i as u32);
+        }
+        for (name, value) in self.uniforms.iter() {
+            value.use_uniform(program, name);
+        }
+        for (name, texture) in self.textures.iter() {
+            if program.requires_uniform(name) {
+                program
+                    .use_uniform_if_required(&format!("{}Transform", name), texture.transformation);
+                program.use_texture(name, texture);
+            }
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        self.render_states
+    }
+
+    fn material_type(&self) -> MaterialType {
+        self.material_type
+    }
+
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        self.vertex_deformation.clone()
+    }
+}
+
+/// The 64 bit FNV-1a hash, which unlike the hash of the standard library is stable between compilations.
+pub(crate) fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,222 @@
+use crate::core::*;
+use crate::renderer::*;
+use std::sync::Arc;
+
+///
+/// A layer of a [TerrainMaterial], for example grass, rock or snow.
+/// The textures of the layer are the layers with the same index in the texture arrays of the [TerrainMaterial].
+///
+#[derive(Clone, Debug)]
+pub struct TerrainLayer {
+    /// Albedo base color, also called diffuse color, which is multiplied with the color from the [TerrainMaterial::albedo_textures].
+    pub albedo: Srgba,
+    /// A value in the range `[0..1]` specifying how metallic the surface is.
+    pub metallic: f32,
+    /// A value in the range `[0..1]` specifying how rough the surface is, which is multiplied with the value from the [TerrainMaterial::roughness_textures].
+    pub roughness: f32,
+    /// A scalar multiplier applied to each normal vector of the [TerrainMaterial::normal_textures].
+    pub normal_scale: f32,
+    /// The number of times the textures of this layer are repeated per unit length.
+    pub tiling: f32,
+    /// The range of heights, ie. the y coordinate of the surface, in which this layer is applied. `None` means that the layer is applied at all heights.
+    pub height_range: Option<(f32, f32)>,
+    /// The distance over which the layer fades in and out at the boundaries of the [Self::height_range].
+    pub height_blend: f32,
+    /// The range of slopes, ie. the angle between the surface normal and the up direction, in which this layer is applied. `None` means that the layer is applied at all slopes.
+    pub slope_range: Option<(Radians, Radians)>,
+    /// The angle over which the layer fades in and out at the boundaries of the [Self::slope_range].
+    pub slope_blend: Radians,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            albedo: Srgba::WHITE,
+            metallic: 0.0,
+            roughness: 1.0,
+            normal_scale: 1.0,
+            tiling: 1.0,
+            height_range: None,
+            height_blend: 1.0,
+            slope_range: None,
+            slope_blend: degrees(5.0).into(),
+        }
+    }
+}
+
+///
+/// A physically-based material for rendering a [Terrain] which blends a number of [TerrainLayer]s, for example grass on the flat parts, rock on the steep parts and snow on the peaks.
+/// The weight of each layer is the product of the weight in the [splat maps](TerrainMaterial::splat_maps) and the weights from the [height](TerrainLayer::height_range) and [slope](TerrainLayer::slope_range) rules of the layer.
+/// A layer with a height or slope rule covers the layers before it according to its weight, so the order of the layers matters, for example the rock layer applied on steep slopes should come after the grass layer.
+/// Finally, the weights of all layers are normalized such that they sum to one, which means that layers without rules are blended according to the splat maps. If all weights are zero, the first layer is used.
+///
+/// The textures are projected onto the geometry from above, ie. the texture coordinates are the `(x, z)` world coordinates, so only the `position` and `normal` [FragmentAttributes] are required.
+/// To avoid visible repetition and aliasing far from the camera, where the [Terrain] is often rendered with a low level of detail [Lod],
+/// the textures are faded into a version with a lower [tiling](TerrainMaterial::distant_tiling_factor) and the normal maps are faded out between [TerrainMaterial::detail_fade_start] and [TerrainMaterial::detail_fade_end].
+///
+#[derive(Clone)]
+pub struct TerrainMaterial {
+    /// The layers. Only the first [TerrainMaterial::MAX_LAYERS] layers are used.
+    pub layers: Vec<TerrainLayer>,
+    /// Texture array with the albedo base colors of each layer, also called diffuse color.
+    /// The colors are assumed to be in linear sRGB (`RgbU8`), linear sRGB with an alpha channel (`RgbaU8`) or HDR color space.
+    pub albedo_textures: Option<Arc<Texture2DArray>>,
+    /// Texture array with the tangent space normal maps of each layer.
+    pub normal_textures: Option<Arc<Texture2DArray>>,
+    /// Texture array with the roughness of each layer which is sampled from the green channel, such that metallic roughness textures can be used.
+    pub roughness_textures: Option<Arc<Texture2DArray>>,
+    /// Texture array where each layer contains the weights of four [TerrainLayer]s in the red, green, blue and alpha channels,
+    /// ie. the first texture contains the weights of the first four terrain layers and so on.
+    /// Terrain layers without a weight in the splat maps are not affected by the splat maps.
+    pub splat_maps: Option<Arc<Texture2DArray>>,
+    /// The `(x, z)` world coordinates of the corner of the area covered by the [Self::splat_maps].
+    pub splat_map_offset: Vec2,
+    /// The size of the area covered by the [Self::splat_maps] in world coordinates.
+    pub splat_map_size: Vec2,
+    /// The distance from the camera where the textures start to fade into the distant textures.
+    pub detail_fade_start: f32,
+    /// The distance from the camera beyond which only the distant textures are used and the normal maps are no longer applied.
+    pub detail_fade_end: f32,
+    /// The factor which the [TerrainLayer::tiling] is multiplied with for the distant textures.
+    pub distant_tiling_factor: f32,
+    /// Render states.
+    pub render_states: RenderStates,
+    /// The lighting model used when rendering this material
+    pub lighting_model: LightingModel,
+}
+
+impl TerrainMaterial {
+    /// The maximum number of [TerrainLayer]s.
+    pub const MAX_LAYERS: usize = 8;
+}
+
+impl Material for TerrainMaterial {
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::TerrainMaterial(
+            self.albedo_textures.is_some(),
+            self.normal_textures.is_some(),
+            self.roughness_textures.is_some(),
+            self.splat_maps.is_some(),
+        )
+    }
+
+    fn fragment_shader_source(&self, lights: &[&dyn Light]) -> String {
+        let mut output = lights_shader_source(lights, self.lighting_model);
+        output.push_str(&format!("#define MAX_LAYERS {}\n", Self::MAX_LAYERS));
+        if self.albedo_textures.is_some() {
+            output.push_str("#define USE_ALBEDO_TEXTURES;\n");
+        }
+        if self.normal_textures.is_some() {
+            output.push_str("#define USE_NORMAL_TEXTURES;\n");
+        }
+        if self.roughness_textures.is_some() {
+            output.push_str("#define USE_ROUGHNESS_TEXTURES;\n");
+        }
+        if self.splat_maps.is_some() {
+            output.push_str("#define USE_SPLAT_MAPS;\n");
+        }
+        output.push_str(ToneMapping::fragment_shader_source());
+        output.push_str(ColorMapping::fragment_shader_source());
+        output.push_str(include_str!("shaders/terrain_material.frag"));
+        output
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            position: true,
+            normal: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        program.use_uniform_if_required("cameraPosition", camera.position());
+        for (i, light) in lights.iter().enumerate() {
+            light.use_uniforms(program, i as u32);
+        }
+
+        let layers = &self.layers[..self.layers.len().min(Self::MAX_LAYERS)];
+        let mut albedos = vec![Vec4::zero(); Self::MAX_LAYERS];
+        let mut parameters = vec![Vec4::zero(); Self::MAX_LAYERS];
+        let mut height_ranges = vec![Vec4::zero(); Self::MAX_LAYERS];
+        let mut slope_ranges = vec![Vec4::zero(); Self::MAX_LAYERS];
+        for (i, layer) in layers.iter().enumerate() {
+            albedos[i] = layer.albedo.to_linear_srgb();
+            parameters[i] = vec4(
+                layer.metallic,
+                layer.roughness,
+                layer.normal_scale,
+                layer.tiling,
+            );
+            if let Some((min, max)) = layer.height_range {
+                height_ranges[i] = vec4(min, max, layer.height_blend, 1.0);
+            }
+            if let Some((min, max)) = layer.slope_range {
+                slope_ranges[i] = vec4(min.0, max.0, layer.slope_blend.0, 1.0);
+            }
+        }
+        program.use_uniform("layerCount", layers.len().max(1) as i32);
+        program.use_uniform_array("layerAlbedo", &albedos);
+        if program.requires_uniform("layerParameters") {
+            program.use_uniform_array("layerParameters", &parameters);
+        }
+        program.use_uniform_array("layerHeightRange", &height_ranges);
+        program.use_uniform_array("layerSlopeRange", &slope_ranges);
+        program.use_uniform_if_required("detailFadeStart", self.detail_fade_start);
+        program.use_uniform_if_required("detailFadeEnd", self.detail_fade_end);
+        program.use_uniform_if_required("distantTilingFactor", self.distant_tiling_factor);
+
+        if program.requires_uniform("albedoTextures") {
+            if let Some(ref textures) = self.albedo_textures {
+                program.use_texture_array("albedoTextures", textures);
+            }
+        }
+        if program.requires_uniform("normalTextures") {
+            if let Some(ref textures) = self.normal_textures {
+                program.use_texture_array("normalTextures", textures);
+            }
+        }
+        if program.requires_uniform("roughnessTextures") {
+            if let Some(ref textures) = self.roughness_textures {
+                program.use_texture_array("roughnessTextures", textures);
+            }
+        }
+        if program.requires_uniform("splatMaps") {
+            if let Some(ref textures) = self.splat_maps {
+                program.use_uniform("splatMapCount", textures.depth() as i32);
+                program.use_uniform("splatMapOffset", self.splat_map_offset);
+                program.use_uniform("splatMapSize", self.splat_map_size);
+                program.use_texture_array("splatMaps", textures);
+            }
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        self.render_states
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Opaque
+    }
+}
+
+impl Default for TerrainMaterial {
+    fn default() -> Self {
+        Self {
+            layers: vec![TerrainLayer::default()],
+            albedo_textures: None,
+            normal_textures: None,
+            roughness_textures: None,
+            splat_maps: None,
+            splat_map_offset: vec2(0.0, 0.0),
+            splat_map_size: vec2(1.0, 1.0),
+            detail_fade_start: 50.0,
+            detail_fade_end: 200.0,
+            distant_tiling_factor: 0.2,
+            render_states: RenderStates::default(),
+            lighting_model: LightingModel::Blinn,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// A material that renders a [Geometry] with toon shading, also called cel shading, which is a non-photorealistic style with flat colors and hard edges between light and shadow.
+/// The diffuse light from each light is quantized into a number of [bands](ToonMaterial::bands) or mapped through a [ramp texture](ToonMaterial::ramp_texture)
+/// and the specular highlight and the optional rim light have hard edges.
+/// This material is affected by lights, including the shadows of lights with a shadow map, however, only [DirectionalLight], [SpotLight], [PointLight], [ClusteredLights] and [DynamicLights] are quantized
+/// while other lights, for example an [AmbientLight], contribute as usual.
+///
+/// Combine with an [OutlineEffect] to draw outlines around the objects.
+///
+#[derive(Clone)]
+pub struct ToonMaterial {
+    /// Albedo base color, also called diffuse color.
+    pub albedo: Srgba,
+    /// Texture with albedo base colors, also called diffuse color.
+    /// The colors are assumed to be in linear sRGB (`RgbU8`), linear sRGB with an alpha channel (`RgbaU8`) or HDR color space.
+    pub albedo_texture: Option<Texture2DRef>,
+    /// The number of discrete bands that the diffuse light is quantized into. Only used if no [Self::ramp_texture] is specified.
+    pub bands: u32,
+    /// A texture which maps the diffuse light intensity, in the range `[0..1]`, to the light color.
+    /// The texture is sampled horizontally at the light intensity and vertically in the middle, so a one pixel high texture with [Interpolation::Nearest] gives hard edges between the bands.
+    pub ramp_texture: Option<Texture2DRef>,
+    /// The color of the specular highlight. Use black to disable the specular highlight.
+    pub specular_color: Srgba,
+    /// The glossiness of the surface. The higher the value, the smaller the specular highlight.
+    pub glossiness: f32,
+    /// The color of the rim light, ie. the light at the edges of the object as seen from the camera on the side facing the light. Use black to disable the rim light.
+    pub rim_color: Srgba,
+    /// A value in the range `[0..1]` specifying how far from the edges the rim light starts. The higher the value, the thinner the rim light.
+    pub rim_amount: f32,
+    /// Render states.
+    pub render_states: RenderStates,
+}
+
+impl ToonMaterial {
+    ///
+    /// Constructs a new toon material from a [CpuMaterial] where the albedo color and texture are used.
+    ///
+    pub fn new(context: &Context, cpu_material: &CpuMaterial) -> Self {
+        let albedo_texture =
+            cpu_material
+                .albedo_texture
+                .as_ref()
+                .map(|cpu_texture| match &cpu_texture.data {
+                    TextureData::RgbU8(_) | TextureData::RgbaU8(_) => {
+                        let mut cpu_texture = cpu_texture.clone();
+                        cpu_texture.data.to_linear_srgb();
+                        Texture2DRef::from_cpu_texture(context, &cpu_texture)
+                    }
+                    _ => Texture2DRef::from_cpu_texture(context, cpu_texture),
+                });
+        Self {
+            albedo: cpu_material.albedo,
+            albedo_texture,
+            ..Default::default()
+        }
+    }
+}
+
+impl FromCpuMaterial for ToonMaterial {
+    fn from_cpu_material(context: &Context, cpu_material: &CpuMaterial) -> Self {
+        Self::new(context, cpu_material)
+    }
+}
+
+impl Material for ToonMaterial {
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::ToonMaterial(self.albedo_texture.is_some(), self.ramp_texture.is_some())
+    }
+
+    fn fragment_shader_source(&self, lights: &[&dyn Light]) -> String {
+        // The toon shading must be defined before the lights since it changes the lighting calculations
+        let mut output = "#define USE_TOON\n".to_string();
+        if self.ramp_texture.is_some() {
+            output.push_str("#define USE_TOON_RAMP\n");
+        }
+        output.push_str(&lights_shader_source(lights, LightingModel::Blinn));
+        if self.albedo_texture.is_some() {
+            output.push_str("in vec2 uvs;\n#define USE_ALBEDO_TEXTURE;\n");
+        }
+        output.push_str(ToneMapping::fragment_shader_source());
+        output.push_str(ColorMapping::fragment_shader_source());
+        output.push_str(include_str!("shaders/toon_material.frag"));
+        output
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            position: true,
+            normal: true,
+            color: true,
+            uv: self.albedo_texture.is_some(),
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        if !lights.is_empty() {
+            program.use_uniform_if_required("cameraPosition", camera.position());
+            for (i, light) in lights.iter().enumerate() {
+                light.use_uniforms(program, i as u32);
+            }
+            program.use_uniform_if_required("bands", self.bands.max(1) as f32);
+            program.use_uniform_if_required(
+                "specularColor",
+                self.specular_color.to_linear_srgb().truncate(),
+            );
+            program.use_uniform_if_required("glossiness", self.glossiness);
+            program.use_uniform_if_required("rimColor", self.rim_color.to_linear_srgb().truncate());
+            program.use_uniform_if_required("rimAmount", self.rim_amount);
+            if program.requires_uniform("toonRamp") {
+                if let Some(ref texture) = self.ramp_texture {
+                    program.use_texture("toonRamp", texture);
+                }
+            }
+        }
+        program.use_uniform("albedo", self.albedo.to_linear_srgb());
+        if program.requires_uniform("albedoTexture") {
+            if let Some(ref texture) = self.albedo_texture {
+                program.use_uniform("albedoTexTransform", texture.transformation);
+                program.use_texture("albedoTexture", texture);
+            }
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        self.render_states
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Opaque
+    }
+}
+
+impl Default for ToonMaterial {
+    fn default() -> Self {
+        Self {
+            albedo: Srgba::WHITE,
+            albedo_texture: None,
+            bands: 3,
+            ramp_texture: None,
+            specular_color: Srgba::WHITE,
+            glossiness: 16.0,
+            rim_color: Srgba::BLACK,
+            rim_amount: 0.7,
+            render_states: RenderStates::default(),
+        }
+    }
+}
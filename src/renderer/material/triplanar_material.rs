@@ -0,0 +1,191 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// A physically-based material similar to [PhysicalMaterial], except that the textures are projected onto the [Geometry] along the three axes instead of being sampled using uv coordinates,
+/// which is also called triplanar mapping.
+/// This is useful for geometries without usable uv coordinates, for example scanned data, procedurally generated meshes or meshes generated from a [VoxelGrid],
+/// since only the `position` and `normal` [FragmentAttributes] are required.
+///
+/// The textures are projected along the world axes by default, use the [TriplanarMaterial::transformation] to project along other axes, for example the axes of the object.
+/// Each texture is sampled three times per fragment and the results are blended according to the surface normal.
+///
+#[derive(Clone)]
+pub struct TriplanarMaterial {
+    /// Name.
+    pub name: String,
+    /// Albedo base color, also called diffuse color.
+    pub albedo: Srgba,
+    /// Texture with albedo base colors, also called diffuse color.
+    /// The colors are assumed to be in linear sRGB (`RgbU8`), linear sRGB with an alpha channel (`RgbaU8`) or HDR color space.
+    pub albedo_texture: Option<Texture2DRef>,
+    /// A value in the range `[0..1]` specifying how metallic the surface is.
+    pub metallic: f32,
+    /// A value in the range `[0..1]` specifying how rough the surface is.
+    pub roughness: f32,
+    /// Texture containing the metallic and roughness parameters which are multiplied with the [Self::metallic] and [Self::roughness] values in the shader.
+    /// The metallic values are sampled from the blue channel and the roughness from the green channel.
+    pub metallic_roughness_texture: Option<Texture2DRef>,
+    /// A scalar multiplier applied to each normal vector of the [Self::normal_texture].
+    pub normal_scale: f32,
+    /// A tangent space normal map, also known as bump map.
+    pub normal_texture: Option<Texture2DRef>,
+    /// The number of times the textures are repeated per unit length along each axis.
+    pub texture_scale: f32,
+    /// Controls how sharp the transition between the three projections is. The higher the value, the sharper the transition.
+    pub blend_sharpness: f32,
+    /// The transformation from world space to the space in which the textures are projected along the axes.
+    /// Set this to the inverse of the transformation of the object to project along the axes of the object, such that the textures follow the object when it moves.
+    pub transformation: Mat4,
+    /// Render states.
+    pub render_states: RenderStates,
+    /// The lighting model used when rendering this material
+    pub lighting_model: LightingModel,
+}
+
+impl TriplanarMaterial {
+    ///
+    /// Constructs a new triplanar material from a [CpuMaterial] where the albedo, metallic, roughness and normal parameters and textures are used.
+    /// If the input contains an [CpuMaterial::occlusion_metallic_roughness_texture], this texture is used as [TriplanarMaterial::metallic_roughness_texture].
+    ///
+    pub fn new(context: &Context, cpu_material: &CpuMaterial) -> Self {
+        let albedo_texture =
+            cpu_material
+                .albedo_texture
+                .as_ref()
+                .map(|cpu_texture| match &cpu_texture.data {
+                    TextureData::RgbU8(_) | TextureData::RgbaU8(_) => {
+                        let mut cpu_texture = cpu_texture.clone();
+                        cpu_texture.data.to_linear_srgb();
+                        Texture2DRef::from_cpu_texture(context, &cpu_texture)
+                    }
+                    _ => Texture2DRef::from_cpu_texture(context, cpu_texture),
+                });
+        let metallic_roughness_texture = cpu_material
+            .occlusion_metallic_roughness_texture
+            .as_ref()
+            .or(cpu_material.metallic_roughness_texture.as_ref())
+            .map(|cpu_texture| Texture2DRef::from_cpu_texture(context, cpu_texture));
+        let normal_texture = cpu_material
+            .normal_texture
+            .as_ref()
+            .map(|cpu_texture| Texture2DRef::from_cpu_texture(context, cpu_texture));
+        Self {
+            name: cpu_material.name.clone(),
+            albedo: cpu_material.albedo,
+            albedo_texture,
+            metallic: cpu_material.metallic,
+            roughness: cpu_material.roughness,
+            metallic_roughness_texture,
+            normal_scale: cpu_material.normal_scale,
+            normal_texture,
+            lighting_model: cpu_material.lighting_model,
+            ..Default::default()
+        }
+    }
+}
+
+impl FromCpuMaterial for TriplanarMaterial {
+    fn from_cpu_material(context: &Context, cpu_material: &CpuMaterial) -> Self {
+        Self::new(context, cpu_material)
+    }
+}
+
+impl Material for TriplanarMaterial {
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::TriplanarMaterial(
+            self.albedo_texture.is_some(),
+            self.metallic_roughness_texture.is_some(),
+            self.normal_texture.is_some(),
+        )
+    }
+
+    fn fragment_shader_source(&self, lights: &[&dyn Light]) -> String {
+        let mut output = lights_shader_source(lights, self.lighting_model);
+        if self.albedo_texture.is_some() {
+            output.push_str("#define USE_ALBEDO_TEXTURE;\n");
+        }
+        if self.metallic_roughness_texture.is_some() {
+            output.push_str("#define USE_METALLIC_ROUGHNESS_TEXTURE;\n");
+        }
+        if self.normal_texture.is_some() {
+            output.push_str("#define USE_NORMAL_TEXTURE;\n");
+        }
+        output.push_str(ToneMapping::fragment_shader_source());
+        output.push_str(ColorMapping::fragment_shader_source());
+        output.push_str(include_str!("shaders/triplanar_material.frag"));
+        output
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            position: true,
+            normal: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, camera: &Camera, lights: &[&dyn Light]) {
+        camera.tone_mapping.use_uniforms(program);
+        camera.color_mapping.use_uniforms(program);
+        if !lights.is_empty() {
+            program.use_uniform_if_required("cameraPosition", camera.position());
+            for (i, light) in lights.iter().enumerate() {
+                light.use_uniforms(program, i as u32);
+            }
+            program.use_uniform_if_required("metallic", self.metallic);
+            program.use_uniform_if_required("roughness", self.roughness);
+            if program.requires_uniform("metallicRoughnessTexture") {
+                if let Some(ref texture) = self.metallic_roughness_texture {
+                    program.use_uniform("metallicRoughnessTexTransform", texture.transformation);
+                    program.use_texture("metallicRoughnessTexture", texture);
+                }
+            }
+            if program.requires_uniform("normalTexture") {
+                if let Some(ref texture) = self.normal_texture {
+                    program.use_uniform("normalTexTransform", texture.transformation);
+                    program.use_uniform("normalScale", self.normal_scale);
+                    program.use_texture("normalTexture", texture);
+                }
+            }
+        }
+        program.use_uniform_if_required("triplanarTransformation", self.transformation);
+        program.use_uniform_if_required("textureScale", self.texture_scale);
+        program.use_uniform_if_required("blendSharpness", self.blend_sharpness);
+        program.use_uniform("albedo", self.albedo.to_linear_srgb());
+        if program.requires_uniform("albedoTexture") {
+            if let Some(ref texture) = self.albedo_texture {
+                program.use_uniform("albedoTexTransform", texture.transformation);
+                program.use_texture("albedoTexture", texture);
+            }
+        }
+    }
+
+    fn render_states(&self) -> RenderStates {
+        self.render_states
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Opaque
+    }
+}
+
+impl Default for TriplanarMaterial {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            albedo: Srgba::WHITE,
+            albedo_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_scale: 1.0,
+            normal_texture: None,
+            texture_scale: 1.0,
+            blend_sharpness: 4.0,
+            transformation: Mat4::identity(),
+            render_states: RenderStates::default(),
+            lighting_model: LightingModel::Blinn,
+        }
+    }
+}
@@ -15,6 +15,10 @@ macro_rules! impl_object_body {
         fn material_type(&self) -> MaterialType {
             self.$inner().material_type()
         }
+
+        fn uses_material_extensions(&self) -> bool {
+            self.$inner().uses_material_extensions()
+        }
     };
 }
 
@@ -38,6 +42,10 @@ mod skybox;
 #[doc(inline)]
 pub use skybox::*;
 
+mod procedural_sky;
+#[doc(inline)]
+pub use procedural_sky::*;
+
 mod imposters;
 #[doc(inline)]
 pub use imposters::*;
@@ -72,6 +80,14 @@ pub trait Object: Geometry {
     /// Returns the type of material applied to this object.
     ///
     fn material_type(&self) -> MaterialType;
+
+    ///
+    /// Returns whether the material applied to this object uses the material extensions of the geometry pass, see [Material::uses_material_extensions].
+    /// The default implementation returns `false`.
+    ///
+    fn uses_material_extensions(&self) -> bool {
+        false
+    }
 }
 
 use std::ops::Deref;
@@ -107,4 +123,8 @@ impl<T: Object> Object for std::sync::RwLock<T> {
     fn material_type(&self) -> MaterialType {
         self.read().unwrap().material_type()
     }
+
+    fn uses_material_extensions(&self) -> bool {
+        self.read().unwrap().uses_material_extensions()
+    }
 }
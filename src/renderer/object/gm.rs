@@ -39,7 +39,6 @@ impl<G: Geometry + Clone, M: Material + Clone> Clone for Gm<G, M> {
     }
 }
 
-use std::ops::Deref;
 impl<G: Geometry, M: Material> std::ops::Deref for Gm<G, M> {
     type Target = G;
     fn deref(&self) -> &Self::Target {
@@ -54,7 +53,57 @@ impl<G: Geometry, M: Material> std::ops::DerefMut for Gm<G, M> {
 }
 
 impl<G: Geometry, M: Material> Geometry for Gm<G, M> {
-    impl_geometry_body!(deref);
+    fn draw(
+        &self,
+        camera: &Camera,
+        program: &Program,
+        render_states: RenderStates,
+        attributes: FragmentAttributes,
+    ) {
+        self.geometry
+            .draw(camera, program, render_states, attributes)
+    }
+
+    fn vertex_shader_source(&self, required_attributes: FragmentAttributes) -> String {
+        self.geometry.vertex_shader_source(required_attributes)
+    }
+
+    fn id(&self, required_attributes: FragmentAttributes) -> GeometryId {
+        self.geometry.id(required_attributes)
+    }
+
+    fn render_with_material(
+        &self,
+        material: &dyn Material,
+        camera: &Camera,
+        lights: &[&dyn Light],
+    ) {
+        self.geometry.render_with_material(material, camera, lights)
+    }
+
+    fn render_with_effect(
+        &self,
+        material: &dyn Effect,
+        camera: &Camera,
+        lights: &[&dyn Light],
+        color_texture: Option<ColorTexture>,
+        depth_texture: Option<DepthTexture>,
+    ) {
+        self.geometry
+            .render_with_effect(material, camera, lights, color_texture, depth_texture)
+    }
+
+    fn aabb(&self) -> AxisAlignedBoundingBox {
+        self.geometry.aabb()
+    }
+
+    fn alpha_mask(&self) -> Option<AlphaMask> {
+        self.material.alpha_mask()
+    }
+
+    fn vertex_deformation(&self) -> Option<VertexDeformation> {
+        self.material.vertex_deformation()
+    }
 
     fn animate(&mut self, time: f32) {
         self.geometry.animate(time)
@@ -69,4 +118,8 @@ impl<G: Geometry, M: Material> Object for Gm<G, M> {
     fn material_type(&self) -> MaterialType {
         self.material.material_type()
     }
+
+    fn uses_material_extensions(&self) -> bool {
+        self.material.uses_material_extensions()
+    }
 }
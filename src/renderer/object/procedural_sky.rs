@@ -0,0 +1,350 @@
+use crate::core::*;
+use crate::renderer::*;
+
+///
+/// An analytic model of the light scattered in a clear sky by the atmosphere (the Preetham model), driven by the direction of the sun and the turbidity of the atmosphere.
+/// Use [ProceduralSky::cube_map] to create a cube map which can be shown using a [Skybox] (see [Skybox::new_procedural])
+/// and used as the environment map of an [Environment] or [AmbientLight::new_with_environment].
+/// The sun itself is best represented by a [DirectionalLight] which can be kept synchronized with the sky using [ProceduralSky::update_directional_light].
+///
+/// The model is only valid when the sun is above the horizon and for a turbidity between 2 and 10.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProceduralSky {
+    /// The direction from the ground towards the sun.
+    pub sun_direction: Vec3,
+    /// The amount of haze in the atmosphere, where 2 is a very clear sky, 3 is a clear sky and 10 is a hazy sky.
+    pub turbidity: f32,
+    /// The color of the ground, which is seen below the horizon and lit by the sun and the sky.
+    pub ground_albedo: Srgba,
+    /// The intensity of the sky, ie. the luminance of the sky in kcd/m² is multiplied by this value.
+    pub intensity: f32,
+    /// The intensity of the sun before it is attenuated by the atmosphere, see [ProceduralSky::sun_color].
+    pub sun_intensity: f32,
+    /// The angular radius of the sun disk shown in the sky.
+    /// Use zero to not show the sun, for example when using the sky as an environment map together with a [DirectionalLight] representing the sun.
+    pub sun_angular_radius: Radians,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        Self {
+            sun_direction: vec3(0.5, 0.5, 0.5).normalize(),
+            turbidity: 3.0,
+            ground_albedo: Srgba::new_opaque(80, 80, 80),
+            intensity: 0.05,
+            sun_intensity: 3.0,
+            sun_angular_radius: degrees(0.5).into(),
+        }
+    }
+}
+
+impl ProceduralSky {
+    ///
+    /// Renders the sky into a new cube map where each side has the given texture size.
+    /// The colors in the cube map are linear and mip maps are generated, so it can be used directly as the environment map of an [Environment].
+    ///
+    pub fn cube_map(&self, context: &Context, texture_size: u32) -> TextureCubeMap {
+        let mut cube_map = TextureCubeMap::new_empty::<[f16; 4]>(
+            context,
+            texture_size,
+            texture_size,
+            Interpolation::Linear,
+            Interpolation::Linear,
+            Some(Interpolation::Linear),
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+            Wrapping::ClampToEdge,
+        );
+        let viewport = Viewport::new_at_origo(texture_size, texture_size);
+        for side in CubeMapSide::iter() {
+            cube_map
+                .as_color_target(&[side], None)
+                .clear(ClearState::default())
+                .apply_screen_material(
+                    &ProceduralSkyMaterial { sky: self, side },
+                    &Camera::new_2d(viewport),
+                    &[],
+                );
+        }
+        cube_map
+    }
+
+    ///
+    /// Returns the color of the sun light after it has travelled through the atmosphere,
+    /// which is more red when the sun is close to the horizon or the turbidity is high.
+    ///
+    pub fn sun_color(&self) -> Srgba {
+        let transmittance = self.sun_transmittance();
+        let encode = |c: f32| {
+            let c = if c < 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            };
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        Srgba::new_opaque(
+            encode(transmittance.x),
+            encode(transmittance.y),
+            encode(transmittance.z),
+        )
+    }
+
+    ///
+    /// Creates a new directional light representing the sun, see [ProceduralSky::update_directional_light].
+    ///
+    pub fn directional_light(&self, context: &Context) -> DirectionalLight {
+        DirectionalLight::new(
+            context,
+            self.sun_intensity,
+            self.sun_color(),
+            &-self.sun_direction,
+        )
+    }
+
+    ///
+    /// Updates the direction, color and intensity of the given directional light such that it represents the sun in this sky.
+    /// Call this each time the sky is changed to keep the light synchronized with the sky.
+    ///
+    pub fn update_directional_light(&self, light: &mut DirectionalLight) {
+        light.direction = -self.sun_direction;
+        light.color = self.sun_color();
+        light.intensity = self.sun_intensity;
+    }
+
+    ///
+    /// The fraction of the sun light at the wavelengths of red, green and blue which is transmitted through the atmosphere,
+    /// taking Rayleigh scattering by molecules and Mie scattering by aerosols into account.
+    ///
+    fn sun_transmittance(&self) -> Vec3 {
+        let theta = self.sun_zenith_angle();
+        // The relative optical mass of the air the light travels through
+        let theta_degrees = theta.to_degrees();
+        let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta_degrees).powf(-1.253));
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |wavelength: f32| {
+            let rayleigh = (-0.008735 * wavelength.powf(-4.08) * air_mass).exp();
+            let aerosol = (-beta * wavelength.powf(-1.3) * air_mass).exp();
+            rayleigh * aerosol
+        };
+        // The wavelengths of red, green and blue in micrometers
+        vec3(
+            transmittance(0.68),
+            transmittance(0.55),
+            transmittance(0.44),
+        )
+    }
+
+    ///
+    /// The angle between the zenith and the sun, limited to the horizon.
+    ///
+    fn sun_zenith_angle(&self) -> f32 {
+        self.sun_direction
+            .normalize()
+            .y
+            .clamp(0.0, 1.0)
+            .acos()
+            .min(0.5 * std::f32::consts::PI - 0.001)
+    }
+}
+
+///
+/// The coefficients of the Perez sky luminance distribution function for the luminance (Y) and the chromaticity (x and y) respectively.
+///
+fn perez_coefficients(turbidity: f32) -> [Vec3; 5] {
+    let t = turbidity;
+    [
+        vec3(
+            0.1787 * t - 1.4630,
+            -0.0193 * t - 0.2592,
+            -0.0167 * t - 0.2608,
+        ),
+        vec3(
+            -0.3554 * t + 0.4275,
+            -0.0665 * t + 0.0008,
+            -0.0950 * t + 0.0092,
+        ),
+        vec3(
+            -0.0227 * t + 5.3251,
+            -0.0004 * t + 0.2125,
+            -0.0079 * t + 0.2102,
+        ),
+        vec3(
+            0.1206 * t - 2.5771,
+            -0.0641 * t - 0.8989,
+            -0.0441 * t - 1.6537,
+        ),
+        vec3(
+            -0.0670 * t + 0.3703,
+            -0.0033 * t + 0.0452,
+            -0.0109 * t + 0.0529,
+        ),
+    ]
+}
+
+///
+/// The luminance (Y) in kcd/m² and chromaticity (x and y) at the zenith.
+///
+fn zenith_color(turbidity: f32, sun_zenith_angle: f32) -> Vec3 {
+    let (t, theta) = (turbidity, sun_zenith_angle);
+    let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+    let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+    let chromaticity = |coefficients: [[f32; 4]; 3]| {
+        let polynomial =
+            |c: [f32; 4]| c[0] * theta.powi(3) + c[1] * theta.powi(2) + c[2] * theta + c[3];
+        t * t * polynomial(coefficients[0])
+            + t * polynomial(coefficients[1])
+            + polynomial(coefficients[2])
+    };
+    let x = chromaticity([
+        [0.00166, -0.00375, 0.00209, 0.0],
+        [-0.02903, 0.06377, -0.03202, 0.00394],
+        [0.11693, -0.21196, 0.06052, 0.25886],
+    ]);
+    let y = chromaticity([
+        [0.00275, -0.00610, 0.00317, 0.0],
+        [-0.04214, 0.08970, -0.04153, 0.00516],
+        [0.15346, -0.26756, 0.06670, 0.26688],
+    ]);
+    vec3(luminance, x, y)
+}
+
+struct ProceduralSkyMaterial<'a> {
+    sky: &'a ProceduralSky,
+    side: CubeMapSide,
+}
+
+impl Material for ProceduralSkyMaterial<'_> {
+    fn fragment_shader_source(&self, _lights: &[&dyn Light]) -> String {
+        format!(
+            "{}{}",
+            include_str!("../../core/shared.frag"),
+            include_str!("shaders/procedural_sky.frag")
+        )
+    }
+
+    fn id(&self) -> EffectMaterialId {
+        EffectMaterialId::ProceduralSkyMaterial
+    }
+
+    fn fragment_attributes(&self) -> FragmentAttributes {
+        FragmentAttributes {
+            uv: true,
+            ..FragmentAttributes::NONE
+        }
+    }
+
+    fn use_uniforms(&self, program: &Program, _camera: &Camera, _lights: &[&dyn Light]) {
+        let sky = self.sky;
+        let sun_zenith_angle = sky.sun_zenith_angle();
+        let sun_direction = sky.sun_direction.normalize();
+        let coefficients = perez_coefficients(sky.turbidity);
+        // The Perez function evaluated at the zenith, which is used to normalize the distribution such that it equals the zenith color at the zenith
+        let perez_zenith = vec3(0usize, 1, 2).map(|i| {
+            let [a, b, c, d, e] = coefficients.map(|v| v[i]);
+            (1.0 + a * b.exp())
+                * (1.0 + c * (d * sun_zenith_angle).exp() + e * sun_zenith_angle.cos().powi(2))
+        });
+        let cos_angular_radius = sky.sun_angular_radius.0.cos();
+        // The radiance of the sun disk which gives the irradiance of the sun when integrated over the solid angle of the disk
+        let sun_solid_angle = 2.0 * std::f32::consts::PI * (1.0 - cos_angular_radius);
+        let sun_irradiance = sky.sun_transmittance() * sky.sun_intensity;
+
+        program.use_uniform("direction", self.side.direction());
+        program.use_uniform("up", self.side.up());
+        program.use_uniform("sunDirection", sun_direction);
+        program.use_uniform("perezA", coefficients[0]);
+        program.use_uniform("perezB", coefficients[1]);
+        program.use_uniform("perezC", coefficients[2]);
+        program.use_uniform("perezD", coefficients[3]);
+        program.use_uniform("perezE", coefficients[4]);
+        program.use_uniform("perezZenith", perez_zenith);
+        program.use_uniform("zenithColor", zenith_color(sky.turbidity, sun_zenith_angle));
+        program.use_uniform("intensity", sky.intensity);
+        program.use_uniform("sunIrradiance", sun_irradiance);
+        program.use_uniform(
+            "sunRadiance",
+            if sun_solid_angle > 0.0 {
+                sun_irradiance / sun_solid_angle
+            } else {
+                Vec3::zero()
+            },
+        );
+        program.use_uniform("sunCosAngularRadius", cos_angular_radius);
+        program.use_uniform(
+            "groundAlbedo",
+            sky.ground_albedo.to_linear_srgb().truncate(),
+        );
+    }
+
+    fn render_states(&self) -> RenderStates {
+        RenderStates::default()
+    }
+
+    fn material_type(&self) -> MaterialType {
+        MaterialType::Opaque
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sun_color(sun_direction: Vec3, turbidity: f32) -> Srgba {
+        ProceduralSky {
+            sun_direction,
+            turbidity,
+            ..Default::default()
+        }
+        .sun_color()
+    }
+
+    #[test]
+    fn sun_color_at_zenith() {
+        let color = sun_color(vec3(0.0, 1.0, 0.0), 2.0);
+        assert!(color.r >= color.g && color.g >= color.b);
+        assert!(color.b > 180);
+        assert_eq!(color.a, 255);
+    }
+
+    #[test]
+    fn sun_color_is_redder_towards_the_horizon() {
+        let mut previous = sun_color(vec3(0.0, 1.0, 0.0), 3.0);
+        for elevation in [60.0, 30.0, 10.0, 0.0] {
+            let elevation = degrees(elevation);
+            let color = sun_color(vec3(elevation.cos(), elevation.sin(), 0.0), 3.0);
+            assert!(color.r <= previous.r && color.g <= previous.g && color.b <= previous.b);
+            assert!(
+                color.r as f32 / color.b.max(1) as f32
+                    >= previous.r as f32 / previous.b.max(1) as f32
+            );
+            previous = color;
+        }
+        assert!(previous.r > 2 * previous.b);
+    }
+
+    #[test]
+    fn sun_color_is_darker_with_higher_turbidity() {
+        let direction = vec3(1.0, 1.0, 0.0);
+        let clear = sun_color(direction, 2.0);
+        let hazy = sun_color(direction, 10.0);
+        assert!(hazy.r < clear.r && hazy.g < clear.g && hazy.b < clear.b);
+    }
+
+    #[test]
+    fn sun_color_below_the_horizon() {
+        assert_eq!(
+            sun_color(vec3(1.0, -0.5, 0.0), 3.0),
+            sun_color(vec3(1.0, 0.0, 0.0), 3.0)
+        );
+    }
+
+    #[test]
+    fn sun_color_with_unnormalized_direction() {
+        assert_eq!(
+            sun_color(vec3(2.0, 2.0, 0.0), 3.0),
+            sun_color(vec3(1.0, 1.0, 0.0), 3.0)
+        );
+    }
+}
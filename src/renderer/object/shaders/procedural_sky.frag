@@ -0,0 +1,67 @@
+uniform vec3 direction;
+uniform vec3 up;
+uniform vec3 sunDirection;
+uniform vec3 perezA;
+uniform vec3 perezB;
+uniform vec3 perezC;
+uniform vec3 perezD;
+uniform vec3 perezE;
+uniform vec3 perezZenith;
+uniform vec3 zenithColor;
+uniform float intensity;
+uniform vec3 sunIrradiance;
+uniform vec3 sunRadiance;
+uniform float sunCosAngularRadius;
+uniform vec3 groundAlbedo;
+
+in vec2 uvs;
+
+layout (location = 0) out vec4 outColor;
+
+// The Perez sky luminance distribution function for the luminance and chromaticity
+vec3 perez(float cos_theta, float gamma, float cos_gamma)
+{
+    return (1.0 + perezA * exp(perezB / cos_theta)) * (1.0 + perezC * exp(perezD * gamma) + perezE * cos_gamma * cos_gamma);
+}
+
+// Converts a color given as luminance and chromaticity (xyY) to linear sRGB
+vec3 xyY_to_rgb(vec3 Yxy)
+{
+    float Y = Yxy.x;
+    float x = Yxy.y;
+    float y = max(Yxy.z, 0.0001);
+    vec3 XYZ = vec3(x * Y / y, Y, (1.0 - x - y) * Y / y);
+    mat3 XYZ_to_rgb = mat3(
+        3.2406, -0.9689, 0.0557,
+        -1.5372, 1.8758, -0.2040,
+        -0.4986, 0.0415, 1.0570
+    );
+    return max(XYZ_to_rgb * XYZ, vec3(0.0));
+}
+
+vec3 sky_color(vec3 view_direction)
+{
+    float cos_theta = max(view_direction.y, 0.001);
+    float cos_gamma = clamp(dot(view_direction, sunDirection), -1.0, 1.0);
+    float gamma = acos(cos_gamma);
+    return intensity * xyY_to_rgb(zenithColor * perez(cos_theta, gamma, cos_gamma) / perezZenith);
+}
+
+void main()
+{
+    vec3 right = cross(direction, up);
+    vec3 N = normalize(up * (uvs.y - 0.5) * 2.0 + right * (uvs.x - 0.5) * 2.0 + direction);
+
+    vec3 color;
+    if (N.y < 0.0) {
+        // The ground reflects the light from the sun and from the sky, where the sky is approximated by the color at the zenith
+        vec3 irradiance = sunIrradiance * max(sunDirection.y, 0.0) + PI * sky_color(vec3(0.0, 1.0, 0.0));
+        color = groundAlbedo * irradiance / PI;
+    } else {
+        color = sky_color(N);
+        if (dot(N, sunDirection) >= sunCosAngularRadius) {
+            color += sunRadiance;
+        }
+    }
+    outColor = vec4(color, 1.0);
+}
@@ -16,17 +16,34 @@ out vec3 tang;
 out vec3 bitang;
 #endif
 
+#ifdef USE_VERTEX_DEFORMATION
+void deform_vertex(vec3 local_position, vec2 uv, inout vec3 world_position, inout vec3 world_normal);
+#endif
+
 void main()
 {
     vec4 worldPos = vec4(position, 1.);
-    pos = worldPos.xyz;
     uvs = worldPos.xz;
     col = vec4(1.0);
 #ifdef USE_NORMALS
     nor = normalize(normal);
+#endif
+#ifdef USE_VERTEX_DEFORMATION
+#ifdef USE_NORMALS
+    vec3 world_normal = nor;
+#else
+    vec3 world_normal = vec3(0.0);
+#endif
+    deform_vertex(position, uvs, worldPos.xyz, world_normal);
+#ifdef USE_NORMALS
+    nor = normalize(world_normal);
+#endif
+#endif
+#ifdef USE_NORMALS
     tang = cross(vec3(1.0, 0.0, 0.0), nor);
     bitang = cross(nor, tang);
 #endif
+    pos = worldPos.xyz;
     gl_Position = viewProjectionMatrix * worldPos;
     instance_id = gl_InstanceID;
 }
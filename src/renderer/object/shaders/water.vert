@@ -16,6 +16,10 @@ out vec3 pos;
 out vec4 col;
 flat out int instance_id;
 
+#ifdef USE_VERTEX_DEFORMATION
+void deform_vertex(vec3 local_position, vec2 uv, inout vec3 world_position, inout vec3 world_normal);
+#endif
+
 void main()
 {
     pos = position + offset;
@@ -51,9 +55,15 @@ void main()
             nor.z -= frequency * b.y;
         }
     }
+    uvs = pos.xz;
+
+#ifdef USE_VERTEX_DEFORMATION
+    vec3 world_normal = normalize(nor);
+    deform_vertex(position, uvs, pos, world_normal);
+    nor = world_normal;
+#endif
     
     gl_Position = viewProjection * vec4(pos, 1.);
-    uvs = pos.xz;
     col = vec4(1.0);
     instance_id = gl_InstanceID;
 }
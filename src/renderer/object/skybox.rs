@@ -77,6 +77,13 @@ impl Skybox {
         Self::new_with_texture(context, Arc::new(texture))
     }
 
+    ///
+    /// Creates a new skybox showing the given procedural sky, see [ProceduralSky::cube_map].
+    ///
+    pub fn new_procedural(context: &Context, sky: &ProceduralSky, texture_size: u32) -> Self {
+        Self::new_with_texture(context, Arc::new(sky.cube_map(context, texture_size)))
+    }
+
     ///
     /// Creates a new skybox with the given [TextureCubeMap].
     /// The colors are assumed to be in linear sRGB (`RgbU8`), linear sRGB with an alpha channel (`RgbaU8`) or HDR color space.
@@ -357,7 +357,8 @@ impl Geometry for TerrainPatch {
     ) {
         program.use_uniform("viewProjectionMatrix", camera.projection() * camera.view());
         program.use_vertex_attribute("position", &self.positions_buffer);
-        if attributes.normal || attributes.tangents {
+        // The normal is unused if the terrain is only affected by an ambient light, in which case the compiler removes the input
+        if (attributes.normal || attributes.tangents) && program.requires_attribute("normal") {
             program.use_vertex_attribute("normal", &self.normals_buffer);
         }
         program.draw_elements(render_states, camera.viewport(), &self.index_buffer);
@@ -28,9 +28,23 @@ macro_rules! bitfield_bit {
     };
 }
 
+///
+/// Recursive macro to count the number of bits used by multiple booleans
+///
+macro_rules! bitfield_count {
+    ($field:ident) => {
+        1
+    };
+
+    ($field:ident, $($fields:ident),+) => {
+        1 + bitfield_count!($($fields),+)
+    };
+}
+
 ///
 /// Generates a function accepting bit parameters, ordering from least significant bit to most significant bit
 /// bitfield_bit abstracts out the process of converting a tuple of bools into a single int literal
+/// An optional trailing value is placed in the bits following the boolean bits
 ///
 macro_rules! enum_bitfield {
     ($base_name:ident, $name:ident($($field:ident),+ $(,)?)) => {
@@ -43,6 +57,18 @@ macro_rules! enum_bitfield {
             )
         }
     };
+
+    ($base_name:ident, $name:ident($($field:ident),+; $value:ident)) => {
+        #[allow(non_snake_case)]
+        #[inline]
+        pub(crate) fn $name($($field: bool),+, $value: u8) -> Self {
+            Self(
+                Self::$base_name.0
+                    | bitfield_bit!($($field),+ << 0)
+                    | ($value << bitfield_count!($($field),+))
+            )
+        }
+    };
 }
 
 ///
@@ -131,39 +157,92 @@ impl GeometryId {
 #[open_enum]
 #[repr(u16)]
 pub enum EffectMaterialId {
-    LightingPassEffectBase = 0x5000, // To 0x503F
-    WaterEffectBase = 0x5800,        // To 0x583F
-    CopyEffectBase = 0x6000,         // To 0x603F
-    ScreenEffectBase = 0x6800,       // To 0x683F
-    FogEffectBase = 0x7000,          // To 0x703F
-    FxaaEffectBase = 0x7800,         // To 0x7838 (has holes)
-
-    ColorMaterialBase = 0x8000, // To 0x8001
-    DepthMaterial = 0x8002,
+    LightingPassEffectBase = 0x5000,    // To 0x53FF (has holes)
+    WaterEffectBase = 0x5800,           // To 0x583F
+    CopyEffectBase = 0x6000,            // To 0x603F
+    ContactShadowEffectBase = 0x6400,   // To 0x643F
+    ScreenEffectBase = 0x6800,          // To 0x683F
+    FogEffectBase = 0x7000,             // To 0x703F
+    VolumetricLightEffectBase = 0x7400, // To 0x743F
+    FxaaEffectBase = 0x7800,            // To 0x7838 (has holes)
+    OutlineEffectBase = 0x7C00,         // To 0x7C3F
+
     PositionMaterial = 0x8003,
     SkyboxMaterial = 0x8004,
     UVMaterial = 0x8005,
     NormalMaterialBase = 0x8006, // To 0x8007
-    IntersectionMaterial = 0x800B,
     IsosurfaceMaterial = 0x800C,
     ImpostersMaterial = 0x800D,
     BrdfMaterial = 0x800E,
     IrradianceMaterial = 0x800F,
-    ORMMaterialBase = 0x8010,              // To 0x8013
-    PhysicalMaterialBase = 0x8020,         // To 0x803F
-    DeferredPhysicalMaterialBase = 0x8040, // To 0x807F
+    ORMMaterialBase = 0x8010, // To 0x8013
     PrefilterMaterial = 0x8080,
+    CubeMapSampleMaterial = 0x8081,
+    ProceduralSkyMaterial = 0x8082,
+    ShadowCatcherMaterial = 0x8083,
+    ColorMaterialBase = 0x8084,        // To 0x8087
+    DepthMaterialBase = 0x8088,        // To 0x808B
+    IntersectionMaterialBase = 0x808C, // To 0x808F
+    ToonMaterialBase = 0x8090,         // To 0x8093
+    TriplanarMaterialBase = 0x8098,    // To 0x809F
+    TerrainMaterialBase = 0x80A0,      // To 0x80AF
+    SnippetMaterial = 0x9000,
+    DeferredPhysicalMaterialBase = 0xA000, // To 0xA1FF
+    PhysicalMaterialBase = 0xC000,         // To 0xFFFF
 }
 
 impl EffectMaterialId {
-    enum_effectfield!(LightingPassEffectBase, LightingPassEffect(...Default));
+    ///
+    /// The lighting pass is placed in the bits following the texture ids,
+    /// where the lighting pass with contact shadows and with material extensions each sets its own bit.
+    ///
+    #[allow(non_snake_case)]
+    #[inline]
+    pub(crate) fn LightingPassEffect(
+        color_texture: ColorTexture,
+        depth_texture: DepthTexture,
+        contact_shadows: bool,
+        material_extensions: bool,
+    ) -> Self {
+        Self(
+            Self::LightingPassEffectBase.0
+                | color_texture.id()
+                | depth_texture.id()
+                | bitfield_bit!(contact_shadows, material_extensions << 8),
+        )
+    }
     enum_effectfield!(WaterEffectBase, WaterEffect(...Default));
     enum_effectfield!(CopyEffectBase, CopyEffect(Option<...Default>));
+    enum_effectfield!(ContactShadowEffectBase, ContactShadowEffect(...Default));
     enum_effectfield!(ScreenEffectBase, ScreenEffect(Option<...Default>));
     enum_effectfield!(FogEffectBase, FogEffect(...Default));
+    enum_effectfield!(
+        VolumetricLightEffectBase,
+        VolumetricLightEffect(...Default)
+    );
     enum_effectfield!(FxaaEffectBase, FxaaEffect(color_texture: ColorTexture));
+    enum_effectfield!(OutlineEffectBase, OutlineEffect(...Default));
 
-    enum_bitfield!(ColorMaterialBase, ColorMaterial(texture));
+    enum_bitfield!(ColorMaterialBase, ColorMaterial(texture, alpha_cutout));
+    enum_bitfield!(DepthMaterialBase, DepthMaterial(alpha_mask, alpha_texture));
+    enum_bitfield!(
+        IntersectionMaterialBase,
+        IntersectionMaterial(alpha_mask, alpha_texture)
+    );
+    enum_bitfield!(ToonMaterialBase, ToonMaterial(albedo_texture, ramp_texture));
+    enum_bitfield!(
+        TriplanarMaterialBase,
+        TriplanarMaterial(albedo_texture, metallic_roughness_texture, normal_texture)
+    );
+    enum_bitfield!(
+        TerrainMaterialBase,
+        TerrainMaterial(
+            albedo_textures,
+            normal_textures,
+            roughness_textures,
+            splat_maps
+        )
+    );
     enum_bitfield!(NormalMaterialBase, NormalMaterial(normal_texture));
     enum_bitfield!(
         ORMMaterialBase,
@@ -177,6 +256,15 @@ impl EffectMaterialId {
             occlusion_texture,
             normal_texture,
             emissive_texture,
+            clearcoat,
+            clearcoat_normal_texture,
+            sheen,
+            specular,
+            transmission,
+            transmission_texture,
+            volume,
+            alpha_cutout,
+            height_texture,
         )
     );
     enum_bitfield!(
@@ -188,6 +276,9 @@ impl EffectMaterialId {
             normal_texture,
             emissive_texture,
             alpha_cutout,
+            clearcoat,
+            sheen,
+            specular,
         )
     );
 }
@@ -200,14 +291,27 @@ impl EffectMaterialId {
 #[open_enum]
 #[repr(u8)]
 pub enum LightId {
-    AmbientLightBase = 0x80,     // To 0x81
-    DirectionalLightBase = 0x82, // To 0x83
-    PointLight = 0x84,
-    SpotLightBase = 0x86, // To 0x87
+    RectAreaLight = 0x82,
+    DiskAreaLight = 0x83,
+    ClusteredLights = 0x84,
+    DynamicLights = 0x85,
+    LightProbeGrid = 0x86,
+    TransmissionBackground = 0x87,
+    PointLightBase = 0x88,       // To 0x8F
+    AmbientLightBase = 0x90,     // To 0x9F
+    DirectionalLightBase = 0xA0, // To 0xAF
+    SpotLightBase = 0xC0,        // To 0xDF
 }
 
 impl LightId {
-    enum_bitfield!(AmbientLightBase, AmbientLight(environment));
-    enum_bitfield!(DirectionalLightBase, DirectionalLight(shadow_texture));
-    enum_bitfield!(SpotLightBase, SpotLight(shadow_texture));
+    enum_bitfield!(AmbientLightBase, AmbientLight(environment; reflection_probes));
+    enum_bitfield!(
+        DirectionalLightBase,
+        DirectionalLight(shadow_texture, cascaded_shadow_texture; shadow_filter)
+    );
+    enum_bitfield!(PointLightBase, PointLight(shadow_texture; shadow_filter));
+    enum_bitfield!(
+        SpotLightBase,
+        SpotLight(shadow_texture, ies_profile, cookie; shadow_filter)
+    );
 }